@@ -19,7 +19,7 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize with configuration
     let data_dir = "./data/advanced_example".to_string();
-    let mut store = store::DocumentStore::new(data_dir).await?;
+    let store = store::DocumentStore::new(data_dir).await?;
     println!("✅ DocumentStore initialized\n");
 
     let db_name = "default";
@@ -37,6 +37,10 @@ async fn main() -> anyhow::Result<()> {
                    ("paradigm".to_string(), json!("systems"))].into(),
         tags: vec!["rust".to_string(), "programming".to_string()],
         vectorize: true,
+        vector: None,
+        model: None,
+        expires_at: None,
+        ttl_seconds: None,
     }).await?;
 
     let cargo_doc = store.add_document(db_name, table_name, StoreDocumentRequest {
@@ -47,6 +51,10 @@ async fn main() -> anyhow::Result<()> {
                    ("for".to_string(), json!("rust"))].into(),
         tags: vec!["rust".to_string(), "build-tool".to_string()],
         vectorize: true,
+        vector: None,
+        model: None,
+        expires_at: None,
+        ttl_seconds: None,
     }).await?;
 
     let tokio_doc = store.add_document(db_name, table_name, StoreDocumentRequest {
@@ -57,6 +65,10 @@ async fn main() -> anyhow::Result<()> {
                    ("category".to_string(), json!("async"))].into(),
         tags: vec!["rust".to_string(), "async".to_string(), "library".to_string()],
         vectorize: true,
+        vector: None,
+        model: None,
+        expires_at: None,
+        ttl_seconds: None,
     }).await?;
 
     let python_doc = store
@@ -76,6 +88,10 @@ async fn main() -> anyhow::Result<()> {
                 .into(),
                 tags: vec!["python".to_string(), "programming".to_string()],
                 vectorize: true,
+                vector: None,
+                model: None,
+                expires_at: None,
+                ttl_seconds: None,
             },
         )
         .await?;
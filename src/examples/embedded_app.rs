@@ -31,7 +31,7 @@ async fn main() -> anyhow::Result<()> {
     // 1. Initialize the DocumentStore
     println!("📦 Initializing DocumentStore...");
     let data_dir = "./data/example_embedded".to_string();
-    let mut store = store::DocumentStore::new(data_dir).await?;
+    let store = store::DocumentStore::new(data_dir).await?;
 
     // Database and table names
     let db_name = "default";
@@ -52,6 +52,10 @@ async fn main() -> anyhow::Result<()> {
                    ("difficulty".to_string(), json!("intermediate"))].into(),
         tags: vec!["rust".to_string(), "programming".to_string()],
         vectorize: true,
+        vector: None,
+        model: None,
+        expires_at: None,
+        ttl_seconds: None,
     }).await?;
     println!("  Added document: {}", doc1.id);
 
@@ -63,6 +67,10 @@ async fn main() -> anyhow::Result<()> {
                    ("difficulty".to_string(), json!("beginner"))].into(),
         tags: vec!["python".to_string(), "programming".to_string()],
         vectorize: true,
+        vector: None,
+        model: None,
+        expires_at: None,
+        ttl_seconds: None,
     }).await?;
     println!("  Added document: {}", doc2.id);
 
@@ -83,6 +91,10 @@ async fn main() -> anyhow::Result<()> {
                 .into(),
                 tags: vec!["ai".to_string(), "ml".to_string()],
                 vectorize: true,
+                vector: None,
+                model: None,
+                expires_at: None,
+                ttl_seconds: None,
             },
         )
         .await?;
@@ -104,6 +116,10 @@ async fn main() -> anyhow::Result<()> {
                 .into(),
                 tags: vec!["database".to_string(), "vector".to_string()],
                 vectorize: true,
+                vector: None,
+                model: None,
+                expires_at: None,
+                ttl_seconds: None,
             },
         )
         .await?;
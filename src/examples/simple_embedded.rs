@@ -11,7 +11,7 @@ async fn main() -> anyhow::Result<()> {
     println!("Simple kuiperdb Embedded Example\n");
 
     // Create a DocumentStore
-    let mut store = store::DocumentStore::new("./data/simple_example".to_string()).await?;
+    let store = store::DocumentStore::new("./data/simple_example".to_string()).await?;
     println!("✅ Store initialized\n");
 
     // Database and table
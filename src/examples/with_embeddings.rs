@@ -18,7 +18,7 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize the DocumentStore
     let data_dir = "./data/embeddings_example".to_string();
-    let mut store = store::DocumentStore::new(data_dir).await?;
+    let store = store::DocumentStore::new(data_dir).await?;
 
     let db_name = "default";
     let table_name = "documents";
@@ -117,6 +117,9 @@ async fn main() -> anyhow::Result<()> {
             parent_id: None,
             chunk_index: None,
             token_count: None,
+            is_vectorized: true,
+            embedding_model: None,
+            expires_at: None,
         };
 
         store.store_document(db_name, table_name, doc).await?;
@@ -132,7 +135,7 @@ async fn main() -> anyhow::Result<()> {
     println!("Query: 'fast compiled programming language'");
     let results = searcher
         .search(
-            &mut store,
+            &store,
             Some(&embedder as &dyn embedder::Embedder),
             db_name,
             table_name,
@@ -147,7 +150,7 @@ async fn main() -> anyhow::Result<()> {
     println!("\nQuery: 'artificial intelligence and data analytics'");
     let results = searcher
         .search(
-            &mut store,
+            &store,
             Some(&embedder as &dyn embedder::Embedder),
             db_name,
             table_name,
@@ -162,7 +165,7 @@ async fn main() -> anyhow::Result<()> {
     println!("\nQuery: 'building websites and user interfaces'");
     let results = searcher
         .search(
-            &mut store,
+            &store,
             Some(&embedder as &dyn embedder::Embedder),
             db_name,
             table_name,
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::models::BackupOptions;
+use crate::store::DocumentStore;
+
+/// Background task that backs up every database on a schedule, keeping the
+/// configured number of backups per database
+pub struct BackupScheduler {
+    store: Arc<DocumentStore>,
+    config: Arc<Config>,
+    shutdown: Arc<tokio::sync::Notify>,
+}
+
+impl BackupScheduler {
+    pub fn new(store: Arc<DocumentStore>, config: Arc<Config>) -> Self {
+        Self {
+            store,
+            config,
+            shutdown: Arc::new(tokio::sync::Notify::new()),
+        }
+    }
+
+    /// Start the scheduler
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("Backup scheduler started");
+
+            let period = Duration::from_secs(self.config.backup.interval_secs.max(1));
+            // The first tick fires right away; wait a full period instead
+            let mut interval = time::interval_at(time::Instant::now() + period, period);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+            loop {
+                tokio::select! {
+                    _ = self.shutdown.notified() => {
+                        info!("Backup scheduler stopped");
+                        break;
+                    }
+                    _ = interval.tick() => {
+                        if let Err(e) = self.backup_all().await {
+                            error!("Error running scheduled backups: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Stop the scheduler
+    pub fn stop(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Back up every database and prune old backups, returning how many
+    /// databases were backed up
+    pub async fn backup_all(&self) -> anyhow::Result<usize> {
+        let options = BackupOptions {
+            include_index: self.config.backup.include_index,
+            include_cache: self.config.backup.include_cache,
+        };
+
+        let databases = self.store.list_databases().await?;
+
+        let mut backed_up = 0;
+        for db_name in databases {
+            match self.store.backup_database(&db_name, &options).await {
+                Ok(_) => backed_up += 1,
+                Err(e) => {
+                    warn!("Failed to back up {}: {}", db_name, e);
+                    continue;
+                }
+            }
+            let keep = self.config.backup.keep;
+            if keep > 0 {
+                if let Err(e) = self.store.prune_backups(&db_name, keep) {
+                    warn!("Failed to prune backups of {}: {}", db_name, e);
+                }
+            }
+        }
+
+        info!("Backup scheduler: backed up {} databases", backed_up);
+        Ok(backed_up)
+    }
+}
+
+impl Drop for BackupScheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -91,7 +91,12 @@ impl EmbeddingCache {
 
     /// Get embedding from cache (memory → disk → None)
     pub async fn get(&self, content: &str) -> Result<Option<Vec<f32>>> {
-        let hash = hash_content(content);
+        self.get_for_model(content, &self.model).await
+    }
+
+    /// Get an embedding made by `model` from cache (memory → disk → None)
+    pub async fn get_for_model(&self, content: &str, model: &str) -> Result<Option<Vec<f32>>> {
+        let hash = self.cache_key(content, model);
 
         // Check memory cache first
         {
@@ -108,7 +113,7 @@ impl EmbeddingCache {
             "SELECT vector FROM embedding_cache WHERE content_hash = ? AND model = ?",
         )
         .bind(&hash)
-        .bind(&self.model)
+        .bind(model)
         .fetch_optional(&self.pool)
         .await?;
 
@@ -134,7 +139,12 @@ impl EmbeddingCache {
 
     /// Store embedding in cache (both memory and disk)
     pub async fn put(&self, content: &str, vector: Vec<f32>) -> Result<()> {
-        let hash = hash_content(content);
+        self.put_for_model(content, &self.model, vector).await
+    }
+
+    /// Store an embedding made by `model` in cache (both memory and disk)
+    pub async fn put_for_model(&self, content: &str, model: &str, vector: Vec<f32>) -> Result<()> {
+        let hash = self.cache_key(content, model);
         let vector_bytes = serialize_vector(&vector);
 
         // Store in disk cache
@@ -149,7 +159,7 @@ impl EmbeddingCache {
         )
         .bind(&hash)
         .bind(&vector_bytes)
-        .bind(&self.model)
+        .bind(model)
         .bind(Utc::now())
         .execute(&self.pool)
         .await?;
@@ -161,6 +171,18 @@ impl EmbeddingCache {
         Ok(())
     }
 
+    /// Key of a cache entry
+    ///
+    /// Entries are keyed by content hash alone, so the hash of an entry for a model
+    /// other than the cache's own also covers the model name.
+    fn cache_key(&self, content: &str, model: &str) -> String {
+        if model == self.model {
+            hash_content(content)
+        } else {
+            hash_content(&format!("{}\n{}", model, content))
+        }
+    }
+
     /// Get cache statistics
     pub async fn stats(&self) -> CacheStats {
         self.hits.read().await.clone()
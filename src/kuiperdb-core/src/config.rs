@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::models::TableSettings;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub embedding_url: String,
@@ -29,6 +31,26 @@ pub struct Config {
     // Chunking configuration
     #[serde(default)]
     pub chunking: ChunkingConfig,
+
+    // Trash configuration
+    #[serde(default)]
+    pub trash: TrashConfig,
+
+    // Document expiry configuration
+    #[serde(default)]
+    pub expiry: ExpiryConfig,
+
+    // Backup configuration
+    #[serde(default)]
+    pub backup: BackupConfig,
+
+    // Change log configuration
+    #[serde(default)]
+    pub changes: ChangesConfig,
+
+    // Webhook delivery configuration
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -103,6 +125,14 @@ pub struct VectorIndexConfig {
     /// HNSW ef_search (search quality, 50-500, default 100)
     #[serde(default = "default_hnsw_ef_search")]
     pub hnsw_ef_search: usize,
+
+    /// Rebuild an index once this fraction of its points are deleted or replaced
+    #[serde(default = "default_compaction_threshold")]
+    pub compaction_threshold: f64,
+
+    /// Save built indexes next to the database files and load them at startup
+    #[serde(default = "default_persist_to_disk")]
+    pub persist_to_disk: bool,
 }
 
 fn default_index_mode() -> String {
@@ -125,6 +155,14 @@ fn default_hnsw_ef_search() -> usize {
     100
 }
 
+fn default_compaction_threshold() -> f64 {
+    0.2
+}
+
+fn default_persist_to_disk() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChunkingConfig {
     #[serde(default)]
@@ -163,6 +201,193 @@ fn default_chunk_strategy() -> String {
     "fixed_tokens".to_string()
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TrashConfig {
+    /// Days deleted documents, tables and databases stay in the trash before being purged
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u64,
+
+    /// Seconds between purges of expired trash
+    #[serde(default = "default_purge_interval_secs")]
+    pub purge_interval_secs: u64,
+}
+
+fn default_retention_days() -> u64 {
+    30
+}
+
+fn default_purge_interval_secs() -> u64 {
+    3600
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: default_retention_days(),
+            purge_interval_secs: default_purge_interval_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExpiryConfig {
+    /// Seconds between deletions of expired documents (they are hidden as soon as they expire)
+    #[serde(default = "default_reap_interval_secs")]
+    pub reap_interval_secs: u64,
+}
+
+fn default_reap_interval_secs() -> u64 {
+    60
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            reap_interval_secs: default_reap_interval_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChangesConfig {
+    /// Days change log entries are kept before being pruned (0 keeps them forever)
+    #[serde(default = "default_changes_retention_days")]
+    pub retention_days: u64,
+
+    /// Longest a long-poll request waits for a change
+    #[serde(default = "default_changes_max_wait_secs")]
+    pub max_wait_secs: u64,
+
+    /// Milliseconds between checks for new changes while a feed waits
+    #[serde(default = "default_changes_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+fn default_changes_retention_days() -> u64 {
+    7
+}
+
+fn default_changes_max_wait_secs() -> u64 {
+    60
+}
+
+fn default_changes_poll_interval_ms() -> u64 {
+    250
+}
+
+impl Default for ChangesConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: default_changes_retention_days(),
+            max_wait_secs: default_changes_max_wait_secs(),
+            poll_interval_ms: default_changes_poll_interval_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhooksConfig {
+    /// Milliseconds between checks for new events and deliveries due
+    #[serde(default = "default_webhooks_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+
+    /// Attempts at a delivery before it is marked failed
+    #[serde(default = "default_webhooks_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Seconds before the first retry, doubling with each further attempt
+    #[serde(default = "default_webhooks_backoff_base_secs")]
+    pub backoff_base_secs: u64,
+
+    /// Longest wait between two attempts
+    #[serde(default = "default_webhooks_backoff_max_secs")]
+    pub backoff_max_secs: u64,
+
+    /// Seconds a webhook has to respond before the attempt counts as failed
+    #[serde(default = "default_webhooks_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_webhooks_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_webhooks_max_attempts() -> u32 {
+    8
+}
+
+fn default_webhooks_backoff_base_secs() -> u64 {
+    10
+}
+
+fn default_webhooks_backoff_max_secs() -> u64 {
+    3600
+}
+
+fn default_webhooks_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: default_webhooks_poll_interval_ms(),
+            max_attempts: default_webhooks_max_attempts(),
+            backoff_base_secs: default_webhooks_backoff_base_secs(),
+            backoff_max_secs: default_webhooks_backoff_max_secs(),
+            timeout_secs: default_webhooks_timeout_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BackupConfig {
+    /// Directory backups are written to (default: `backups` under the data directory)
+    #[serde(default)]
+    pub dir: String,
+
+    /// Back up every database on a schedule
+    #[serde(default)]
+    pub scheduled: bool,
+
+    /// Seconds between scheduled backups
+    #[serde(default = "default_backup_interval_secs")]
+    pub interval_secs: u64,
+
+    /// Backups kept per database by the scheduler, which deletes older ones (0 keeps all)
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+
+    /// Include saved HNSW indexes in scheduled backups
+    #[serde(default)]
+    pub include_index: bool,
+
+    /// Include the global embedding cache in scheduled backups
+    #[serde(default)]
+    pub include_cache: bool,
+}
+
+fn default_backup_interval_secs() -> u64 {
+    86400
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: String::new(),
+            scheduled: false,
+            interval_secs: default_backup_interval_secs(),
+            keep: default_backup_keep(),
+            include_index: false,
+            include_cache: false,
+        }
+    }
+}
+
 impl Default for ChunkingConfig {
     fn default() -> Self {
         Self {
@@ -175,6 +400,26 @@ impl Default for ChunkingConfig {
     }
 }
 
+impl VectorIndexConfig {
+    /// This configuration with a table's index overrides applied
+    pub fn for_table(&self, settings: &TableSettings) -> VectorIndexConfig {
+        let overrides = &settings.vector_index;
+        VectorIndexConfig {
+            mode: overrides.mode.clone().unwrap_or_else(|| self.mode.clone()),
+            threshold: overrides.threshold.unwrap_or(self.threshold),
+            hnsw_m: overrides.hnsw_m.unwrap_or(self.hnsw_m),
+            hnsw_ef_construction: overrides
+                .hnsw_ef_construction
+                .unwrap_or(self.hnsw_ef_construction),
+            hnsw_ef_search: overrides.hnsw_ef_search.unwrap_or(self.hnsw_ef_search),
+            compaction_threshold: overrides
+                .compaction_threshold
+                .unwrap_or(self.compaction_threshold),
+            persist_to_disk: self.persist_to_disk,
+        }
+    }
+}
+
 impl Default for VectorIndexConfig {
     fn default() -> Self {
         Self {
@@ -183,6 +428,8 @@ impl Default for VectorIndexConfig {
             hnsw_m: default_hnsw_m(),
             hnsw_ef_construction: default_hnsw_ef_construction(),
             hnsw_ef_search: default_hnsw_ef_search(),
+            compaction_threshold: default_compaction_threshold(),
+            persist_to_disk: default_persist_to_disk(),
         }
     }
 }
@@ -197,6 +444,35 @@ impl Config {
     pub fn database_path(&self, db_name: &str) -> String {
         format!("{}/{}.db", self.data_dir, db_name)
     }
+
+    /// The configuration a table's documents are handled with: this one with the
+    /// table's overrides applied
+    pub fn for_table(&self, settings: &TableSettings) -> Config {
+        let mut config = self.clone();
+        if let Some(dimensions) = settings.embedding_dimensions {
+            config.embedding_dimensions = dimensions;
+        }
+
+        let chunking = &settings.chunking;
+        if let Some(enabled) = chunking.enabled {
+            config.chunking.enabled = enabled;
+        }
+        if let Some(token_threshold) = chunking.token_threshold {
+            config.chunking.token_threshold = token_threshold;
+        }
+        if let Some(chunk_size) = chunking.chunk_size {
+            config.chunking.chunk_size = chunk_size;
+        }
+        if let Some(chunk_overlap) = chunking.chunk_overlap {
+            config.chunking.chunk_overlap = chunk_overlap;
+        }
+        if let Some(ref strategy) = chunking.strategy {
+            config.chunking.strategy = strategy.clone();
+        }
+
+        config.vector_index = self.vector_index.for_table(settings);
+        config
+    }
 }
 
 impl Default for Config {
@@ -222,6 +498,11 @@ impl Default for Config {
             num_embedding_workers: default_num_workers(),
             embedding_batch_size: default_batch_size(),
             chunking: ChunkingConfig::default(),
+            trash: TrashConfig::default(),
+            expiry: ExpiryConfig::default(),
+            backup: BackupConfig::default(),
+            changes: ChangesConfig::default(),
+            webhooks: WebhooksConfig::default(),
         }
     }
 }
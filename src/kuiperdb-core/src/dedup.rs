@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+/// Hash functions in a MinHash signature
+const SIGNATURE_LEN: usize = 64;
+
+/// Signature values per band when bucketing signatures to find candidate pairs;
+/// with 16 bands of 4, pairs above about 0.5 similarity are very likely found
+const BAND_ROWS: usize = 4;
+
+/// Words per shingle
+const SHINGLE_WORDS: usize = 5;
+
+/// Cosine similarity from which an embedding makes a near-duplicate by default
+pub const DEFAULT_VECTOR_THRESHOLD: f64 = 0.95;
+
+/// Estimated Jaccard similarity of shingles from which a document is a
+/// near-duplicate by default
+pub const DEFAULT_MINHASH_THRESHOLD: f64 = 0.8;
+
+/// MinHash signature of the word shingles of a text
+///
+/// Words are lowercased and split on anything but letters and digits, so
+/// formatting and punctuation do not matter. Signatures are stored, so the
+/// hashing must stay stable across versions.
+pub fn minhash_signature(text: &str) -> Vec<u32> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut signature = vec![u32::MAX; SIGNATURE_LEN];
+    let shingles = words.windows(SHINGLE_WORDS.min(words.len()).max(1));
+    for shingle in shingles {
+        let base = fnv1a(shingle);
+        for (i, min) in signature.iter_mut().enumerate() {
+            let (a, b) = hash_params(i);
+            let hash = (base.wrapping_mul(a).wrapping_add(b) >> 32) as u32;
+            *min = (*min).min(hash);
+        }
+    }
+    signature
+}
+
+/// Estimated Jaccard similarity of the shingles behind two signatures
+pub fn minhash_similarity(a: &[u32], b: &[u32]) -> f64 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / a.len() as f64
+}
+
+/// Serialize a signature for storage
+pub fn serialize_signature(signature: &[u32]) -> Vec<u8> {
+    signature.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// Deserialize a stored signature
+pub fn deserialize_signature(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// Pairs of signatures, by position, that share a band and are at least
+/// `threshold` similar
+pub fn similar_pairs(signatures: &[Vec<u32>], threshold: f64) -> Vec<(usize, usize, f64)> {
+    let mut buckets: HashMap<(usize, &[u32]), Vec<usize>> = HashMap::new();
+    for (position, signature) in signatures.iter().enumerate() {
+        for (band, rows) in signature.chunks(BAND_ROWS).enumerate() {
+            buckets.entry((band, rows)).or_default().push(position);
+        }
+    }
+
+    let mut pairs = HashMap::new();
+    for members in buckets.values().filter(|members| members.len() > 1) {
+        for (i, &a) in members.iter().enumerate() {
+            for &b in &members[i + 1..] {
+                pairs
+                    .entry((a, b))
+                    .or_insert_with(|| minhash_similarity(&signatures[a], &signatures[b]));
+            }
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize, f64)> = pairs
+        .into_iter()
+        .filter(|(_, similarity)| *similarity >= threshold)
+        .map(|((a, b), similarity)| (a, b, similarity))
+        .collect();
+    pairs.sort_by_key(|&(a, b, _)| (a, b));
+    pairs
+}
+
+/// Pairs of vectors, by position, whose cosine similarity is at least `threshold`
+///
+/// Every pair is compared, so callers bound the number of vectors.
+pub fn similar_vectors(vectors: &[Vec<f32>], threshold: f64) -> Vec<(usize, usize, f64)> {
+    let normalized: Vec<Vec<f32>> = vectors
+        .iter()
+        .map(|vector| {
+            let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let scale = if norm > 0.0 { 1.0 / norm } else { 0.0 };
+            vector.iter().map(|x| x * scale).collect()
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    for (a, x) in normalized.iter().enumerate() {
+        for (b, y) in normalized.iter().enumerate().skip(a + 1) {
+            if x.len() != y.len() {
+                continue;
+            }
+            let similarity = x.iter().zip(y).map(|(x, y)| x * y).sum::<f32>() as f64;
+            if similarity >= threshold {
+                pairs.push((a, b, similarity));
+            }
+        }
+    }
+    pairs
+}
+
+/// Groups of items joined by pairs, e.g. duplicates found pairwise
+pub struct Clusters {
+    parents: Vec<usize>,
+}
+
+impl Clusters {
+    pub fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    /// Put two items in the same group
+    pub fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        // The earlier item stays the root, so a group is led by its first item
+        self.parents[a.max(b)] = a.min(b);
+    }
+
+    /// Groups of more than one item, each in item order, ordered by first item
+    pub fn groups(mut self) -> Vec<Vec<usize>> {
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for item in 0..self.parents.len() {
+            let root = self.root(item);
+            groups.entry(root).or_default().push(item);
+        }
+
+        let mut groups: Vec<Vec<usize>> = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect();
+        groups.sort_by_key(|group| group[0]);
+        groups
+    }
+
+    fn root(&mut self, mut item: usize) -> usize {
+        while self.parents[item] != item {
+            self.parents[item] = self.parents[self.parents[item]];
+            item = self.parents[item];
+        }
+        item
+    }
+}
+
+/// 64-bit FNV-1a hash of a shingle, stable across platforms and versions
+fn fnv1a(words: &[String]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (i, word) in words.iter().enumerate() {
+        let separator = (i > 0).then_some(b' ');
+        for byte in separator.into_iter().chain(word.bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// Multiplier (odd) and increment of the `i`-th hash function, from SplitMix64
+fn hash_params(i: usize) -> (u64, u64) {
+    let mix = |mut z: u64| {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+    let seed = (i as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15);
+    (mix(seed) | 1, mix(seed ^ 0x5555555555555555))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minhash_finds_near_duplicates_only() {
+        let page = "The quick brown fox jumps over the lazy dog while the farmer \
+                    watches from the porch and the sun sets behind the hills";
+        let reposted = format!("{} Shared from another site.", page.to_uppercase());
+        let other = "Rust ownership rules make memory safety possible without a \
+                     garbage collector by checking borrows at compile time";
+
+        let signatures = vec![
+            minhash_signature(page),
+            minhash_signature(other),
+            minhash_signature(&reposted),
+        ];
+        assert_eq!(signatures[0], minhash_signature(page));
+        assert!(minhash_similarity(&signatures[0], &signatures[2]) > 0.6);
+        assert!(minhash_similarity(&signatures[0], &signatures[1]) < 0.2);
+        assert_eq!(
+            deserialize_signature(&serialize_signature(&signatures[0])),
+            signatures[0]
+        );
+
+        let pairs = similar_pairs(&signatures, 0.6);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0, pairs[0].1), (0, 2));
+
+        let vectors = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![2.0, 0.1]];
+        let pairs = similar_vectors(&vectors, 0.95);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0, pairs[0].1), (0, 2));
+
+        let mut clusters = Clusters::new(signatures.len());
+        clusters.join(2, 0);
+        assert_eq!(clusters.groups(), vec![vec![0, 2]]);
+    }
+}
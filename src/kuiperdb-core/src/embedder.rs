@@ -10,6 +10,14 @@ use crate::cache::EmbeddingCache;
 #[async_trait::async_trait]
 pub trait Embedder: Send + Sync {
     async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+    /// Embed with another model and/or dimensions than the embedder's own, e.g.
+    /// those of a table's settings
+    async fn embed_with(
+        &self,
+        text: &str,
+        model: Option<&str>,
+        dimensions: Option<usize>,
+    ) -> Result<Vec<f32>>;
     async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
     fn dimensions(&self) -> usize;
 }
@@ -28,6 +36,8 @@ pub struct OpenAIEmbedder {
 struct EmbeddingRequest {
     input: serde_json::Value, // String or array of strings
     model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>, // Only sent when not the embedder's own
 }
 
 #[derive(Deserialize)]
@@ -66,10 +76,11 @@ impl OpenAIEmbedder {
     }
 
     /// Call GPU endpoint (bypassing cache)
-    async fn embed_uncached(&self, text: &str) -> Result<Vec<f32>> {
+    async fn embed_uncached(&self, text: &str, model: &str, dimensions: usize) -> Result<Vec<f32>> {
         let request = EmbeddingRequest {
             input: serde_json::Value::String(text.to_string()),
-            model: self.model.clone(),
+            model: model.to_string(),
+            dimensions: (dimensions != self.dimensions).then_some(dimensions),
         };
 
         let response = self
@@ -97,10 +108,10 @@ impl OpenAIEmbedder {
 
         let embedding = embedding_response.data[0].embedding.clone();
 
-        if embedding.len() != self.dimensions {
+        if embedding.len() != dimensions {
             anyhow::bail!(
                 "Expected embedding dimension {}, got {}",
-                self.dimensions,
+                dimensions,
                 embedding.len()
             );
         }
@@ -112,19 +123,33 @@ impl OpenAIEmbedder {
 #[async_trait::async_trait]
 impl Embedder for OpenAIEmbedder {
     async fn embed(&self, text: &str) -> Result<Vec<f32>> {
-        // Check cache first if enabled
+        self.embed_with(text, None, None).await
+    }
+
+    async fn embed_with(
+        &self,
+        text: &str,
+        model: Option<&str>,
+        dimensions: Option<usize>,
+    ) -> Result<Vec<f32>> {
+        let model = model.unwrap_or(&self.model);
+        let dimensions = dimensions.unwrap_or(self.dimensions);
+
+        // Check cache first if enabled (vectors of other dimensions are a miss)
         if let Some(cache) = &self.cache {
-            if let Some(vector) = cache.get(text).await? {
-                return Ok(vector);
+            if let Some(vector) = cache.get_for_model(text, model).await? {
+                if vector.len() == dimensions {
+                    return Ok(vector);
+                }
             }
         }
 
         // Cache miss or no cache - call GPU
-        let vector = self.embed_uncached(text).await?;
+        let vector = self.embed_uncached(text, model, dimensions).await?;
 
         // Store in cache if enabled
         if let Some(cache) = &self.cache {
-            cache.put(text, vector.clone()).await?;
+            cache.put_for_model(text, model, vector.clone()).await?;
         }
 
         Ok(vector)
@@ -0,0 +1,545 @@
+use anyhow::Result;
+use serde_json::Value;
+use sqlx::query::Query;
+use sqlx::sqlite::{Sqlite, SqliteArguments};
+use std::collections::HashMap;
+
+use crate::models::MetadataIndex;
+
+/// Comparison applied to a metadata field
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterOp {
+    Eq(Value),
+    Ne(Value),
+    In(Vec<Value>),
+    Gt(Value),
+    Gte(Value),
+    Lt(Value),
+    Lte(Value),
+    Exists(bool),
+}
+
+/// Single filter condition
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    /// Document must carry this tag
+    Tag(String),
+    /// Document must carry at least one of these tags
+    AnyTag(Vec<String>),
+    /// Condition on a (dot-separated) metadata field
+    Field { path: String, op: FilterOp },
+    /// Only root documents (no chunks)
+    RootsOnly,
+    /// Exclude the document with this ID
+    NotId(String),
+    /// Exclude the chunks of this parent document
+    NotChunkOf(String),
+}
+
+/// Search filter: all conditions must hold (AND logic)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchFilter {
+    pub conditions: Vec<FilterExpr>,
+}
+
+/// Positional SQL parameter produced by the filter compiler
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlParam {
+    Text(String),
+    Integer(i64),
+    Real(f64),
+}
+
+impl SearchFilter {
+    /// Parse the `filters` object of a search request
+    ///
+    /// - `"tag": "x"` - document has tag `x`
+    /// - `"tags": ["x", "y"]` - document has all tags
+    /// - `"tag"`/`"tags": {"$in": [...]}` - document has any of the tags
+    /// - `"field": value` - metadata equality (dot paths reach nested fields)
+    /// - `"field": {"$ne"|"$in"|"$gt"|"$gte"|"$lt"|"$lte"|"$exists": ...}` - operators
+    pub fn parse(filters: &HashMap<String, Value>) -> Result<Self> {
+        // Sort keys so the generated SQL is deterministic
+        let mut keys: Vec<&String> = filters.keys().collect();
+        keys.sort();
+
+        let mut conditions = Vec::new();
+        for key in keys {
+            let value = &filters[key];
+            match key.as_str() {
+                "tag" | "tags" => parse_tag_filter(key, value, &mut conditions)?,
+                _ => {
+                    if !is_valid_field_path(key) {
+                        anyhow::bail!("Invalid metadata field: {}", key);
+                    }
+                    for op in parse_field_ops(key, value)? {
+                        conditions.push(FilterExpr::Field {
+                            path: key.clone(),
+                            op,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(Self { conditions })
+    }
+
+    /// Restrict matches to root documents, excluding chunks
+    pub fn roots_only(mut self) -> Self {
+        if !self.conditions.contains(&FilterExpr::RootsOnly) {
+            self.conditions.push(FilterExpr::RootsOnly);
+        }
+        self
+    }
+
+    /// Exclude a single document (e.g. the source of a "more like this" search)
+    pub fn excluding_id(mut self, id: impl Into<String>) -> Self {
+        self.conditions.push(FilterExpr::NotId(id.into()));
+        self
+    }
+
+    /// Exclude the chunks of a parent document
+    pub fn excluding_chunks_of(mut self, parent_id: impl Into<String>) -> Self {
+        self.conditions
+            .push(FilterExpr::NotChunkOf(parent_id.into()));
+        self
+    }
+
+    /// Check if the filter has no conditions
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Compile to an SQL fragment (` AND (...)`) plus its parameters
+    ///
+    /// `alias` is the alias of the documents table `table_name` in the surrounding
+    /// query. Comparisons on fields in `indexes` use their generated columns when
+    /// the values have the indexed type. Returns an empty string when there is
+    /// nothing to filter.
+    pub fn to_sql(
+        &self,
+        table_name: &str,
+        alias: &str,
+        indexes: &[MetadataIndex],
+    ) -> (String, Vec<SqlParam>) {
+        if self.conditions.is_empty() {
+            return (String::new(), Vec::new());
+        }
+
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+
+        for condition in &self.conditions {
+            match condition {
+                FilterExpr::Tag(tag) => {
+                    clauses.push(tag_clause(table_name, alias, 1));
+                    params.push(SqlParam::Text(tag.clone()));
+                }
+                FilterExpr::AnyTag(tags) => {
+                    if tags.is_empty() {
+                        clauses.push("0".to_string());
+                        continue;
+                    }
+                    clauses.push(tag_clause(table_name, alias, tags.len()));
+                    params.extend(tags.iter().map(|t| SqlParam::Text(t.clone())));
+                }
+                FilterExpr::RootsOnly => {
+                    clauses.push(format!("{}.parent_id IS NULL", alias));
+                }
+                FilterExpr::NotId(id) => {
+                    clauses.push(format!("{}.id <> ?", alias));
+                    params.push(SqlParam::Text(id.clone()));
+                }
+                FilterExpr::NotChunkOf(parent_id) => {
+                    clauses.push(format!("{}.parent_id IS NOT ?", alias));
+                    params.push(SqlParam::Text(parent_id.clone()));
+                }
+                FilterExpr::Field { path, op } => {
+                    let json_path = SqlParam::Text(json_path(path));
+                    // A generated column replaces json_extract and its path parameter
+                    let (extract, path_param) = match indexed_column(indexes, path, op) {
+                        Some(column) => (format!(r#"{}."{}""#, alias, column), None),
+                        None => (
+                            format!("json_extract({}.metadata, ?)", alias),
+                            Some(json_path.clone()),
+                        ),
+                    };
+                    match op {
+                        FilterOp::Eq(Value::Null) => {
+                            clauses.push(format!("json_type({}.metadata, ?) = 'null'", alias));
+                            params.push(json_path);
+                        }
+                        FilterOp::Eq(value) => {
+                            clauses.push(format!("{} = ?", extract));
+                            params.extend(path_param);
+                            params.push(value_param(value));
+                        }
+                        FilterOp::Ne(Value::Null) => {
+                            clauses.push(format!("json_type({}.metadata, ?) IS NOT 'null'", alias));
+                            params.push(json_path);
+                        }
+                        FilterOp::Ne(value) => {
+                            clauses.push(format!("{} IS NOT ?", extract));
+                            params.extend(path_param);
+                            params.push(value_param(value));
+                        }
+                        FilterOp::In(values) => {
+                            if values.is_empty() {
+                                clauses.push("0".to_string());
+                                continue;
+                            }
+                            let placeholders = vec!["?"; values.len()].join(", ");
+                            clauses.push(format!("{} IN ({})", extract, placeholders));
+                            params.extend(path_param);
+                            params.extend(values.iter().map(value_param));
+                        }
+                        FilterOp::Gt(value)
+                        | FilterOp::Gte(value)
+                        | FilterOp::Lt(value)
+                        | FilterOp::Lte(value) => {
+                            let cmp = match op {
+                                FilterOp::Gt(_) => ">",
+                                FilterOp::Gte(_) => ">=",
+                                FilterOp::Lt(_) => "<",
+                                _ => "<=",
+                            };
+                            clauses.push(format!("{} {} ?", extract, cmp));
+                            params.extend(path_param);
+                            params.push(value_param(value));
+                        }
+                        FilterOp::Exists(exists) => {
+                            let check = if *exists { "IS NOT NULL" } else { "IS NULL" };
+                            clauses.push(format!("json_type({}.metadata, ?) {}", alias, check));
+                            params.push(json_path);
+                        }
+                    }
+                }
+            }
+        }
+
+        (format!(" AND ({})", clauses.join(" AND ")), params)
+    }
+}
+
+/// Bind filter parameters to a query, in order
+pub fn bind_params<'q>(
+    mut query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    params: &[SqlParam],
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    for param in params {
+        query = match param {
+            SqlParam::Text(s) => query.bind(s.clone()),
+            SqlParam::Integer(i) => query.bind(*i),
+            SqlParam::Real(f) => query.bind(*f),
+        };
+    }
+    query
+}
+
+/// Generated column to compare against for `op` on `path`, if the field is indexed
+/// and every operand has the indexed type
+///
+/// Null and existence checks stay on the JSON, which tells a null from a missing
+/// field.
+fn indexed_column(indexes: &[MetadataIndex], path: &str, op: &FilterOp) -> Option<String> {
+    let index = indexes.iter().find(|index| index.field == path)?;
+    let accepts = |value: &Value| index.index_type.accepts(value);
+    let usable = match op {
+        FilterOp::Eq(value)
+        | FilterOp::Ne(value)
+        | FilterOp::Gt(value)
+        | FilterOp::Gte(value)
+        | FilterOp::Lt(value)
+        | FilterOp::Lte(value) => accepts(value),
+        FilterOp::In(values) => values.iter().all(accepts),
+        FilterOp::Exists(_) => false,
+    };
+    usable.then(|| index.column_name())
+}
+
+/// Build a quoted JSON path (`$."a"."b"`) from a dot-separated field path
+pub(crate) fn json_path(path: &str) -> String {
+    let segments: Vec<String> = path.split('.').map(|s| format!("\"{}\"", s)).collect();
+    format!("$.{}", segments.join("."))
+}
+
+/// Document carries any of `count` tags, looked up in the indexed tag side table
+fn tag_clause(table_name: &str, alias: &str, count: usize) -> String {
+    format!(
+        r#"{}.id IN (SELECT doc_id FROM "{}_tags" WHERE tag IN ({}))"#,
+        alias,
+        table_name,
+        vec!["?"; count].join(", ")
+    )
+}
+
+fn parse_tag_filter(key: &str, value: &Value, conditions: &mut Vec<FilterExpr>) -> Result<()> {
+    match value {
+        Value::String(tag) => conditions.push(FilterExpr::Tag(tag.clone())),
+        Value::Array(tags) => {
+            for tag in tags {
+                conditions.push(FilterExpr::Tag(tag_string(key, tag)?));
+            }
+        }
+        Value::Object(ops) if ops.len() == 1 && ops.contains_key("$in") => {
+            let tags = match &ops["$in"] {
+                Value::Array(tags) => tags
+                    .iter()
+                    .map(|t| tag_string(key, t))
+                    .collect::<Result<Vec<_>>>()?,
+                _ => anyhow::bail!("Filter '{}': $in expects an array", key),
+            };
+            conditions.push(FilterExpr::AnyTag(tags));
+        }
+        _ => anyhow::bail!(
+            "Filter '{}' expects a string, an array of strings or {{\"$in\": [...]}}",
+            key
+        ),
+    }
+    Ok(())
+}
+
+fn tag_string(key: &str, value: &Value) -> Result<String> {
+    value
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("Filter '{}': tags must be strings", key))
+}
+
+fn parse_field_ops(key: &str, value: &Value) -> Result<Vec<FilterOp>> {
+    let ops = match value {
+        Value::Object(map) if !map.is_empty() && map.keys().all(|k| k.starts_with('$')) => map,
+        _ => return Ok(vec![FilterOp::Eq(value.clone())]),
+    };
+
+    let mut result = Vec::new();
+    for (op, operand) in ops {
+        let parsed = match op.as_str() {
+            "$eq" => FilterOp::Eq(operand.clone()),
+            "$ne" => FilterOp::Ne(operand.clone()),
+            "$in" => match operand {
+                Value::Array(values) => FilterOp::In(values.clone()),
+                _ => anyhow::bail!("Filter '{}': $in expects an array", key),
+            },
+            "$gt" | "$gte" | "$lt" | "$lte" => {
+                if !(operand.is_number() || operand.is_string()) {
+                    anyhow::bail!("Filter '{}': {} expects a number or string", key, op);
+                }
+                match op.as_str() {
+                    "$gt" => FilterOp::Gt(operand.clone()),
+                    "$gte" => FilterOp::Gte(operand.clone()),
+                    "$lt" => FilterOp::Lt(operand.clone()),
+                    _ => FilterOp::Lte(operand.clone()),
+                }
+            }
+            "$exists" => match operand {
+                Value::Bool(b) => FilterOp::Exists(*b),
+                _ => anyhow::bail!("Filter '{}': $exists expects a boolean", key),
+            },
+            _ => anyhow::bail!("Filter '{}': unknown operator {}", key, op),
+        };
+        result.push(parsed);
+    }
+
+    Ok(result)
+}
+
+/// Convert a JSON value to the SQL value `json_extract` would return for it
+fn value_param(value: &Value) -> SqlParam {
+    match value {
+        Value::Bool(b) => SqlParam::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlParam::Integer(i),
+            None => SqlParam::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlParam::Text(s.clone()),
+        // Arrays/objects compare against their minified JSON text
+        other => SqlParam::Text(other.to_string()),
+    }
+}
+
+/// Validate metadata field path (dot-separated alphanumeric/underscore/dash segments)
+pub(crate) fn is_valid_field_path(path: &str) -> bool {
+    !path.is_empty()
+        && path.split('.').all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use sqlx::sqlite::SqlitePool;
+
+    fn filters(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_parse_tags_and_metadata() {
+        let filter = SearchFilter::parse(&filters(json!({
+            "tags": ["ml", "nlp"],
+            "author": "Alice",
+            "year": {"$gte": 2020}
+        })))
+        .unwrap();
+
+        assert_eq!(
+            filter.conditions,
+            vec![
+                FilterExpr::Field {
+                    path: "author".to_string(),
+                    op: FilterOp::Eq(json!("Alice")),
+                },
+                FilterExpr::Tag("ml".to_string()),
+                FilterExpr::Tag("nlp".to_string()),
+                FilterExpr::Field {
+                    path: "year".to_string(),
+                    op: FilterOp::Gte(json!(2020)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert!(SearchFilter::parse(&filters(json!({"year": {"$between": [1, 2]}}))).is_err());
+        assert!(SearchFilter::parse(&filters(json!({"a'b": 1}))).is_err());
+        assert!(SearchFilter::parse(&filters(json!({"tags": [1, 2]}))).is_err());
+        assert!(SearchFilter::parse(&filters(json!({"x": {"$exists": "yes"}}))).is_err());
+    }
+
+    #[test]
+    fn test_indexed_fields_use_generated_columns() {
+        let indexes = [MetadataIndex {
+            field: "year".to_string(),
+            index_type: crate::models::MetadataIndexType::Integer,
+        }];
+        let sql = |value: Value| {
+            let filter = SearchFilter::parse(&filters(json!({ "year": value }))).unwrap();
+            filter.to_sql("docs", "d", &indexes)
+        };
+
+        let (clause, params) = sql(json!({"$gte": 2020}));
+        assert_eq!(clause, r#" AND (d."meta.year" >= ?)"#);
+        assert_eq!(params, vec![SqlParam::Integer(2020)]);
+
+        // Values of another type, and null checks, go through the JSON
+        let (clause, _) = sql(json!("2020"));
+        assert!(clause.contains("json_extract"));
+        let (clause, _) = sql(json!({"$in": [2019, 2020.5]}));
+        assert!(clause.contains("json_extract"));
+        let (clause, _) = sql(json!({"$exists": true}));
+        assert!(clause.contains("json_type"));
+    }
+
+    #[tokio::test]
+    async fn test_compiled_sql_matches_rows() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE docs (id TEXT, metadata TEXT, parent_id TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE docs_tags (doc_id TEXT, tag TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let rows = [
+            (
+                "a",
+                json!({"author": "Alice", "year": 2024, "draft": true}),
+                "ml,tutorial",
+            ),
+            (
+                "b",
+                json!({"author": "Bob", "year": 2019, "meta": {"lang": "en"}}),
+                "ml,research",
+            ),
+            ("c", json!({"author": "Carol"}), "ml-ops"),
+        ];
+        for (id, metadata, tags) in rows {
+            // "c" is a chunk of "a"
+            let parent_id = (id == "c").then_some("a");
+            sqlx::query("INSERT INTO docs VALUES (?, ?, ?)")
+                .bind(id)
+                .bind(metadata.to_string())
+                .bind(parent_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            for tag in tags.split(',') {
+                sqlx::query("INSERT INTO docs_tags VALUES (?, ?)")
+                    .bind(id)
+                    .bind(tag)
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let cases = [
+            (json!({"tag": "ml"}), vec!["a", "b"]),
+            (
+                json!({"tags": {"$in": ["research", "ml-ops"]}}),
+                vec!["b", "c"],
+            ),
+            (
+                json!({"author": {"$in": ["Alice", "Carol"]}}),
+                vec!["a", "c"],
+            ),
+            (json!({"year": {"$gte": 2020}}), vec!["a"]),
+            (json!({"year": {"$exists": false}}), vec!["c"]),
+            (json!({"draft": true}), vec!["a"]),
+            (json!({"author": {"$ne": "Alice"}}), vec!["b", "c"]),
+            (json!({"meta.lang": "en", "tags": ["ml"]}), vec!["b"]),
+        ];
+
+        for (filter_json, expected) in cases {
+            let filter = SearchFilter::parse(&filters(filter_json.clone())).unwrap();
+            let (clause, params) = filter.to_sql("docs", "d", &[]);
+            let sql = format!("SELECT id FROM docs AS d WHERE 1 = 1{} ORDER BY id", clause);
+            let ids: Vec<String> = bind_params(sqlx::query(&sql), &params)
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| sqlx::Row::get(row, "id"))
+                .collect();
+            assert_eq!(ids, expected, "filter {}", filter_json);
+        }
+
+        let exclusions = [
+            (SearchFilter::default().roots_only(), vec!["a", "b"]),
+            (SearchFilter::default().excluding_id("a"), vec!["b", "c"]),
+            (
+                SearchFilter::default().excluding_chunks_of("a"),
+                vec!["a", "b"],
+            ),
+            (
+                SearchFilter::default()
+                    .excluding_id("a")
+                    .excluding_chunks_of("a"),
+                vec!["b"],
+            ),
+        ];
+
+        for (filter, expected) in exclusions {
+            let (clause, params) = filter.to_sql("docs", "d", &[]);
+            let sql = format!("SELECT id FROM docs AS d WHERE 1 = 1{} ORDER BY id", clause);
+            let ids: Vec<String> = bind_params(sqlx::query(&sql), &params)
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| sqlx::Row::get(row, "id"))
+                .collect();
+            assert_eq!(ids, expected, "filter {:?}", filter);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::models::DocumentRevision;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// Largest line-by-line comparison (old lines x new lines, after trimming the common
+/// prefix and suffix) that is diffed exactly; bigger changes are reported as a
+/// replacement of the whole differing region
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A changed line of content, numbered from 1 in its own revision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum LineChange {
+    Delete { old_line: usize, text: String },
+    Insert { new_line: usize, text: String },
+}
+
+/// A top-level metadata key whose value differs between two revisions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetadataChange {
+    pub key: String,
+    pub from: Option<serde_json::Value>, // None if the key was added
+    pub to: Option<serde_json::Value>,   // None if the key was removed
+}
+
+/// Differences between two revisions of a document
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionDiff {
+    pub from: i64,
+    pub to: i64,
+    pub content: Vec<LineChange>,
+    pub metadata: Vec<MetadataChange>,
+    pub tags_added: Vec<String>,
+    pub tags_removed: Vec<String>,
+}
+
+/// Compare two revisions of the same document
+pub fn diff_revisions(from: &DocumentRevision, to: &DocumentRevision) -> RevisionDiff {
+    let keys: BTreeSet<&String> = from.metadata.keys().chain(to.metadata.keys()).collect();
+    let metadata = keys
+        .into_iter()
+        .filter_map(|key| {
+            let old = from.metadata.get(key);
+            let new = to.metadata.get(key);
+            (old != new).then(|| MetadataChange {
+                key: key.clone(),
+                from: old.cloned(),
+                to: new.cloned(),
+            })
+        })
+        .collect();
+
+    RevisionDiff {
+        from: from.rev,
+        to: to.rev,
+        content: diff_lines(&from.content, &to.content),
+        metadata,
+        tags_added: to
+            .tags
+            .iter()
+            .filter(|tag| !from.tags.contains(tag))
+            .cloned()
+            .collect(),
+        tags_removed: from
+            .tags
+            .iter()
+            .filter(|tag| !to.tags.contains(tag))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Line diff of two texts, listing deleted and inserted lines
+pub fn diff_lines(old: &str, new: &str) -> Vec<LineChange> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Only the region between the common prefix and suffix needs comparing
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let delete = |i: usize| LineChange::Delete {
+        old_line: prefix + i + 1,
+        text: a[i].to_string(),
+    };
+    let insert = |j: usize| LineChange::Insert {
+        new_line: prefix + j + 1,
+        text: b[j].to_string(),
+    };
+
+    if a.len().saturating_mul(b.len()) > MAX_DIFF_CELLS {
+        return (0..a.len())
+            .map(delete)
+            .chain((0..b.len()).map(insert))
+            .collect();
+    }
+
+    // lcs[i][j] is the longest common subsequence of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j == b.len()
+            || (i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            changes.push(delete(i));
+            i += 1;
+        } else {
+            changes.push(insert(j));
+            j += 1;
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn revision(
+        rev: i64,
+        content: &str,
+        metadata: serde_json::Value,
+        tags: &[&str],
+    ) -> DocumentRevision {
+        DocumentRevision {
+            doc_id: "doc".to_string(),
+            rev,
+            content: content.to_string(),
+            metadata: serde_json::from_value::<HashMap<_, _>>(metadata).unwrap(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            recorded_at: chrono::Utc::now(),
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn test_diff_lines_reports_changed_lines() {
+        let changes = diff_lines("a\nb\nc\nd", "a\nx\nc\nd\ne");
+
+        assert_eq!(
+            changes,
+            vec![
+                LineChange::Delete {
+                    old_line: 2,
+                    text: "b".to_string()
+                },
+                LineChange::Insert {
+                    new_line: 2,
+                    text: "x".to_string()
+                },
+                LineChange::Insert {
+                    new_line: 5,
+                    text: "e".to_string()
+                },
+            ]
+        );
+        assert!(diff_lines("same\ntext", "same\ntext").is_empty());
+    }
+
+    #[test]
+    fn test_diff_revisions_compares_metadata_and_tags() {
+        let from = revision(1, "text", serde_json::json!({"a": 1, "b": 2}), &["x", "y"]);
+        let to = revision(2, "text", serde_json::json!({"b": 3, "c": 4}), &["y", "z"]);
+
+        let diff = diff_revisions(&from, &to);
+
+        assert!(diff.content.is_empty());
+        let keys: Vec<&str> = diff.metadata.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+        assert_eq!(diff.metadata[0].to, None);
+        assert_eq!(diff.metadata[1].to, Some(serde_json::json!(3)));
+        assert_eq!(diff.tags_added, vec!["z"]);
+        assert_eq!(diff.tags_removed, vec!["x"]);
+    }
+}
@@ -1,18 +1,38 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use hnsw_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 use tracing::{debug, info, warn};
 
+/// Version of the on-disk index format (bump when the layout changes)
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// Number of HNSW layers (hnsw_rs' maximum, which its dump format requires)
+const HNSW_MAX_LAYERS: usize = 16;
+
 /// Vector index using HNSW for fast approximate nearest neighbor search
 pub struct VectorIndex {
     /// HNSW index (thread-safe)
-    hnsw: Arc<RwLock<Option<Hnsw<'static, f32, DistCosine>>>>,
+    hnsw: Arc<RwLock<Option<Graph>>>,
 
     /// Mapping from HNSW index -> document ID
     id_map: Arc<RwLock<Vec<String>>>,
 
-    /// Reverse mapping from doc ID -> HNSW index
-    reverse_map: Arc<RwLock<std::collections::HashMap<String, usize>>>,
+    /// Reverse mapping from doc ID -> HNSW index (live points only)
+    reverse_map: Arc<RwLock<HashMap<String, usize>>>,
+
+    /// HNSW indices of deleted or replaced points, skipped at search time
+    tombstones: Arc<RwLock<HashSet<usize>>>,
+
+    /// Bumped whenever the graph is replaced, so compaction can detect a
+    /// concurrent rebuild
+    generation: AtomicU64,
+
+    /// Set while a compaction is running
+    compacting: AtomicBool,
 
     /// Vector dimensions
     dimensions: usize,
@@ -21,11 +41,46 @@ pub struct VectorIndex {
     config: IndexConfig,
 }
 
+/// HNSW graph, with the loader it was read with if it was loaded from disk
+///
+/// A loaded graph may borrow from its loader, so the loader is kept for as long
+/// as the graph and dropped after it.
+struct Graph {
+    hnsw: Hnsw<'static, f32, DistCosine>,
+    _loader: Option<Loader>,
+}
+
+impl Graph {
+    /// A graph built in memory
+    fn built(hnsw: Hnsw<'static, f32, DistCosine>) -> Self {
+        Self {
+            hnsw,
+            _loader: None,
+        }
+    }
+}
+
+/// Heap-allocated loader of a saved graph, freed when dropped
+struct Loader(*mut HnswIo);
+
+// The loader is not used again once its graph is read
+unsafe impl Send for Loader {}
+unsafe impl Sync for Loader {}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        // SAFETY: the pointer comes from `Box::into_raw`, and a graph borrowing
+        // from the loader is dropped before it
+        drop(unsafe { Box::from_raw(self.0) });
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexConfig {
     pub hnsw_m: usize,               // Max connections per layer (default: 16)
     pub hnsw_ef_construction: usize, // Build quality (default: 200)
     pub hnsw_ef_search: usize,       // Search quality (default: 100)
+    pub compaction_threshold: f64,   // Tombstone ratio that triggers a rebuild (default: 0.2)
 }
 
 impl Default for IndexConfig {
@@ -34,17 +89,42 @@ impl Default for IndexConfig {
             hnsw_m: 16,
             hnsw_ef_construction: 200,
             hnsw_ef_search: 100,
+            compaction_threshold: 0.2,
         }
     }
 }
 
+/// Sidecar manifest stored next to a dumped HNSW graph
+///
+/// Records the HNSW id -> document ID mapping and the table state the index
+/// was saved from, so a stale index can be detected on load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexManifest {
+    pub format_version: u32,
+    pub dimensions: usize,
+    pub hnsw_m: usize,
+    pub hnsw_ef_construction: usize,
+    /// Mapping from HNSW index -> document ID
+    pub id_map: Vec<String>,
+    /// HNSW indices of deleted or replaced points
+    #[serde(default)]
+    pub tombstones: Vec<usize>,
+    /// Number of embedded rows in the table when the index was saved
+    pub row_count: i64,
+    /// Latest `updated_at` among embedded rows when the index was saved
+    pub watermark: Option<String>,
+}
+
 impl VectorIndex {
     /// Create a new empty vector index
     pub fn new(dimensions: usize, config: IndexConfig) -> Self {
         Self {
             hnsw: Arc::new(RwLock::new(None)),
             id_map: Arc::new(RwLock::new(Vec::new())),
-            reverse_map: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            reverse_map: Arc::new(RwLock::new(HashMap::new())),
+            tombstones: Arc::new(RwLock::new(HashSet::new())),
+            generation: AtomicU64::new(0),
+            compacting: AtomicBool::new(false),
             dimensions,
             config,
         }
@@ -67,16 +147,16 @@ impl VectorIndex {
         let hnsw: Hnsw<'static, f32, DistCosine> = Hnsw::new(
             self.config.hnsw_m,
             documents.len(),
-            self.dimensions,
+            HNSW_MAX_LAYERS,
             self.config.hnsw_ef_construction,
             DistCosine,
         );
 
         // Insert all vectors
         let mut id_map = Vec::new();
-        let mut reverse_map = std::collections::HashMap::new();
+        let mut reverse_map = HashMap::new();
 
-        for (idx, (doc_id, vector)) in documents.into_iter().enumerate() {
+        for (doc_id, vector) in documents {
             if vector.len() != self.dimensions {
                 warn!(
                     "Skipping document {} with wrong dimensions: {} (expected {})",
@@ -88,15 +168,20 @@ impl VectorIndex {
             }
 
             // Insert as (data, id) tuple
+            let idx = id_map.len();
             hnsw.insert((&vector, idx));
             id_map.push(doc_id.clone());
             reverse_map.insert(doc_id, idx);
         }
 
         // Store index
-        *self.hnsw.write().unwrap() = Some(hnsw);
+        let mut hnsw_lock = self.hnsw.write().unwrap();
+        *hnsw_lock = Some(Graph::built(hnsw));
         *self.id_map.write().unwrap() = id_map;
         *self.reverse_map.write().unwrap() = reverse_map;
+        self.tombstones.write().unwrap().clear();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        drop(hnsw_lock);
 
         info!(
             "HNSW index built successfully with {} vectors",
@@ -107,6 +192,9 @@ impl VectorIndex {
     }
 
     /// Add a single document to the index
+    ///
+    /// If the document is already indexed its old point is tombstoned and
+    /// replaced with the new vector.
     pub fn add(&self, doc_id: String, vector: Vec<f32>) -> Result<()> {
         if vector.len() != self.dimensions {
             anyhow::bail!(
@@ -119,11 +207,12 @@ impl VectorIndex {
         let mut hnsw_lock = self.hnsw.write().unwrap();
         let mut id_map = self.id_map.write().unwrap();
         let mut reverse_map = self.reverse_map.write().unwrap();
+        let mut tombstones = self.tombstones.write().unwrap();
 
-        // Check if document already exists
-        if reverse_map.contains_key(&doc_id) {
-            debug!("Document {} already in index, skipping", doc_id);
-            return Ok(());
+        // Replace an existing vector: HNSW can't remove points, so hide the old one
+        if let Some(old_idx) = reverse_map.get(&doc_id) {
+            debug!("Document {} already in index, replacing vector", doc_id);
+            tombstones.insert(*old_idx);
         }
 
         // Get or create HNSW index
@@ -132,16 +221,16 @@ impl VectorIndex {
             let hnsw: Hnsw<'static, f32, DistCosine> = Hnsw::new(
                 self.config.hnsw_m,
                 10000, // Initial capacity
-                self.dimensions,
+                HNSW_MAX_LAYERS,
                 self.config.hnsw_ef_construction,
                 DistCosine,
             );
-            *hnsw_lock = Some(hnsw);
+            *hnsw_lock = Some(Graph::built(hnsw));
             info!("Created new HNSW index");
         }
 
         let idx = id_map.len();
-        hnsw_lock.as_ref().unwrap().insert((&vector, idx));
+        hnsw_lock.as_ref().unwrap().hnsw.insert((&vector, idx));
         id_map.push(doc_id.clone());
         reverse_map.insert(doc_id, idx);
 
@@ -150,6 +239,166 @@ impl VectorIndex {
         Ok(())
     }
 
+    /// Remove a document from the index
+    ///
+    /// The point stays in the graph as a tombstone until the next compaction.
+    /// Returns `false` if the document was not indexed.
+    pub fn delete(&self, doc_id: &str) -> bool {
+        let mut reverse_map = self.reverse_map.write().unwrap();
+        let mut tombstones = self.tombstones.write().unwrap();
+
+        match reverse_map.remove(doc_id) {
+            Some(idx) => {
+                tombstones.insert(idx);
+                debug!("Deleted document {} from index", doc_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fraction of points in the graph that are tombstones
+    pub fn tombstone_ratio(&self) -> f64 {
+        let total = self.id_map.read().unwrap().len();
+        if total == 0 {
+            return 0.0;
+        }
+        self.tombstones.read().unwrap().len() as f64 / total as f64
+    }
+
+    /// Check if enough points are tombstoned to warrant a compaction
+    pub fn needs_compaction(&self) -> bool {
+        !self.compacting.load(Ordering::SeqCst)
+            && self.tombstone_ratio() > self.config.compaction_threshold
+    }
+
+    /// Rebuild the graph without its tombstones
+    ///
+    /// The new graph is built from a snapshot of the live points while searches
+    /// keep using the old one; points added or deleted in the meantime are
+    /// applied before the new graph is swapped in. Does nothing if another
+    /// compaction is already running.
+    pub fn compact(&self) -> Result<()> {
+        if self
+            .compacting
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        let result = self.compact_inner();
+        self.compacting.store(false, Ordering::SeqCst);
+        result
+    }
+
+    fn compact_inner(&self) -> Result<()> {
+        // Snapshot the live points
+        let (generation, snapshot_len, live) = {
+            let hnsw_lock = self.hnsw.read().unwrap();
+            let id_map = self.id_map.read().unwrap();
+            let tombstones = self.tombstones.read().unwrap();
+
+            let Some(Graph { hnsw, .. }) = hnsw_lock.as_ref() else {
+                return Ok(());
+            };
+
+            let mut live: Vec<(usize, Vec<f32>)> = hnsw
+                .get_point_indexation()
+                .into_iter()
+                .filter(|point| !tombstones.contains(&point.get_origin_id()))
+                .map(|point| (point.get_origin_id(), point.get_v().to_vec()))
+                .collect();
+            live.sort_by_key(|(idx, _)| *idx);
+
+            (self.generation.load(Ordering::SeqCst), id_map.len(), live)
+        };
+
+        info!(
+            "Compacting HNSW index ({} live of {} points)",
+            live.len(),
+            snapshot_len
+        );
+
+        // Build the new graph without holding any locks
+        let compacted: Hnsw<'static, f32, DistCosine> = Hnsw::new(
+            self.config.hnsw_m,
+            live.len().max(1),
+            HNSW_MAX_LAYERS,
+            self.config.hnsw_ef_construction,
+            DistCosine,
+        );
+        let mut old_to_new = HashMap::with_capacity(live.len());
+        for (new_idx, (old_idx, vector)) in live.iter().enumerate() {
+            compacted.insert((vector, new_idx));
+            old_to_new.insert(*old_idx, new_idx);
+        }
+
+        let mut hnsw_lock = self.hnsw.write().unwrap();
+        let mut id_map = self.id_map.write().unwrap();
+        let mut reverse_map = self.reverse_map.write().unwrap();
+        let mut tombstones = self.tombstones.write().unwrap();
+
+        if self.generation.load(Ordering::SeqCst) != generation {
+            debug!("Index rebuilt during compaction, discarding compacted graph");
+            return Ok(());
+        }
+        let Some(Graph { hnsw: old, .. }) = hnsw_lock.as_ref() else {
+            return Ok(());
+        };
+
+        let mut new_id_map: Vec<String> = live
+            .iter()
+            .map(|(old_idx, _)| id_map[*old_idx].clone())
+            .collect();
+
+        // Replay points added since the snapshot
+        let mut added: Vec<(usize, Vec<f32>)> = old
+            .get_point_indexation()
+            .into_iter()
+            .filter(|point| point.get_origin_id() >= snapshot_len)
+            .map(|point| (point.get_origin_id(), point.get_v().to_vec()))
+            .collect();
+        added.sort_by_key(|(idx, _)| *idx);
+        for (old_idx, vector) in added {
+            let new_idx = new_id_map.len();
+            compacted.insert((&vector, new_idx));
+            new_id_map.push(id_map[old_idx].clone());
+            old_to_new.insert(old_idx, new_idx);
+        }
+
+        // Carry over tombstones created since the snapshot
+        let new_tombstones: HashSet<usize> = tombstones
+            .iter()
+            .filter_map(|old_idx| old_to_new.get(old_idx).copied())
+            .collect();
+
+        let new_reverse_map: HashMap<String, usize> = new_id_map
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !new_tombstones.contains(idx))
+            .map(|(idx, doc_id)| (doc_id.clone(), idx))
+            .collect();
+
+        info!(
+            "HNSW index compacted: {} -> {} points",
+            id_map.len(),
+            new_id_map.len()
+        );
+
+        *hnsw_lock = if new_id_map.is_empty() {
+            None
+        } else {
+            Some(Graph::built(compacted))
+        };
+        *id_map = new_id_map;
+        *reverse_map = new_reverse_map;
+        *tombstones = new_tombstones;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        Ok(())
+    }
+
     /// Search for k nearest neighbors
     pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<(String, f32)>> {
         if query.len() != self.dimensions {
@@ -162,15 +411,21 @@ impl VectorIndex {
 
         let hnsw_lock = self.hnsw.read().unwrap();
         let id_map = self.id_map.read().unwrap();
+        let tombstones = self.tombstones.read().unwrap();
 
         if hnsw_lock.is_none() {
             return Ok(Vec::new());
         }
 
-        let hnsw = hnsw_lock.as_ref().unwrap();
+        let hnsw = &hnsw_lock.as_ref().unwrap().hnsw;
 
-        // Search HNSW (returns Vec<Neighbour>)
-        let neighbors = hnsw.search(query, k, self.config.hnsw_ef_search);
+        // Search HNSW (returns Vec<Neighbour>), skipping deleted and replaced points
+        let neighbors = if tombstones.is_empty() {
+            hnsw.search(query, k, self.config.hnsw_ef_search)
+        } else {
+            let live = |idx: &usize| !tombstones.contains(idx);
+            hnsw.search_filter(query, k, self.config.hnsw_ef_search, Some(&live))
+        };
 
         // Map indices to document IDs with similarity scores
         let results: Vec<(String, f32)> = neighbors
@@ -194,9 +449,181 @@ impl VectorIndex {
         Ok(results)
     }
 
-    /// Get number of indexed documents
+    /// Save the index to `{dir}/{basename}.hnsw.graph`/`.hnsw.data` plus a
+    /// `{basename}.hnsw.json` manifest
+    pub fn save(
+        &self,
+        dir: &Path,
+        basename: &str,
+        row_count: i64,
+        watermark: Option<String>,
+    ) -> Result<()> {
+        let hnsw_lock = self.hnsw.read().unwrap();
+        let hnsw = match hnsw_lock.as_ref() {
+            Some(graph) => &graph.hnsw,
+            None => {
+                debug!("Index {} not built, nothing to save", basename);
+                return Ok(());
+            }
+        };
+
+        // Remove the old manifest first so a partial dump is never trusted
+        let manifest_path = manifest_path(dir, basename);
+        if manifest_path.exists() {
+            std::fs::remove_file(&manifest_path)?;
+        }
+
+        hnsw.file_dump(dir, basename)
+            .context("Failed to dump HNSW index")?;
+
+        let manifest = IndexManifest {
+            format_version: INDEX_FORMAT_VERSION,
+            dimensions: self.dimensions,
+            hnsw_m: self.config.hnsw_m,
+            hnsw_ef_construction: self.config.hnsw_ef_construction,
+            id_map: self.id_map.read().unwrap().clone(),
+            tombstones: self.tombstones.read().unwrap().iter().copied().collect(),
+            row_count,
+            watermark,
+        };
+
+        let tmp_path = manifest_path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(&manifest)?)?;
+        std::fs::rename(&tmp_path, &manifest_path)?;
+
+        info!(
+            "Saved HNSW index {} ({} vectors)",
+            basename,
+            manifest.id_map.len()
+        );
+
+        Ok(())
+    }
+
+    /// Load an index saved with [`VectorIndex::save`]
+    ///
+    /// Returns `None` when no index is saved, or when it was built with a
+    /// different format or HNSW configuration and must be rebuilt.
+    pub fn load(
+        dir: &Path,
+        basename: &str,
+        config: IndexConfig,
+    ) -> Result<Option<(Self, IndexManifest)>> {
+        let manifest_path = manifest_path(dir, basename);
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+
+        let manifest: IndexManifest = serde_json::from_slice(&std::fs::read(&manifest_path)?)
+            .context("Failed to parse index manifest")?;
+
+        if manifest.format_version != INDEX_FORMAT_VERSION
+            || manifest.hnsw_m != config.hnsw_m
+            || manifest.hnsw_ef_construction != config.hnsw_ef_construction
+        {
+            info!(
+                "Saved HNSW index {} does not match current format/config, ignoring",
+                basename
+            );
+            return Ok(None);
+        }
+
+        // The loaded graph borrows from its loader, which is kept with it
+        let loader = Loader(Box::into_raw(Box::new(HnswIo::new(dir, basename))));
+        // SAFETY: the loader lives at a fixed address until the graph is dropped
+        let hnsw: Hnsw<'static, f32, DistCosine> = unsafe { &mut *loader.0 }
+            .load_hnsw()
+            .context("Failed to load HNSW index")?;
+        let graph = Graph {
+            hnsw,
+            _loader: Some(loader),
+        };
+
+        if graph.hnsw.get_nb_point() != manifest.id_map.len() {
+            anyhow::bail!(
+                "HNSW index {} has {} points but manifest lists {}",
+                basename,
+                graph.hnsw.get_nb_point(),
+                manifest.id_map.len()
+            );
+        }
+
+        let tombstones: HashSet<usize> = manifest.tombstones.iter().copied().collect();
+        let reverse_map = manifest
+            .id_map
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !tombstones.contains(idx))
+            .map(|(idx, doc_id)| (doc_id.clone(), idx))
+            .collect();
+
+        let index = Self {
+            hnsw: Arc::new(RwLock::new(Some(graph))),
+            id_map: Arc::new(RwLock::new(manifest.id_map.clone())),
+            reverse_map: Arc::new(RwLock::new(reverse_map)),
+            tombstones: Arc::new(RwLock::new(tombstones)),
+            generation: AtomicU64::new(0),
+            compacting: AtomicBool::new(false),
+            dimensions: manifest.dimensions,
+            config,
+        };
+
+        info!(
+            "Loaded HNSW index {} ({} vectors)",
+            basename,
+            manifest.id_map.len()
+        );
+
+        Ok(Some((index, manifest)))
+    }
+
+    /// Delete the files of a saved index
+    pub fn remove_files(dir: &Path, basename: &str) -> Result<()> {
+        for suffix in ["hnsw.json", "hnsw.graph", "hnsw.data"] {
+            let path = dir.join(format!("{}.{}", basename, suffix));
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Total size of the files of a saved index, or `None` if none is saved
+    pub fn saved_size(dir: &Path, basename: &str) -> Option<u64> {
+        if !manifest_path(dir, basename).exists() {
+            return None;
+        }
+        let size = ["hnsw.json", "hnsw.graph", "hnsw.data"]
+            .iter()
+            .filter_map(|suffix| std::fs::metadata(dir.join(format!("{}.{}", basename, suffix))).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        Some(size)
+    }
+
+    /// Copy the files of a saved index to another directory
+    ///
+    /// Returns `false` if no index is saved under `basename`.
+    pub fn copy_files(from: &Path, to: &Path, basename: &str) -> Result<bool> {
+        if !manifest_path(from, basename).exists() {
+            return Ok(false);
+        }
+        for suffix in ["hnsw.graph", "hnsw.data", "hnsw.json"] {
+            let name = format!("{}.{}", basename, suffix);
+            std::fs::copy(from.join(&name), to.join(&name))
+                .with_context(|| format!("Failed to copy {}", name))?;
+        }
+        Ok(true)
+    }
+
+    /// Get vector dimensions
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Get number of indexed documents (excluding tombstones)
     pub fn len(&self) -> usize {
-        self.id_map.read().unwrap().len()
+        self.reverse_map.read().unwrap().len()
     }
 
     /// Check if index is empty
@@ -214,6 +641,164 @@ impl VectorIndex {
         *self.hnsw.write().unwrap() = None;
         self.id_map.write().unwrap().clear();
         self.reverse_map.write().unwrap().clear();
+        self.tombstones.write().unwrap().clear();
+        self.generation.fetch_add(1, Ordering::SeqCst);
         info!("Vector index cleared");
     }
 }
+
+/// Path of the manifest for a saved index
+fn manifest_path(dir: &Path, basename: &str) -> PathBuf {
+    dir.join(format!("{}.hnsw.json", basename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> IndexConfig {
+        IndexConfig {
+            hnsw_m: 8,
+            hnsw_ef_construction: 50,
+            hnsw_ef_search: 50,
+            compaction_threshold: 0.2,
+        }
+    }
+
+    fn unit(axis: usize) -> Vec<f32> {
+        let mut v = vec![0.0; 4];
+        v[axis] = 1.0;
+        v
+    }
+
+    /// Points of the graph that are not tombstones, by document ID, read
+    /// without searching (recall of a tiny graph is not guaranteed)
+    fn live_points(index: &VectorIndex) -> Vec<(String, Vec<f32>)> {
+        let hnsw_lock = index.hnsw.read().unwrap();
+        let id_map = index.id_map.read().unwrap();
+        let tombstones = index.tombstones.read().unwrap();
+
+        let mut points: Vec<(String, Vec<f32>)> = hnsw_lock
+            .as_ref()
+            .unwrap()
+            .hnsw
+            .get_point_indexation()
+            .into_iter()
+            .filter(|point| !tombstones.contains(&point.get_origin_id()))
+            .map(|point| (id_map[point.get_origin_id()].clone(), point.get_v().to_vec()))
+            .collect();
+        points.sort_by(|a, b| a.0.cmp(&b.0));
+        points
+    }
+
+    #[test]
+    fn test_delete_and_replace() {
+        let index = VectorIndex::new(4, test_config());
+        index
+            .build(vec![
+                ("a".to_string(), unit(0)),
+                ("b".to_string(), unit(1)),
+                ("c".to_string(), unit(2)),
+            ])
+            .unwrap();
+
+        // Deleted documents never come back from search
+        assert!(index.delete("a"));
+        assert!(!index.delete("a"));
+        let results = index.search(&unit(0), 3).unwrap();
+        assert!(results.iter().all(|(id, _)| id != "a"));
+        assert_eq!(index.len(), 2);
+
+        // Re-adding a document replaces its vector
+        index.add("b".to_string(), unit(3)).unwrap();
+        let results = index.search(&unit(3), 1).unwrap();
+        assert_eq!(results[0].0, "b");
+        assert!(results[0].1 > 0.99);
+        // The old vector of b is a tombstone, so b is never found by it
+        let results = index.search(&unit(1), 3).unwrap();
+        assert!(results.iter().all(|(id, score)| id != "a" && *score < 0.99));
+        assert_eq!(
+            live_points(&index),
+            vec![("b".to_string(), unit(3)), ("c".to_string(), unit(2))]
+        );
+        assert_eq!(index.len(), 2);
+
+        // 2 of 4 points are tombstones
+        assert!((index.tombstone_ratio() - 0.5).abs() < f64::EPSILON);
+        assert!(index.needs_compaction());
+    }
+
+    #[test]
+    fn test_compact_drops_tombstones() {
+        let index = VectorIndex::new(4, test_config());
+        index
+            .build((0..4).map(|i| (format!("doc{}", i), unit(i))).collect())
+            .unwrap();
+        index.delete("doc0");
+        index.add("doc1".to_string(), unit(2)).unwrap();
+
+        index.compact().unwrap();
+
+        assert_eq!(index.tombstone_ratio(), 0.0);
+        assert!(!index.needs_compaction());
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.id_map.read().unwrap().len(), 3);
+
+        let results = index.search(&unit(2), 2).unwrap();
+        let ids: HashSet<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, HashSet::from(["doc1", "doc2"]));
+        assert!(results.iter().all(|(_, score)| *score > 0.99));
+
+        // Compacting away every point leaves an empty, reusable index
+        for i in 1..4 {
+            index.delete(&format!("doc{}", i));
+        }
+        index.compact().unwrap();
+        assert!(index.is_empty());
+        assert!(!index.is_built());
+        index.add("doc5".to_string(), unit(0)).unwrap();
+        assert_eq!(index.search(&unit(0), 1).unwrap()[0].0, "doc5");
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("kuiperdb-index-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let index = VectorIndex::new(3, test_config());
+        index
+            .build(vec![
+                ("a".to_string(), vec![1.0, 0.0, 0.0]),
+                ("b".to_string(), vec![0.0, 1.0, 0.0]),
+                ("c".to_string(), vec![0.0, 0.0, 1.0]),
+            ])
+            .unwrap();
+        index
+            .save(&dir, "db.docs", 3, Some("2026-01-01 00:00:00".to_string()))
+            .unwrap();
+
+        let (loaded, manifest) = VectorIndex::load(&dir, "db.docs", test_config())
+            .unwrap()
+            .expect("saved index should load");
+        assert_eq!(manifest.row_count, 3);
+        assert_eq!(manifest.watermark.as_deref(), Some("2026-01-01 00:00:00"));
+        assert_eq!(loaded.len(), 3);
+
+        let results = loaded.search(&[0.0, 1.0, 0.1], 1).unwrap();
+        assert_eq!(results[0].0, "b");
+
+        // A different HNSW configuration invalidates the saved index
+        let other = IndexConfig {
+            hnsw_m: 16,
+            ..test_config()
+        };
+        assert!(VectorIndex::load(&dir, "db.docs", other).unwrap().is_none());
+
+        VectorIndex::remove_files(&dir, "db.docs").unwrap();
+        assert!(VectorIndex::load(&dir, "db.docs", test_config())
+            .unwrap()
+            .is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
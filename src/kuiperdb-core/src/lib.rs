@@ -4,28 +4,49 @@
 //! - Database storage layer
 //! - Vector indexing with HNSW
 //! - Graph operations
-//! - Search functionality
+//! - Document version history
+//! - Soft delete with a trash and retention purging
+//! - Document expiry (TTL)
+//! - Online backup and restore
+//! - Outgoing webhooks for document events
+//! - Per-database quotas and limits
+//! - Duplicate detection at ingest
+//! - Search functionality and filtering
 //! - Embedding generation and chunking
 //! - Caching layer
 
+pub mod backup;
 pub mod cache;
 pub mod chunking;
 pub mod config;
+pub mod dedup;
 pub mod embedder;
+pub mod filter;
 pub mod graph;
+pub mod history;
 pub mod index;
 pub mod models;
+pub mod purger;
+pub mod quota;
+pub mod reaper;
 pub mod search;
 pub mod store;
+pub mod webhooks;
 pub mod worker;
 
 // Re-export commonly used types
+pub use backup::BackupScheduler;
 pub use cache::EmbeddingCache;
 pub use config::Config;
 pub use embedder::Embedder;
+pub use filter::SearchFilter;
 pub use graph::GraphStatistics;
 pub use index::VectorIndex;
 pub use models::*;
+pub use purger::TrashPurger;
+pub use quota::QuotaExceeded;
+pub use reaper::ExpiryReaper;
 pub use search::{HybridSearcher, SearchResult};
 pub use store::DocumentStore;
+pub use webhooks::{WebhookDispatcher, WebhookManager};
 pub use worker::BackgroundWorker;
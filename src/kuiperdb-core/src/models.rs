@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 
+use crate::cache::hash_content;
+
 // Serialize DateTime<Utc> as Unix timestamp in milliseconds
 fn serialize_datetime_as_millis<S>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -21,11 +23,43 @@ where
         .ok_or_else(|| Error::custom(format!("invalid timestamp: {}", millis)))
 }
 
+// Serialize an optional DateTime<Utc> as Unix timestamp in milliseconds
+fn serialize_optional_datetime_as_millis<S>(
+    dt: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match dt {
+        Some(dt) => serializer.serialize_some(&dt.timestamp_millis()),
+        None => serializer.serialize_none(),
+    }
+}
+
+// Deserialize an optional Unix timestamp in milliseconds to DateTime<Utc>
+fn deserialize_optional_datetime_from_millis<'de, D>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    Option::<i64>::deserialize(deserializer)?
+        .map(|millis| {
+            DateTime::from_timestamp_millis(millis)
+                .ok_or_else(|| Error::custom(format!("invalid timestamp: {}", millis)))
+        })
+        .transpose()
+}
+
 /// Document represents a stored document with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
     pub id: String,
+    #[serde(default)]
     pub db: String,
+    #[serde(default)]
     pub table: String,
     pub content: String, // Markdown text
     #[serde(default)]
@@ -59,6 +93,69 @@ pub struct Document {
     pub token_count: Option<i32>, // Cached token count
     #[serde(default)]
     pub is_vectorized: bool, // Whether document has embeddings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>, // Model of a client-supplied vector
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_datetime_as_millis",
+        deserialize_with = "deserialize_optional_datetime_from_millis"
+    )]
+    pub expires_at: Option<DateTime<Utc>>, // Hidden and then deleted after this time
+}
+
+impl Document {
+    /// Build a new top-level document from a store request
+    ///
+    /// A client-supplied vector is kept as-is and marks the document embedded.
+    pub fn from_request(db_id: &str, table_name: &str, request: StoreDocumentRequest) -> Self {
+        let now = Utc::now();
+        let is_embedded = request.vector.is_some();
+        let expires_at = request.expires_at.or_else(|| {
+            request
+                .ttl_seconds
+                .map(|ttl| now + chrono::Duration::seconds(ttl.min(i64::MAX as u64) as i64))
+        });
+
+        Self {
+            id: request
+                .id
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            db: db_id.to_string(),
+            table: table_name.to_string(),
+            content: request.content,
+            metadata: request.metadata,
+            tags: request.tags,
+            vector: request.vector,
+            created_at: now,
+            updated_at: now,
+            is_embedded,
+            vectorize: request.vectorize,
+            is_chunk: false,
+            parent_id: None,
+            chunk_index: None,
+            token_count: None,
+            is_vectorized: is_embedded,
+            embedding_model: request.model,
+            expires_at,
+        }
+    }
+
+    /// Replace the content if it differs (by hash), dropping the now stale
+    /// embedding and token count. Returns whether the content changed.
+    pub fn replace_content(&mut self, content: &str) -> bool {
+        if hash_content(content) == hash_content(&self.content) {
+            return false;
+        }
+
+        self.content = content.to_string();
+        self.vector = None;
+        self.is_embedded = false;
+        self.is_vectorized = false;
+        self.embedding_model = None;
+        self.token_count = None;
+        true
+    }
 }
 
 fn default_true() -> bool {
@@ -77,12 +174,658 @@ pub struct StoreDocumentRequest {
     pub tags: Vec<String>,
     #[serde(default = "default_true")]
     pub vectorize: bool, // Per-document embedding toggle
+    #[serde(default)]
+    pub vector: Option<Vec<f32>>, // Precomputed embedding, stored instead of embedding server-side
+    #[serde(default)]
+    pub model: Option<String>, // Model that produced `vector`
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_datetime_from_millis"
+    )]
+    pub expires_at: Option<DateTime<Utc>>, // Unix milliseconds after which the document expires
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>, // Expire this many seconds after storing, instead of `expires_at`
+}
+
+impl StoreDocumentRequest {
+    /// Check that at most one, future expiry is given
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match (self.expires_at, self.ttl_seconds) {
+            (Some(_), Some(_)) => {
+                anyhow::bail!("expires_at and ttl_seconds are mutually exclusive")
+            }
+            (Some(expires_at), None) if expires_at <= Utc::now() => {
+                anyhow::bail!("expires_at must be in the future")
+            }
+            (None, Some(0)) => anyhow::bail!("ttl_seconds must be positive"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// PatchDocumentRequest represents a partial update of a document
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PatchDocumentRequest {
+    #[serde(default)]
+    pub content: Option<String>, // New content; only re-embedded if it actually changed
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>, // JSON merge patch (RFC 7396) of the metadata
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+    #[serde(default)]
+    pub vectorize: Option<bool>,
+}
+
+impl PatchDocumentRequest {
+    /// Check the patch can be applied to any document
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.content.as_deref() == Some("") {
+            anyhow::bail!("content must not be empty");
+        }
+        if let Some(ref metadata) = self.metadata {
+            if !metadata.is_object() {
+                anyhow::bail!("metadata patch must be a JSON object");
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the patch touches metadata or tags
+    pub fn changes_labels(&self) -> bool {
+        self.metadata.is_some() || !self.add_tags.is_empty() || !self.remove_tags.is_empty()
+    }
+
+    /// Apply the patch to a document, returning whether its content changed
+    ///
+    /// Content is compared by hash, like the embedding cache does. Changed content
+    /// drops the document's embedding and token count. Tags are removed before
+    /// others are added.
+    pub fn apply(&self, doc: &mut Document) -> anyhow::Result<bool> {
+        self.validate()?;
+
+        if let Some(serde_json::Value::Object(ref entries)) = self.metadata {
+            for (key, value) in entries {
+                if value.is_null() {
+                    doc.metadata.remove(key);
+                } else {
+                    merge_patch(
+                        doc.metadata
+                            .entry(key.clone())
+                            .or_insert(serde_json::Value::Null),
+                        value,
+                    );
+                }
+            }
+        }
+
+        doc.tags.retain(|tag| !self.remove_tags.contains(tag));
+        for tag in &self.add_tags {
+            if !doc.tags.contains(tag) {
+                doc.tags.push(tag.clone());
+            }
+        }
+
+        if let Some(vectorize) = self.vectorize {
+            doc.vectorize = vectorize;
+        }
+
+        let content_changed = match self.content {
+            Some(ref content) => doc.replace_content(content),
+            None => false,
+        };
+
+        doc.updated_at = Utc::now();
+        Ok(content_changed)
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7396) to `target`
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(entries) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    if let Some(fields) = target.as_object_mut() {
+        for (key, value) in entries {
+            if value.is_null() {
+                fields.remove(key);
+            } else {
+                merge_patch(
+                    fields.entry(key.clone()).or_insert(serde_json::Value::Null),
+                    value,
+                );
+            }
+        }
+    }
+}
+
+/// TableSettings holds the per-table options stored with a database
+///
+/// Overrides left unset fall back to the server configuration.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableSettings {
+    #[serde(default)]
+    pub history: bool, // Keep prior revisions of root documents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>, // Model the worker and searches embed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_dimensions: Option<usize>,
+    #[serde(default, skip_serializing_if = "ChunkingSettings::is_empty")]
+    pub chunking: ChunkingSettings,
+    #[serde(default, skip_serializing_if = "VectorIndexSettings::is_empty")]
+    pub vector_index: VectorIndexSettings,
+    #[serde(default, skip_serializing_if = "DedupSettings::is_empty")]
+    pub dedup: DedupSettings,
+}
+
+impl TableSettings {
+    /// Check the overrides could be applied to any configuration
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.embedding_model.as_deref() == Some("") {
+            anyhow::bail!("embedding_model must not be empty");
+        }
+        if self.embedding_dimensions == Some(0) {
+            anyhow::bail!("embedding_dimensions must be positive");
+        }
+        self.chunking.validate()?;
+        self.vector_index.validate()?;
+        self.dedup.validate()
+    }
+
+    /// Whether documents are embedded differently under `other`, so existing
+    /// embeddings no longer match
+    pub fn changes_embedding(&self, other: &TableSettings) -> bool {
+        self.embedding_model != other.embedding_model
+            || self.embedding_dimensions != other.embedding_dimensions
+    }
+}
+
+/// ChunkingSettings overrides the chunking configuration for one table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkingSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_threshold: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_overlap: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>, // "fixed_tokens" or "markdown"
+}
+
+impl ChunkingSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.chunk_size == Some(0) {
+            anyhow::bail!("chunking.chunk_size must be positive");
+        }
+        if let (Some(size), Some(overlap)) = (self.chunk_size, self.chunk_overlap) {
+            if overlap >= size {
+                anyhow::bail!("chunking.chunk_overlap must be smaller than chunk_size");
+            }
+        }
+        if let Some(ref strategy) = self.strategy {
+            if !["fixed_tokens", "markdown"].contains(&strategy.as_str()) {
+                anyhow::bail!("Unknown chunking strategy: {}", strategy);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// VectorIndexSettings overrides the HNSW index configuration for one table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VectorIndexSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>, // "auto", "always" or "never"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hnsw_m: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hnsw_ef_construction: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hnsw_ef_search: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compaction_threshold: Option<f64>,
+}
+
+impl VectorIndexSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(ref mode) = self.mode {
+            if !["auto", "always", "never"].contains(&mode.as_str()) {
+                anyhow::bail!("Unknown vector index mode: {}", mode);
+            }
+        }
+        for (name, value) in [
+            ("hnsw_m", self.hnsw_m),
+            ("hnsw_ef_construction", self.hnsw_ef_construction),
+            ("hnsw_ef_search", self.hnsw_ef_search),
+        ] {
+            if value == Some(0) {
+                anyhow::bail!("vector_index.{} must be positive", name);
+            }
+        }
+        if let Some(threshold) = self.compaction_threshold {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                anyhow::bail!("vector_index.compaction_threshold must be in (0, 1]");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// DedupSettings detects documents that duplicate one already in the table when
+/// they are written, e.g. the same page ingested from another source
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DedupSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<DedupPolicy>, // No duplicate detection if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub near: Option<NearDuplicateMethod>, // Only exact duplicates if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>, // Similarity from which a document is a near-duplicate
+}
+
+impl DedupSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Similarity from which a document is a near-duplicate by `method`
+    pub fn threshold_for(&self, method: NearDuplicateMethod) -> f64 {
+        self.threshold.unwrap_or(match method {
+            NearDuplicateMethod::Vector => crate::dedup::DEFAULT_VECTOR_THRESHOLD,
+            NearDuplicateMethod::Minhash => crate::dedup::DEFAULT_MINHASH_THRESHOLD,
+        })
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(threshold) = self.threshold {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                anyhow::bail!("dedup.threshold must be in (0, 1]");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// DedupPolicy is what happens to a document that duplicates an existing one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupPolicy {
+    /// The document is not written
+    Reject,
+    /// Its tags and new metadata keys are added to the existing document instead
+    Merge,
+    /// It is written with a `duplicate_of` relation to the existing document
+    Link,
+}
+
+/// NearDuplicateMethod defines how documents with similar content are found
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NearDuplicateMethod {
+    /// Cosine similarity of the embeddings, for documents written with one
+    Vector,
+    /// Estimated overlap of the content's word shingles
+    Minhash,
+}
+
+/// DuplicateKind tells whether a duplicate has the same or similar content
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateKind {
+    Exact,
+    Near,
+}
+
+/// Duplicate is the existing document a written one duplicates
+#[derive(Debug, Clone, Serialize)]
+pub struct Duplicate {
+    pub id: String,
+    pub kind: DuplicateKind,
+    pub similarity: f64,
+}
+
+/// DuplicatesQuery selects how duplicate clusters are found; unset fields fall
+/// back to the table's dedup settings
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DuplicatesQuery {
+    pub near: Option<NearDuplicateMethod>,
+    pub threshold: Option<f64>,
+    pub limit: Option<usize>, // Most clusters listed (default 100, max 1000)
+}
+
+/// DuplicateCluster is a group of live root documents that duplicate each other,
+/// oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCluster {
+    pub kind: DuplicateKind, // Exact if every document has the same content
+    pub ids: Vec<String>,
+}
+
+/// DuplicateReport lists the duplicate clusters of a table
+#[derive(Debug, Serialize)]
+pub struct DuplicateReport {
+    pub db: String,
+    pub table: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub near: Option<NearDuplicateMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    /// Documents that duplicate the first of their cluster
+    pub duplicate_count: usize,
+    pub clusters: Vec<DuplicateCluster>,
+    /// Whether clusters were left out past the limit, or too many embedded
+    /// documents to compare without an index
+    pub truncated: bool,
+}
+
+/// BackupOptions selects what a database backup includes besides the database
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BackupOptions {
+    #[serde(default)]
+    pub include_index: bool, // Saved HNSW indexes of the database's tables
+    #[serde(default)]
+    pub include_cache: bool, // Global embedding cache
+}
+
+/// RestoreRequest selects the backup to restore a database from
+#[derive(Debug, Clone, Deserialize)]
+pub struct RestoreRequest {
+    #[serde(default)]
+    pub backup: Option<String>, // Backup ID (default: the latest)
+    #[serde(default = "default_true")]
+    pub include_index: bool, // Restore HNSW indexes if the backup has them
+    #[serde(default)]
+    pub include_cache: bool, // Merge the backed-up embedding cache into the current one
+}
+
+/// BackupInfo describes a backup, as stored in its `backup.json` manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub id: String,
+    pub db: String,
+    #[serde(
+        serialize_with = "serialize_datetime_as_millis",
+        deserialize_with = "deserialize_datetime_from_millis"
+    )]
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+    pub tables: Vec<String>,
+    #[serde(default)]
+    pub indexes: Vec<String>, // Tables whose HNSW index is included
+    #[serde(default)]
+    pub includes_cache: bool,
+}
+
+/// MetadataIndex declares an indexed metadata field of a table
+///
+/// The field is copied into a generated column with a B-tree index, which
+/// filters on the field use for values of the declared type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataIndex {
+    pub field: String, // Dot-separated path into the metadata
+    #[serde(rename = "type")]
+    pub index_type: MetadataIndexType,
+}
+
+impl MetadataIndex {
+    /// Check that the field is a valid metadata path
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !crate::filter::is_valid_field_path(&self.field) {
+            anyhow::bail!("Invalid metadata field: {}", self.field);
+        }
+        Ok(())
+    }
+
+    /// Name of the generated column holding the field
+    pub fn column_name(&self) -> String {
+        format!("meta.{}", self.field)
+    }
+}
+
+/// MetadataIndexType is the JSON type of values kept in a metadata index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataIndexType {
+    /// JSON strings
+    Text,
+    /// JSON integers
+    Integer,
+    /// Any JSON number
+    Real,
+}
+
+impl MetadataIndexType {
+    /// Column type of the generated column
+    pub fn sql_type(self) -> &'static str {
+        match self {
+            MetadataIndexType::Text => "TEXT",
+            MetadataIndexType::Integer => "INTEGER",
+            MetadataIndexType::Real => "REAL",
+        }
+    }
+
+    /// Values of `json_type()` kept in the index; other values are left out
+    pub fn json_types(self) -> &'static str {
+        match self {
+            MetadataIndexType::Text => "'text'",
+            MetadataIndexType::Integer => "'integer'",
+            MetadataIndexType::Real => "'integer', 'real'",
+        }
+    }
+
+    /// Whether a filter value can be looked up in the index
+    pub fn accepts(self, value: &serde_json::Value) -> bool {
+        match self {
+            MetadataIndexType::Text => value.is_string(),
+            MetadataIndexType::Integer => value.is_i64(),
+            MetadataIndexType::Real => value.is_number(),
+        }
+    }
+}
+
+/// DocumentRevision is one recorded version of a document in a table with history
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentRevision {
+    pub doc_id: String,
+    pub rev: i64, // Starts at 1 and increases with every change
+    pub content: String,
+    pub metadata: HashMap<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(serialize_with = "serialize_datetime_as_millis")]
+    pub recorded_at: DateTime<Utc>, // When this revision became current
+    pub deleted: bool, // Marks the deletion of the document
+}
+
+/// RestoreRevisionRequest selects the revision to restore
+#[derive(Debug, Deserialize)]
+pub struct RestoreRevisionRequest {
+    pub rev: i64,
+}
+
+/// BulkMode defines how a bulk write treats failing items
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// All items are written or none are
+    #[default]
+    Atomic,
+    /// Failing items are skipped and the rest are written
+    BestEffort,
+}
+
+/// BulkItemResult reports the outcome of one item of a bulk write
+#[derive(Debug, Serialize)]
+pub struct BulkItemResult {
+    pub index: usize, // Position among the non-empty input lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub status: u16, // HTTP-style status for this item
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<usize>, // Chunks created for a stored document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>, // Existing document it was merged into or linked to
+}
+
+/// BulkResponse represents the result of a bulk write
+#[derive(Debug, Serialize)]
+pub struct BulkResponse {
+    pub mode: BulkMode,
+    pub committed: bool,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub items: Vec<BulkItemResult>,
+}
+
+/// ListDocumentsQuery selects one page of a table's documents
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListDocumentsQuery {
+    #[serde(default)]
+    pub limit: Option<usize>, // Page size, defaults to 100
+    #[serde(default)]
+    pub cursor: Option<String>, // `next_cursor` of the previous page
+    #[serde(default)]
+    pub sort: SortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    #[serde(default)]
+    pub kind: DocumentKind,
+    #[serde(default)]
+    pub not_embedded: bool, // Only documents that have no embedding yet
+    #[serde(default = "default_true")]
+    pub include_content: bool,
+    #[serde(default)]
+    pub include_vector: bool,
+}
+
+impl Default for ListDocumentsQuery {
+    fn default() -> Self {
+        Self {
+            limit: None,
+            cursor: None,
+            sort: SortField::default(),
+            order: SortOrder::default(),
+            kind: DocumentKind::default(),
+            not_embedded: false,
+            include_content: true,
+            include_vector: false,
+        }
+    }
+}
+
+/// SortField is the timestamp a document listing is ordered by (ties broken by ID)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl SortField {
+    /// Column holding this field
+    pub fn column(self) -> &'static str {
+        match self {
+            SortField::CreatedAt => "created_at",
+            SortField::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+/// SortOrder defines the direction of a document listing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// DocumentKind restricts a document listing to roots or chunks
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    /// Documents without a parent
+    #[default]
+    Roots,
+    /// Chunks of other documents
+    Chunks,
+    /// Both roots and chunks
+    All,
+}
+
+/// DocumentPage is one page of a document listing
+#[derive(Debug, Serialize)]
+pub struct DocumentPage {
+    pub documents: Vec<Document>,
+    pub next_cursor: Option<String>, // None on the last page
+}
+
+/// TrashedDocument is a deleted document waiting in the trash to be restored or purged
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedDocument {
+    #[serde(flatten)]
+    pub document: Document,
+    #[serde(serialize_with = "serialize_datetime_as_millis")]
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// TrashedTable is a deleted table waiting in the trash to be restored or purged
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedTable {
+    pub name: String,
+    #[serde(serialize_with = "serialize_datetime_as_millis")]
+    pub deleted_at: DateTime<Utc>,
+    pub documents: i64, // Root documents that were live when it was deleted
+}
+
+/// TagCount is a tag with the number of documents carrying it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+/// RenameTagRequest renames a tag on every document, merging it into `to` where
+/// a document already has that tag
+#[derive(Debug, Deserialize)]
+pub struct RenameTagRequest {
+    pub to: String,
+}
+
+/// SearchFacets holds value counts over the results of a search
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchFacets {
+    pub tags: Vec<TagCount>,
 }
 
 /// SearchRequest represents a search query
 #[derive(Debug, Deserialize)]
 pub struct SearchRequest {
-    pub query: String,
+    #[serde(default)]
+    pub query: String, // Optional with query_vector or more_like_this
     #[serde(rename = "type", default)]
     pub search_type: SearchType,
     #[serde(default)]
@@ -93,6 +836,35 @@ pub struct SearchRequest {
     pub include_chunks: bool, // Include chunks in results
     #[serde(default)]
     pub group_by_parent: bool, // Group chunks under parent
+    #[serde(default)]
+    pub aggregation: ChunkAggregation, // How chunk scores roll up when grouping
+    #[serde(default = "default_max_chunks_per_parent")]
+    pub max_chunks_per_parent: usize, // Evidence chunks kept per grouped parent
+    #[serde(default)]
+    pub query_vector: Option<Vec<f32>>, // Precomputed query embedding
+    #[serde(default)]
+    pub more_like_this: Option<String>, // Find documents similar to this document ID
+    #[serde(default = "default_true")]
+    pub exclude_own_chunks: bool, // With more_like_this, also exclude the document's chunks
+    #[serde(default)]
+    pub facets: bool, // Return tag counts over the results
+}
+
+fn default_max_chunks_per_parent() -> usize {
+    3
+}
+
+/// ChunkAggregation defines how chunk scores combine into a parent score
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkAggregation {
+    /// Best chunk score
+    #[default]
+    Max,
+    /// Sum of chunk scores
+    Sum,
+    /// Reciprocal rank fusion over chunk ranks
+    Rrf,
 }
 
 /// SearchType defines the type of search to perform
@@ -115,19 +887,163 @@ pub struct SearchResponse {
     pub search_type: SearchType,
     pub db: String,
     pub total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facets: Option<SearchFacets>,
 }
 
 /// DBInfo represents information about a database
+///
+/// Counts are totals over its tables, see [`TableStats`].
 #[derive(Debug, Serialize)]
 pub struct DBInfo {
     pub name: String,
+    pub tables: Vec<String>,
     pub document_count: i64,
+    pub chunk_count: i64,
     pub embedded_count: i64,
+    pub pending_count: i64,
+    pub failed_count: i64,
+    pub total_tokens: i64,
     #[serde(serialize_with = "serialize_datetime_as_millis")]
     pub created_at: DateTime<Utc>,
     #[serde(serialize_with = "serialize_datetime_as_millis")]
     pub last_updated: DateTime<Utc>,
+    /// Size of the database file, including its write-ahead log
+    pub size_bytes: i64,
+    pub settings: DatabaseSettings,
+    pub usage: DatabaseUsage,
+}
+
+/// DatabaseSettings are recorded with a database in the catalog
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "DatabaseLimits::is_unlimited")]
+    pub limits: DatabaseLimits,
+}
+
+/// DatabaseLimits are quotas enforced on writes and embeddings in a database
+///
+/// Limits that are not set are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatabaseLimits {
+    /// Live root documents across its tables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_documents: Option<u64>,
+    /// Size of the database file, including its write-ahead log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// Bytes of content of one document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_content_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tables: Option<u64>,
+    /// Embedding requests per minute, by writes, searches and the embedding worker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_embedding_requests_per_minute: Option<u64>,
+}
+
+impl DatabaseLimits {
+    /// Whether no limit is set
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// DatabaseUsage is how much of each of its limits a database uses
+#[derive(Debug, Serialize)]
+pub struct DatabaseUsage {
+    pub documents: QuotaUsage,
+    pub bytes: QuotaUsage,
+    pub tables: QuotaUsage,
+    /// Embedding requests in the last minute
+    pub embedding_requests_per_minute: QuotaUsage,
+}
+
+/// QuotaUsage is the current usage of a limit
+#[derive(Debug, Serialize)]
+pub struct QuotaUsage {
+    pub used: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
+/// QuotaKind names a database limit
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaKind {
+    Documents,
+    Bytes,
+    ContentBytes,
+    Tables,
+    EmbeddingRequestsPerMinute,
+}
+
+impl std::fmt::Display for QuotaKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Documents => "documents",
+            Self::Bytes => "bytes",
+            Self::ContentBytes => "content bytes",
+            Self::Tables => "tables",
+            Self::EmbeddingRequestsPerMinute => "embedding requests per minute",
+        };
+        f.write_str(name)
+    }
+}
+
+/// TableStats describes the contents of a table
+#[derive(Debug, Serialize)]
+pub struct TableStats {
+    pub db: String,
+    pub table: String,
+    /// Live root documents
+    pub document_count: i64,
+    /// Live chunks of those documents
+    pub chunk_count: i64,
+    /// Documents and chunks with an embedding
+    pub embedded_count: i64,
+    /// Documents and chunks waiting for the embedding worker
+    pub pending_count: i64,
+    /// Waiting documents and chunks whose last embedding attempt failed; they are
+    /// retried after the pending ones
+    pub failed_count: i64,
+    /// Documents in the trash
+    pub deleted_count: i64,
+    /// Tokens across root documents
+    pub total_tokens: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_dimensions: Option<usize>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_datetime_as_millis"
+    )]
+    pub last_updated: Option<DateTime<Utc>>,
+    /// Pages used by the table, its full-text, tag and duplicate detection tables,
+    /// history and SQL indexes
     pub size_bytes: i64,
+    pub index: IndexStatus,
+}
+
+/// IndexStatus describes a table's HNSW index
+#[derive(Debug, Serialize)]
+pub struct IndexStatus {
+    /// Whether vector searches on the table may use an index
+    pub enabled: bool,
+    /// Embedded documents needed before the index is built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<usize>,
+    /// Whether the index is in memory and built
+    pub built: bool,
+    /// Live vectors in the index
+    pub entries: usize,
+    /// Fraction of the entries that are deleted
+    pub tombstone_ratio: f64,
+    /// Whether the index is saved next to the database file
+    pub saved: bool,
+    /// Size of the saved index files
+    pub saved_bytes: i64,
 }
 
 /// ErrorResponse represents an API error response
@@ -138,6 +1054,28 @@ pub struct ErrorResponse {
     pub message: Option<String>,
 }
 
+/// QuotaErrorResponse is the API error for a write or embedding over a
+/// database's limits
+#[derive(Debug, Serialize)]
+pub struct QuotaErrorResponse {
+    pub error: String,
+    pub message: String,
+    pub quota: QuotaKind,
+    pub limit: u64,
+    pub used: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_seconds: Option<u64>,
+}
+
+/// DuplicateErrorResponse is the API error for a document rejected as a
+/// duplicate of one already in the table
+#[derive(Debug, Serialize)]
+pub struct DuplicateErrorResponse {
+    pub error: String,
+    pub message: String,
+    pub duplicate: Duplicate,
+}
+
 /// DocumentRelation represents a relationship between two documents
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentRelation {
@@ -154,6 +1092,294 @@ pub struct DocumentRelation {
     pub created_at: DateTime<Utc>,
 }
 
+/// ExportRecord is one line of a table export, tagged by `type`
+///
+/// Exports list documents (roots before chunks) followed by the relations that
+/// touch them, and are read back by import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportRecord {
+    Document(ExportedDocument),
+    Relation(DocumentRelation),
+}
+
+/// ExportedDocument is a document with its vector, which is otherwise not serialized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedDocument {
+    #[serde(flatten)]
+    pub document: Document,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<String>, // Base64 of the stored BLOB (f32 little-endian)
+}
+
+/// ChangeKind is what a change log entry refers to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Document,
+    Relation,
+}
+
+/// ChangeOp is the mutation a change log entry records
+///
+/// Moving a document to the trash is recorded as a delete, and restoring it as an
+/// insert.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Change is one entry of a database's change log
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    pub seq: i64, // Position in the log, increasing with every change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>, // Table of a changed document
+    pub id: String, // Document or relation ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>, // Parent document of a changed chunk
+    pub kind: ChangeKind,
+    pub op: ChangeOp,
+    #[serde(serialize_with = "serialize_datetime_as_millis")]
+    pub recorded_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<Document>, // Current version, with `include_docs`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relation: Option<DocumentRelation>, // Current version, with `include_docs`
+}
+
+/// ChangeFeed selects how a changes request is answered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeFeed {
+    /// Return the changes logged so far
+    #[default]
+    Normal,
+    /// Wait for a change if there are none yet
+    Longpoll,
+    /// Stream changes as Server-Sent Events
+    Sse,
+}
+
+/// ChangesQuery selects entries of a database's change log
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChangesQuery {
+    #[serde(default)]
+    pub since: i64, // Last sequence number seen (0: from the start of the log)
+    #[serde(default)]
+    pub limit: Option<usize>, // Changes per response, defaults to 100
+    #[serde(default)]
+    pub table: Option<String>, // Only changes to this table's documents
+    #[serde(default)]
+    pub feed: ChangeFeed,
+    #[serde(default)]
+    pub timeout: Option<u64>, // Long-poll wait in milliseconds
+    #[serde(default)]
+    pub include_docs: bool, // Attach the current document or relation
+}
+
+/// ChangesResponse is one batch of a database's change log
+#[derive(Debug, Serialize)]
+pub struct ChangesResponse {
+    pub changes: Vec<Change>,
+    pub last_seq: i64, // `since` for the next request
+}
+
+/// WebhookEvent is a document lifecycle event a webhook can subscribe to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum WebhookEvent {
+    #[serde(rename = "document.created")]
+    DocumentCreated,
+    #[serde(rename = "document.updated")]
+    DocumentUpdated,
+    #[serde(rename = "document.deleted")]
+    DocumentDeleted,
+    /// The background worker stored a document's embedding
+    #[serde(rename = "document.embedded")]
+    DocumentEmbedded,
+    /// A document's chunks were written
+    #[serde(rename = "document.chunked")]
+    DocumentChunked,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 5] = [
+        WebhookEvent::DocumentCreated,
+        WebhookEvent::DocumentUpdated,
+        WebhookEvent::DocumentDeleted,
+        WebhookEvent::DocumentEmbedded,
+        WebhookEvent::DocumentChunked,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::DocumentCreated => "document.created",
+            WebhookEvent::DocumentUpdated => "document.updated",
+            WebhookEvent::DocumentDeleted => "document.deleted",
+            WebhookEvent::DocumentEmbedded => "document.embedded",
+            WebhookEvent::DocumentChunked => "document.chunked",
+        }
+    }
+}
+
+/// Webhook is an endpoint notified of a database's document events
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: String,
+    pub db: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>, // HMAC key, only returned when the webhook is created
+    #[serde(serialize_with = "serialize_datetime_as_millis")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// CreateWebhookRequest registers a webhook
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub events: Option<Vec<WebhookEvent>>, // Defaults to every event
+    #[serde(default)]
+    pub secret: Option<String>, // Generated if not given
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// UpdateWebhookRequest changes the fields of a webhook that are given
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateWebhookRequest {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub events: Option<Vec<WebhookEvent>>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+impl CreateWebhookRequest {
+    /// Check the URL is HTTP(S) and at least one event is subscribed to
+    pub fn validate(&self) -> anyhow::Result<()> {
+        check_webhook(Some(&self.url), self.events.as_deref())?;
+        if self.secret.as_deref() == Some("") {
+            anyhow::bail!("secret must not be empty");
+        }
+        Ok(())
+    }
+}
+
+impl UpdateWebhookRequest {
+    /// Check the fields given are valid for a webhook
+    pub fn validate(&self) -> anyhow::Result<()> {
+        check_webhook(self.url.as_deref(), self.events.as_deref())
+    }
+}
+
+fn check_webhook(url: Option<&str>, events: Option<&[WebhookEvent]>) -> anyhow::Result<()> {
+    if let Some(url) = url {
+        let host = url
+            .strip_prefix("http://")
+            .or_else(|| url.strip_prefix("https://"))
+            .ok_or_else(|| anyhow::anyhow!("url must be an http:// or https:// URL"))?;
+        if host.is_empty() || host.starts_with('/') {
+            anyhow::bail!("url must include a host");
+        }
+    }
+    if events.is_some_and(|events| events.is_empty()) {
+        anyhow::bail!("events must not be empty");
+    }
+    Ok(())
+}
+
+/// WebhookPayload is the JSON body POSTed to a webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub db: String,
+    pub table: String,
+    pub document_id: String,
+    #[serde(serialize_with = "serialize_datetime_as_millis")]
+    pub occurred_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<i64>, // Change log position of created, updated and deleted events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>, // Parent document of an embedded chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<usize>, // Chunks written, for `document.chunked`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<Document>, // Document as of the event, if it still exists
+}
+
+impl WebhookPayload {
+    /// Payload of an event that happened now, without optional fields
+    pub fn new(event: WebhookEvent, db: &str, table: &str, document_id: &str) -> Self {
+        Self {
+            event,
+            db: db.to_string(),
+            table: table.to_string(),
+            document_id: document_id.to_string(),
+            occurred_at: Utc::now(),
+            seq: None,
+            parent_id: None,
+            chunks: None,
+            document: None,
+        }
+    }
+}
+
+/// WebhookDeliveryStatus is where a delivery is in its retries
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    /// Not sent yet, or to be retried
+    Pending,
+    /// Acknowledged with a 2xx response
+    Delivered,
+    /// Given up on after the last attempt
+    Failed,
+}
+
+/// WebhookDelivery is one event sent, or to be sent, to a webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    pub payload: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(serialize_with = "serialize_datetime_as_millis")]
+    pub created_at: DateTime<Utc>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_datetime_as_millis"
+    )]
+    pub next_attempt_at: Option<DateTime<Utc>>, // Only while pending
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_datetime_as_millis"
+    )]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// WebhookDeliveriesQuery selects entries of a webhook's delivery log
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookDeliveriesQuery {
+    #[serde(default)]
+    pub status: Option<WebhookDeliveryStatus>,
+    #[serde(default)]
+    pub limit: Option<usize>, // Defaults to 100
+}
+
 /// CreateRelationRequest represents a request to create a document relationship
 #[derive(Debug, Deserialize)]
 pub struct CreateRelationRequest {
@@ -201,6 +1427,8 @@ mod tests {
             chunk_index: None,
             token_count: Some(10),
             is_vectorized: true,
+            embedding_model: None,
+            expires_at: None,
         };
 
         let json = serde_json::to_string(&doc).expect("Failed to serialize");
@@ -231,4 +1459,97 @@ mod tests {
         // Verify timestamp is a number (milliseconds)
         assert!(value["created_at"].is_number(), "created_at should be a number");
     }
+
+    fn patch_target() -> Document {
+        let mut doc = Document::from_request(
+            "db",
+            "docs",
+            StoreDocumentRequest {
+                id: Some("doc".to_string()),
+                content: "Original".to_string(),
+                metadata: HashMap::new(),
+                tags: vec!["a".to_string(), "b".to_string()],
+                vectorize: true,
+                vector: Some(vec![1.0, 0.0]),
+                model: None,
+                expires_at: None,
+                ttl_seconds: None,
+            },
+        );
+        doc.metadata.insert(
+            "author".to_string(),
+            serde_json::json!({"name": "Ada", "email": "ada@example.com"}),
+        );
+        doc.metadata
+            .insert("draft".to_string(), serde_json::json!(true));
+        doc
+    }
+
+    #[test]
+    fn test_patch_merges_metadata_and_tags() {
+        let mut doc = patch_target();
+        let patch: PatchDocumentRequest = serde_json::from_value(serde_json::json!({
+            "metadata": {"author": {"email": null, "org": "ACME"}, "draft": null, "year": 2024},
+            "add_tags": ["c", "a"],
+            "remove_tags": ["b"]
+        }))
+        .unwrap();
+
+        assert!(!patch.apply(&mut doc).unwrap());
+        assert_eq!(
+            serde_json::json!(doc.metadata),
+            serde_json::json!({"author": {"name": "Ada", "org": "ACME"}, "year": 2024})
+        );
+        assert_eq!(doc.tags, vec!["a", "c"]);
+        assert!(doc.vector.is_some());
+    }
+
+    #[test]
+    fn test_patch_invalidates_embedding_only_on_new_content() {
+        let mut doc = patch_target();
+        let same = PatchDocumentRequest {
+            content: Some("Original".to_string()),
+            ..Default::default()
+        };
+        assert!(!same.apply(&mut doc).unwrap());
+        assert!(doc.is_embedded);
+
+        let changed = PatchDocumentRequest {
+            content: Some("Rewritten".to_string()),
+            ..Default::default()
+        };
+        assert!(changed.apply(&mut doc).unwrap());
+        assert_eq!(doc.content, "Rewritten");
+        assert!(doc.vector.is_none());
+        assert!(!doc.is_embedded);
+    }
+
+    #[test]
+    fn test_patch_rejects_non_object_metadata() {
+        let patch = PatchDocumentRequest {
+            metadata: Some(serde_json::json!(["not", "an", "object"])),
+            ..Default::default()
+        };
+        assert!(patch.apply(&mut patch_target()).is_err());
+    }
+
+    #[test]
+    fn test_store_request_expiry() {
+        let request: StoreDocumentRequest =
+            serde_json::from_str(r#"{"content": "note", "ttl_seconds": 60}"#).unwrap();
+        assert!(request.validate().is_ok());
+        let doc = Document::from_request("db", "notes", request);
+        let ttl = doc.expires_at.unwrap() - doc.created_at;
+        assert_eq!(ttl.num_seconds(), 60);
+
+        let both: StoreDocumentRequest = serde_json::from_str(
+            r#"{"content": "note", "ttl_seconds": 60, "expires_at": 4102444800000}"#,
+        )
+        .unwrap();
+        assert!(both.validate().is_err());
+
+        let past: StoreDocumentRequest =
+            serde_json::from_str(r#"{"content": "note", "expires_at": 1000}"#).unwrap();
+        assert!(past.validate().is_err());
+    }
 }
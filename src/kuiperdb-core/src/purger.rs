@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::store::DocumentStore;
+
+/// Background task that permanently deletes documents, tables and databases once
+/// they have been in the trash longer than the retention period, and prunes
+/// change logs past theirs
+pub struct TrashPurger {
+    store: Arc<DocumentStore>,
+    config: Arc<Config>,
+    shutdown: Arc<tokio::sync::Notify>,
+}
+
+impl TrashPurger {
+    pub fn new(store: Arc<DocumentStore>, config: Arc<Config>) -> Self {
+        Self {
+            store,
+            config,
+            shutdown: Arc::new(tokio::sync::Notify::new()),
+        }
+    }
+
+    /// Start the purger
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("Trash purger started");
+
+            let period = Duration::from_secs(self.config.trash.purge_interval_secs.max(1));
+            let mut interval = time::interval(period);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+            loop {
+                tokio::select! {
+                    _ = self.shutdown.notified() => {
+                        info!("Trash purger stopped");
+                        break;
+                    }
+                    _ = interval.tick() => {
+                        if let Err(e) = self.purge_expired().await {
+                            error!("Error purging trash: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Stop the purger
+    pub fn stop(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Purge everything deleted before the retention period, across all databases
+    /// and tables, and prune old change log entries. Returns the number of
+    /// documents, tables and databases removed.
+    pub async fn purge_expired(&self) -> anyhow::Result<usize> {
+        let retention = chrono::Duration::days(self.config.trash.retention_days as i64);
+        let cutoff = chrono::Utc::now() - retention;
+
+        let mut purged = self.store.purge_deleted_databases(cutoff).await?;
+        let databases = self.store.list_databases().await?;
+
+        let changes_cutoff = match self.config.changes.retention_days {
+            0 => None,
+            days => Some(chrono::Utc::now() - chrono::Duration::days(days as i64)),
+        };
+
+        for db_name in databases {
+            if let Some(changes_cutoff) = changes_cutoff {
+                match self.store.prune_changes(&db_name, changes_cutoff).await {
+                    Ok(count) if count > 0 => {
+                        info!("Pruned {} change log entries of {}", count, db_name)
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to prune change log of {}: {}", db_name, e),
+                }
+            }
+
+            match self.store.purge_deleted_tables(&db_name, cutoff).await {
+                Ok(count) => purged += count,
+                Err(e) => warn!("Failed to purge deleted tables of {}: {}", db_name, e),
+            }
+
+            let tables = self.store.list_tables(&db_name).await?;
+
+            for table_name in tables {
+                // One failing table should not hold back the rest
+                match self.store.purge_deleted(&db_name, &table_name, cutoff).await {
+                    Ok(count) => purged += count,
+                    Err(e) => warn!("Failed to purge trash of {}.{}: {}", db_name, table_name, e),
+                }
+            }
+        }
+
+        if purged > 0 {
+            info!("Trash purger: removed {} expired items", purged);
+        }
+
+        Ok(purged)
+    }
+}
+
+impl Drop for TrashPurger {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
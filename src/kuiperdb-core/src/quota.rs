@@ -0,0 +1,113 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::QuotaKind;
+
+/// Window over which embedding requests are counted
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A write or embedding that would take a database over one of its limits
+#[derive(Debug, thiserror::Error)]
+#[error("database {db} is limited to {limit} {kind}, {used} used")]
+pub struct QuotaExceeded {
+    pub db: String,
+    pub kind: QuotaKind,
+    pub limit: u64,
+    /// Usage before the rejected write, or the size of a rejected document
+    pub used: u64,
+    /// When an embedding request would be allowed again
+    pub retry_after: Option<Duration>,
+}
+
+impl QuotaExceeded {
+    /// Whether waiting lifts the limit, i.e. it is a rate rather than a size
+    pub fn is_rate_limit(&self) -> bool {
+        self.kind == QuotaKind::EmbeddingRequestsPerMinute
+    }
+}
+
+/// Embedding requests per database over the last minute
+#[derive(Default)]
+pub struct EmbeddingRateLimiter {
+    requests: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl EmbeddingRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record up to `wanted` requests for a database without going over `limit`
+    /// per minute, returning how many were granted
+    pub fn take(&self, db_id: &str, wanted: usize, limit: Option<u64>) -> usize {
+        let now = Instant::now();
+        let mut requests = self.requests.lock().unwrap();
+        let recent = requests.entry(db_id.to_string()).or_default();
+        expire(recent, now);
+
+        let granted = match limit {
+            Some(limit) => wanted.min((limit as usize).saturating_sub(recent.len())),
+            None => wanted,
+        };
+        recent.extend(std::iter::repeat_n(now, granted));
+        granted
+    }
+
+    /// Requests recorded for a database in the last minute
+    pub fn recent(&self, db_id: &str) -> u64 {
+        let mut requests = self.requests.lock().unwrap();
+        let Some(recent) = requests.get_mut(db_id) else {
+            return 0;
+        };
+        expire(recent, Instant::now());
+        recent.len() as u64
+    }
+
+    /// Time until the oldest recorded request for a database leaves the window
+    pub fn retry_after(&self, db_id: &str) -> Duration {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .get(db_id)
+            .and_then(|recent| recent.front())
+            .map_or(Duration::ZERO, |oldest| {
+                RATE_WINDOW.saturating_sub(oldest.elapsed())
+            })
+    }
+
+    /// Forget the requests of a database, e.g. when it is deleted
+    pub fn forget(&self, db_id: &str) {
+        self.requests.lock().unwrap().remove(db_id);
+    }
+}
+
+/// Drop requests older than the window
+fn expire(recent: &mut VecDeque<Instant>, now: Instant) {
+    while recent
+        .front()
+        .is_some_and(|oldest| now.duration_since(*oldest) >= RATE_WINDOW)
+    {
+        recent.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_grants_up_to_limit_per_database() {
+        let limiter = EmbeddingRateLimiter::new();
+        assert_eq!(limiter.take("a", 3, Some(5)), 3);
+        assert_eq!(limiter.take("a", 3, Some(5)), 2);
+        assert_eq!(limiter.take("a", 1, Some(5)), 0);
+        assert_eq!(limiter.recent("a"), 5);
+        assert!(limiter.retry_after("a") > Duration::ZERO);
+
+        // Other databases and unlimited ones are not held up
+        assert_eq!(limiter.take("b", 10, None), 10);
+        assert_eq!(limiter.recent("b"), 10);
+        limiter.forget("a");
+        assert_eq!(limiter.recent("a"), 0);
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::store::DocumentStore;
+
+/// Background task that deletes documents whose `expires_at` has passed
+///
+/// Expired documents are already hidden from reads and searches; the reaper
+/// reclaims their rows, chunks, relations and index entries.
+pub struct ExpiryReaper {
+    store: Arc<DocumentStore>,
+    config: Arc<Config>,
+    shutdown: Arc<tokio::sync::Notify>,
+}
+
+impl ExpiryReaper {
+    pub fn new(store: Arc<DocumentStore>, config: Arc<Config>) -> Self {
+        Self {
+            store,
+            config,
+            shutdown: Arc::new(tokio::sync::Notify::new()),
+        }
+    }
+
+    /// Start the reaper
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("Expiry reaper started");
+
+            let period = Duration::from_secs(self.config.expiry.reap_interval_secs.max(1));
+            let mut interval = time::interval(period);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+            loop {
+                tokio::select! {
+                    _ = self.shutdown.notified() => {
+                        info!("Expiry reaper stopped");
+                        break;
+                    }
+                    _ = interval.tick() => {
+                        if let Err(e) = self.reap_expired().await {
+                            error!("Error reaping expired documents: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Stop the reaper
+    pub fn stop(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Delete expired documents across all databases and tables, returning how
+    /// many rows were removed
+    pub async fn reap_expired(&self) -> anyhow::Result<usize> {
+        let databases = self.store.list_databases().await?;
+
+        let mut reaped = 0;
+        for db_name in databases {
+            let tables = self.store.list_tables(&db_name).await?;
+
+            for table_name in tables {
+                match self.store.reap_expired(&db_name, &table_name).await {
+                    Ok(count) => reaped += count,
+                    Err(e) => warn!(
+                        "Failed to reap expired documents of {}.{}: {}",
+                        db_name, table_name, e
+                    ),
+                }
+            }
+        }
+
+        Ok(reaped)
+    }
+}
+
+impl Drop for ExpiryReaper {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::embedder::Embedder;
+use crate::filter::SearchFilter;
+use crate::models::{ChunkAggregation, Document, SearchType};
 use crate::store::DocumentStore;
 
 /// Type alias for search result tuples
@@ -31,9 +33,22 @@ type RrfScoreMap = HashMap<
     ),
 >;
 
+/// Candidate multiplier used when chunk hits are collapsed under their parents
+const GROUP_OVERFETCH_FACTOR: usize = 4;
+
 /// Hybrid search combining FTS5 and vector similarity
 pub struct HybridSearcher {
     k: usize, // RRF parameter (typically 60)
+    filter: SearchFilter,
+    grouping: Option<ChunkGrouping>,
+    query_vector: Option<Vec<f32>>,
+}
+
+/// Settings for collapsing chunk hits under their parent document
+#[derive(Debug, Clone, Copy)]
+struct ChunkGrouping {
+    aggregation: ChunkAggregation,
+    max_chunks: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,36 +63,360 @@ pub struct SearchResult {
     pub is_chunk: bool,
     pub parent_id: Option<String>,
     pub chunk_index: Option<i32>,
+    /// Best-matching chunks when results are grouped by parent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<SearchResult>,
 }
 
 impl HybridSearcher {
     pub fn new() -> Self {
-        Self { k: 60 }
+        Self {
+            k: 60,
+            filter: SearchFilter::default(),
+            grouping: None,
+            query_vector: None,
+        }
     }
 
-    /// Perform hybrid search combining FTS5 and vector similarity
-    pub async fn search(
+    /// Only return root documents (chunks are excluded)
+    pub fn without_chunks(mut self) -> Self {
+        self.filter = self.filter.roots_only();
+        self
+    }
+
+    /// Collapse chunk hits under their parent document
+    ///
+    /// Parents are scored from their chunks using `aggregation`, and up to
+    /// `max_chunks` best-matching chunks are returned as nested evidence.
+    pub fn with_grouping(mut self, aggregation: ChunkAggregation, max_chunks: usize) -> Self {
+        self.grouping = Some(ChunkGrouping {
+            aggregation,
+            max_chunks,
+        });
+        self
+    }
+
+    /// Restrict all searches to documents matching `filter`
+    pub fn with_filter(mut self, filter: SearchFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Use `vector` as the query embedding instead of embedding the query text
+    pub fn with_query_vector(mut self, vector: Vec<f32>) -> Self {
+        self.query_vector = Some(vector);
+        self
+    }
+
+    /// Search for documents similar to `doc` ("more like this")
+    ///
+    /// Uses the document's vector (see [`DocumentStore::document_vector`]) as the
+    /// query vector and excludes the document itself, plus its own chunks when
+    /// `exclude_chunks` is set.
+    pub async fn like_document(
+        self,
+        store: &DocumentStore,
+        doc: &Document,
+        exclude_chunks: bool,
+    ) -> Result<Self> {
+        let vector = store
+            .document_vector(&doc.db, &doc.table, doc)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Document {} has no embedding yet", doc.id))?;
+
+        let mut searcher = self.with_query_vector(vector);
+        searcher.filter = searcher.filter.excluding_id(doc.id.clone());
+        if exclude_chunks {
+            searcher.filter = searcher.filter.excluding_chunks_of(doc.id.clone());
+        }
+
+        Ok(searcher)
+    }
+
+    /// Dispatch to the search mode selected by `search_type`
+    ///
+    /// With a query vector and no query text, hybrid search falls back to vector search.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_with_type(
         &self,
-        store: &mut DocumentStore,
+        store: &DocumentStore,
         embedder: Option<&dyn Embedder>,
         db_id: &str,
         table_name: &str,
         query: &str,
         limit: usize,
+        search_type: SearchType,
+    ) -> Result<Vec<SearchResult>> {
+        let fetch_limit = match self.grouping {
+            Some(_) => limit * GROUP_OVERFETCH_FACTOR,
+            None => limit,
+        };
+
+        let search_type = match search_type {
+            SearchType::Hybrid if query.trim().is_empty() && self.query_vector.is_some() => {
+                SearchType::Vector
+            }
+            other => other,
+        };
+
+        let results = match search_type {
+            SearchType::Hybrid => {
+                self.search(store, embedder, db_id, table_name, query, fetch_limit)
+                    .await?
+            }
+            SearchType::FullText => {
+                self.search_fulltext(store, db_id, table_name, query, fetch_limit)
+                    .await?
+            }
+            SearchType::Vector => match self.query_vector {
+                Some(ref vector) => {
+                    self.search_by_vector(store, db_id, table_name, vector, fetch_limit)
+                        .await?
+                }
+                None => {
+                    let emb = embedder
+                        .ok_or_else(|| anyhow::anyhow!("vector search requires an embedder"))?;
+                    self.search_vector(store, emb, db_id, table_name, query, fetch_limit)
+                        .await?
+                }
+            },
+        };
+
+        match self.grouping {
+            Some(grouping) => {
+                self.group_by_parent(store, db_id, table_name, results, grouping, limit)
+                    .await
+            }
+            None => Ok(results),
+        }
+    }
+
+    /// Collapse chunk hits under their parents, loading parents that were not hits themselves
+    async fn group_by_parent(
+        &self,
+        store: &DocumentStore,
+        db_id: &str,
+        table_name: &str,
+        results: Vec<SearchResult>,
+        grouping: ChunkGrouping,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        let mut grouped = Vec::new();
+
+        for group in self.aggregate_groups(results, grouping) {
+            let ParentGroup {
+                parent_id,
+                score,
+                parent_hit,
+                chunks,
+            } = group;
+
+            let mut parent = match parent_hit {
+                Some(hit) => hit,
+                None => match store.get_document(db_id, table_name, &parent_id).await {
+                    Ok(doc) => SearchResult {
+                        id: doc.id,
+                        content: doc.content,
+                        metadata: doc.metadata,
+                        score,
+                        fts_rank: None,
+                        vector_similarity: None,
+                        is_chunk: false,
+                        parent_id: None,
+                        chunk_index: None,
+                        chunks: Vec::new(),
+                    },
+                    Err(e) => {
+                        // Orphaned chunks are kept as standalone hits
+                        tracing::warn!("Parent {} not found for grouped chunks: {}", parent_id, e);
+                        grouped.extend(chunks);
+                        continue;
+                    }
+                },
+            };
+
+            if parent.fts_rank.is_none() {
+                parent.fts_rank = chunks.iter().find_map(|c| c.fts_rank);
+            }
+            if parent.vector_similarity.is_none() {
+                parent.vector_similarity = chunks.iter().find_map(|c| c.vector_similarity);
+            }
+            parent.score = score;
+            parent.chunks = chunks;
+            grouped.push(parent);
+        }
+
+        grouped.sort_by(|a, b| b.score.total_cmp(&a.score));
+        grouped.truncate(limit);
+
+        Ok(grouped)
+    }
+
+    /// Group ranked results by parent and compute each parent's aggregated score
+    fn aggregate_groups(
+        &self,
+        results: Vec<SearchResult>,
+        grouping: ChunkGrouping,
+    ) -> Vec<ParentGroup> {
+        let mut groups: Vec<ParentGroup> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
+        // Results arrive best-first, so rank is the position in the list
+        for (rank, result) in results.into_iter().enumerate() {
+            let key = match (&result.parent_id, result.is_chunk) {
+                (Some(parent_id), true) => parent_id.clone(),
+                _ => result.id.clone(),
+            };
+
+            let contribution = match grouping.aggregation {
+                ChunkAggregation::Rrf => 1.0 / (self.k as f64 + rank as f64 + 1.0),
+                ChunkAggregation::Max | ChunkAggregation::Sum => result.score,
+            };
+
+            let idx = *positions.entry(key.clone()).or_insert_with(|| {
+                groups.push(ParentGroup {
+                    parent_id: key,
+                    score: contribution,
+                    parent_hit: None,
+                    chunks: Vec::new(),
+                });
+                groups.len() - 1
+            });
+            let group = &mut groups[idx];
+
+            if group.parent_hit.is_some() || !group.chunks.is_empty() {
+                group.score = match grouping.aggregation {
+                    ChunkAggregation::Max => group.score.max(contribution),
+                    ChunkAggregation::Sum | ChunkAggregation::Rrf => group.score + contribution,
+                };
+            }
+
+            if result.is_chunk {
+                if group.chunks.len() < grouping.max_chunks {
+                    group.chunks.push(result);
+                }
+            } else {
+                group.parent_hit = Some(result);
+            }
+        }
+
+        groups
+    }
+
+    /// Perform pure FTS5 keyword search
+    pub async fn search_fulltext(
+        &self,
+        store: &DocumentStore,
+        db_id: &str,
+        table_name: &str,
+        query: &str,
+        limit: usize,
     ) -> Result<Vec<SearchResult>> {
-        // Get FTS5 results
         let fts_results = store
-            .search_fts(db_id, table_name, query, limit * 2)
+            .search_fts_filtered(db_id, table_name, query, limit, &self.filter)
             .await?;
 
-        // Get vector results if embedder available
-        let vector_results = if let Some(emb) = embedder {
-            let query_vector = emb.embed(query).await?;
+        // FTS5 rank is negated BM25 (lower is better), flip it so higher scores rank first
+        Ok(fts_results
+            .into_iter()
+            .map(
+                |(id, content, metadata, rank, is_chunk, parent_id, chunk_index)| SearchResult {
+                    id,
+                    content,
+                    metadata,
+                    score: -rank,
+                    fts_rank: Some(rank),
+                    vector_similarity: None,
+                    is_chunk,
+                    parent_id,
+                    chunk_index,
+                    chunks: Vec::new(),
+                },
+            )
+            .collect())
+    }
+
+    /// Perform pure vector similarity search
+    pub async fn search_vector(
+        &self,
+        store: &DocumentStore,
+        embedder: &dyn Embedder,
+        db_id: &str,
+        table_name: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        let query_vector = embedder.embed(query).await?;
+        self.search_by_vector(store, db_id, table_name, &query_vector, limit)
+            .await
+    }
+
+    /// Perform vector similarity search with a precomputed query vector
+    pub async fn search_by_vector(
+        &self,
+        store: &DocumentStore,
+        db_id: &str,
+        table_name: &str,
+        query_vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        let vector_results = store
+            .search_vector_filtered(db_id, table_name, query_vector, limit, &self.filter)
+            .await?;
+
+        Ok(vector_results
+            .into_iter()
+            .map(
+                |(id, content, metadata, similarity, is_chunk, parent_id, chunk_index)| {
+                    SearchResult {
+                        id,
+                        content,
+                        metadata,
+                        score: similarity,
+                        fts_rank: None,
+                        vector_similarity: Some(similarity),
+                        is_chunk,
+                        parent_id,
+                        chunk_index,
+                        chunks: Vec::new(),
+                    }
+                },
+            )
+            .collect())
+    }
+
+    /// Perform hybrid search combining FTS5 and vector similarity
+    pub async fn search(
+        &self,
+        store: &DocumentStore,
+        embedder: Option<&dyn Embedder>,
+        db_id: &str,
+        table_name: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        // Get FTS5 results (there is nothing to match without query text)
+        let fts_results = if query.trim().is_empty() {
+            Vec::new()
+        } else {
             store
-                .search_vector(db_id, table_name, &query_vector, limit * 2)
+                .search_fts_filtered(db_id, table_name, query, limit * 2, &self.filter)
                 .await?
-        } else {
-            Vec::new()
+        };
+
+        // Get vector results from the query vector, or by embedding the query if possible
+        let query_vector = match (&self.query_vector, embedder) {
+            (Some(vector), _) => Some(vector.clone()),
+            (None, Some(emb)) => Some(emb.embed(query).await?),
+            (None, None) => None,
+        };
+        let vector_results = match query_vector {
+            Some(ref vector) => {
+                store
+                    .search_vector_filtered(db_id, table_name, vector, limit * 2, &self.filter)
+                    .await?
+            }
+            None => Vec::new(),
         };
 
         // Merge with RRF
@@ -167,6 +506,7 @@ impl HybridSearcher {
                         is_chunk,
                         parent_id,
                         chunk_index,
+                        chunks: Vec::new(),
                     }
                 },
             )
@@ -178,8 +518,276 @@ impl HybridSearcher {
     }
 }
 
+/// Parent document with the hits that matched it directly or through its chunks
+struct ParentGroup {
+    parent_id: String,
+    score: f64,
+    parent_hit: Option<SearchResult>,
+    chunks: Vec<SearchResult>,
+}
+
 impl Default for HybridSearcher {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(id: &str, score: f64, parent_id: Option<&str>) -> SearchResult {
+        SearchResult {
+            id: id.to_string(),
+            content: String::new(),
+            metadata: HashMap::new(),
+            score,
+            fts_rank: None,
+            vector_similarity: Some(score),
+            is_chunk: parent_id.is_some(),
+            parent_id: parent_id.map(String::from),
+            chunk_index: None,
+            chunks: Vec::new(),
+        }
+    }
+
+    fn ranked_hits() -> Vec<SearchResult> {
+        vec![
+            hit("a-1", 0.9, Some("a")),
+            hit("b", 0.8, None),
+            hit("a-2", 0.7, Some("a")),
+            hit("c-1", 0.6, Some("c")),
+            hit("a-3", 0.5, Some("a")),
+        ]
+    }
+
+    /// Embedded document with `content` as its text
+    fn document(id: &str, content: &str, vector: Vec<f32>, parent_id: Option<&str>) -> Document {
+        Document {
+            id: id.to_string(),
+            db: "db".to_string(),
+            table: "docs".to_string(),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            tags: Vec::new(),
+            vector: Some(vector),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            is_embedded: true,
+            vectorize: true,
+            is_chunk: parent_id.is_some(),
+            parent_id: parent_id.map(String::from),
+            chunk_index: parent_id.map(|_| 0),
+            token_count: None,
+            is_vectorized: true,
+            embedding_model: None,
+            expires_at: None,
+        }
+    }
+
+    /// Store in a fresh directory, removed by the caller
+    async fn test_store() -> (DocumentStore, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("kuiperdb-search-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = DocumentStore::new(dir.to_string_lossy().to_string())
+            .await
+            .unwrap();
+        (store, dir)
+    }
+
+    /// Embedder returning the same vector for every text, counting its calls
+    struct FixedEmbedder {
+        vector: Vec<f32>,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl FixedEmbedder {
+        fn new(vector: Vec<f32>) -> Self {
+            Self {
+                vector,
+                calls: Default::default(),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Embedder for FixedEmbedder {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(self.vector.clone())
+        }
+
+        async fn embed_with(
+            &self,
+            text: &str,
+            _model: Option<&str>,
+            _dimensions: Option<usize>,
+        ) -> Result<Vec<f32>> {
+            self.embed(text).await
+        }
+
+        async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+            let mut vectors = Vec::new();
+            for text in texts {
+                vectors.push(self.embed(text).await?);
+            }
+            Ok(vectors)
+        }
+
+        fn dimensions(&self) -> usize {
+            self.vector.len()
+        }
+    }
+
+    /// Search the `docs` table with a default searcher
+    async fn search(
+        store: &DocumentStore,
+        embedder: Option<&FixedEmbedder>,
+        query: &str,
+        search_type: SearchType,
+    ) -> Result<Vec<SearchResult>> {
+        let embedder = embedder.map(|e| e as &dyn Embedder);
+        HybridSearcher::new()
+            .search_with_type(store, embedder, "db", "docs", query, 10, search_type)
+            .await
+    }
+
+    fn grouping(aggregation: ChunkAggregation) -> ChunkGrouping {
+        ChunkGrouping {
+            aggregation,
+            max_chunks: 2,
+        }
+    }
+
+    #[test]
+    fn test_group_max_keeps_best_chunks() {
+        let searcher = HybridSearcher::new();
+        let groups = searcher.aggregate_groups(ranked_hits(), grouping(ChunkAggregation::Max));
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].parent_id, "a");
+        assert!((groups[0].score - 0.9).abs() < 1e-9);
+        let evidence: Vec<&str> = groups[0].chunks.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(evidence, vec!["a-1", "a-2"]);
+
+        assert_eq!(groups[1].parent_id, "b");
+        assert!(groups[1].parent_hit.is_some());
+        assert!(groups[1].chunks.is_empty());
+    }
+
+    #[test]
+    fn test_group_sum_and_rrf() {
+        let searcher = HybridSearcher::new();
+
+        let groups = searcher.aggregate_groups(ranked_hits(), grouping(ChunkAggregation::Sum));
+        assert!((groups[0].score - 2.1).abs() < 1e-9);
+
+        let groups = searcher.aggregate_groups(ranked_hits(), grouping(ChunkAggregation::Rrf));
+        let expected = 1.0 / 61.0 + 1.0 / 63.0 + 1.0 / 65.0;
+        assert!((groups[0].score - expected).abs() < 1e-9);
+        assert!((groups[2].score - 1.0 / 64.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_search_type_selects_retrieval() {
+        let (store, dir) = test_store().await;
+        let docs = [
+            ("apple", "red apple", vec![1.0, 0.0, 0.0]),
+            ("car", "red car", vec![0.0, 1.0, 0.0]),
+            ("sky", "blue sky", vec![0.0, 0.0, 1.0]),
+        ];
+        for (id, content, vector) in docs {
+            let doc = document(id, content, vector, None);
+            store.store_document("db", "docs", doc).await.unwrap();
+        }
+        let embedder = FixedEmbedder::new(vec![0.0, 0.0, 1.0]);
+
+        // Full text matches keywords only, without embedding the query
+        let results = search(&store, Some(&embedder), "apple", SearchType::FullText)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "apple");
+        assert!(results[0].fts_rank.is_some() && results[0].vector_similarity.is_none());
+        assert_eq!(embedder.calls(), 0);
+
+        // Vector search ranks by the embedded query, whatever its keywords
+        let results = search(&store, Some(&embedder), "apple", SearchType::Vector)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].id, "sky");
+        assert!(results.iter().all(|r| r.fts_rank.is_none()));
+        assert!((results[0].score - 1.0).abs() < 1e-6);
+        assert_eq!(embedder.calls(), 1);
+        let e = search(&store, None, "apple", SearchType::Vector).await.unwrap_err();
+        assert!(e.to_string().contains("requires an embedder"));
+
+        // Hybrid search fuses both rankings
+        let results = search(&store, Some(&embedder), "red", SearchType::Hybrid)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        let car = results.iter().find(|r| r.id == "car").unwrap();
+        assert!(car.fts_rank.is_some() && car.vector_similarity.is_some());
+        let sky = results.iter().find(|r| r.id == "sky").unwrap();
+        assert!(sky.fts_rank.is_none() && sky.vector_similarity.is_some());
+        assert!(results.iter().all(|r| r.score < 1.0));
+        assert_eq!(embedder.calls(), 2);
+
+        // Without an embedder it falls back to full text
+        let results = search(&store, None, "red", SearchType::Hybrid).await.unwrap();
+        let mut ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["apple", "car"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_like_document_excludes_source_and_chunks() {
+        let (store, dir) = test_store().await;
+
+        let docs = [
+            ("src", vec![1.0, 0.0, 0.0], None),
+            ("src-1", vec![1.0, 0.1, 0.0], Some("src")),
+            ("near", vec![0.9, 0.2, 0.0], None),
+            ("far", vec![0.0, 0.0, 1.0], None),
+        ];
+        for (id, vector, parent_id) in docs {
+            let doc = document(id, id, vector, parent_id);
+            store.store_document("db", "docs", doc).await.unwrap();
+        }
+
+        let source = store.get_document("db", "docs", "src").await.unwrap();
+        let ids = |results: Vec<SearchResult>| -> Vec<String> {
+            results.into_iter().map(|r| r.id).collect()
+        };
+
+        let searcher = HybridSearcher::new()
+            .like_document(&store, &source, true)
+            .await
+            .unwrap();
+        let results = searcher
+            .search_with_type(&store, None, "db", "docs", "", 10, SearchType::Hybrid)
+            .await
+            .unwrap();
+        assert_eq!(ids(results), vec!["near", "far"]);
+
+        let searcher = HybridSearcher::new()
+            .like_document(&store, &source, false)
+            .await
+            .unwrap();
+        let results = searcher
+            .search_with_type(&store, None, "db", "docs", "", 10, SearchType::Vector)
+            .await
+            .unwrap();
+        assert_eq!(ids(results), vec!["src-1", "near", "far"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
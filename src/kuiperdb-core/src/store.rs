@@ -1,25 +1,255 @@
 use anyhow::{Context, Result};
-use chrono::Utc;
-use sqlx::{sqlite::SqlitePool, Row};
-use std::collections::HashMap;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    sqlite::{
+        SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePool, SqliteRow,
+        SqliteSynchronous,
+    },
+    Acquire, Row,
+};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::Arc;
-
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use crate::cache::hash_content;
+use crate::dedup::{
+    deserialize_signature, minhash_signature, minhash_similarity, serialize_signature,
+    similar_pairs, similar_vectors, Clusters,
+};
+use crate::filter::{bind_params, json_path, SearchFilter};
 use crate::index::{IndexConfig, VectorIndex};
-use crate::models::Document;
+use crate::models::{
+    BackupInfo, BackupOptions, BulkMode, Change, ChangeKind, ChangeOp, DBInfo, DatabaseSettings,
+    DatabaseUsage, DedupSettings, Document, DocumentKind, DocumentPage, DocumentRelation,
+    DocumentRevision, Duplicate, DuplicateCluster, DuplicateKind, DuplicateReport,
+    DuplicatesQuery, ExportRecord, ExportedDocument, IndexStatus, ListDocumentsQuery,
+    MetadataIndex, NearDuplicateMethod, PatchDocumentRequest, QuotaKind, QuotaUsage, SortField,
+    SortOrder, TableSettings, TableStats, TagCount, TrashedDocument, TrashedTable,
+};
+use crate::quota::{EmbeddingRateLimiter, QuotaExceeded};
+
+/// HNSW over-fetch multiplier used when post-filtering index results
+const FILTER_OVERFETCH_FACTOR: usize = 4;
+
+/// Columns selected when loading full documents (see [`row_to_document`])
+const DOCUMENT_COLUMNS: &str =
+    "id, content, metadata, tags, vector, created_at, updated_at, is_embedded, \
+     vectorize, is_chunk, parent_id, chunk_index, token_count, is_vectorized, embedding_model, \
+     expires_at";
+
+/// Condition on rows that are neither in the trash nor expired
+///
+/// Timestamps are stored as RFC 3339 text, so the current time is compared in the
+/// same layout. Expired rows stay hidden until the reaper deletes them.
+const LIVE_ROWS: &str = "deleted_at IS NULL \
+     AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%dT%H:%M:%f', 'now'))";
+
+/// Nearest documents compared with each embedded document when reporting
+/// near-duplicates by vector through the HNSW index
+const DUPLICATE_NEIGHBOURS: usize = 10;
+
+/// Most embedded documents compared pairwise when reporting near-duplicates by
+/// vector without the HNSW index
+const MAX_PAIRWISE_VECTORS: usize = 2000;
+
+/// Page size of [`DocumentStore::list_documents`] when none is given
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page [`DocumentStore::list_documents`] returns
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// [`DOCUMENT_COLUMNS`] with content and/or vector left out (selected as empty)
+fn document_columns(include_content: bool, include_vector: bool) -> String {
+    DOCUMENT_COLUMNS
+        .split(", ")
+        .map(|column| match column {
+            "content" if !include_content => "'' AS content",
+            "vector" if !include_vector => "NULL AS vector",
+            column => column,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Position after the last document of a page, handed out as an opaque token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageCursor {
+    sort: SortField,
+    key: String,
+    id: String,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    /// Decode a cursor, checking it was issued for the same sort field
+    pub fn decode(cursor: &str, sort: SortField) -> Result<Self> {
+        let decoded: Self = hex::decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid cursor"))?;
+
+        if decoded.sort != sort {
+            anyhow::bail!("Cursor was issued for a different sort field");
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Columns added after the initial schema, as `(name, definition)`
+///
+/// Missing ones are added to tables created by older versions when their database
+/// is opened.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("embedding_model", "TEXT DEFAULT NULL"),
+    ("deleted_at", "DATETIME DEFAULT NULL"),
+    ("expires_at", "DATETIME DEFAULT NULL"),
+];
+
+/// Directory under the base directory that deleted database files are moved to
+const TRASH_DIR: &str = "trash";
+
+/// Prefix of the name a deleted table is renamed to, followed by its deletion
+/// time in milliseconds, `$` and its name; `$` is not allowed in table names
+const TABLE_TRASH_PREFIX: &str = "trash$";
+
+/// Default directory under the base directory that backups are written to
+const BACKUP_DIR: &str = "backups";
+
+/// Manifest file in each backup directory (see [`BackupInfo`])
+const BACKUP_MANIFEST: &str = "backup.json";
+
+/// File name of the global database, which holds the embedding cache
+const GLOBAL_DB_FILE: &str = "global.db";
+
+/// How long a connection waits for another connection's write lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A document's new vector (`Some`) or its removal (`None`) from an index
+type IndexChange = (String, Option<Vec<f32>>);
+
+/// Statement starting write transactions
+///
+/// Takes the write lock up front: a deferred transaction that reads first can
+/// fail with `SQLITE_BUSY` when it upgrades while another connection writes.
+pub(crate) const BEGIN_WRITE: &str = "BEGIN IMMEDIATE";
+
+/// A document and its chunks, written together by [`DocumentStore::store_documents`]
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    pub document: Document,
+    pub chunks: Vec<Document>,
+}
+
+impl From<Document> for BatchItem {
+    fn from(document: Document) -> Self {
+        Self {
+            document,
+            chunks: Vec::new(),
+        }
+    }
+}
+
+/// A document or relation read from an export, written by
+/// [`DocumentStore::import_records`]
+#[derive(Debug, Clone)]
+pub enum ImportItem {
+    Document(Document),
+    Relation(DocumentRelation),
+}
+
+impl ImportItem {
+    /// Decode one export line, moving its document into `db_id`/`table_name`
+    pub fn from_record(record: ExportRecord, db_id: &str, table_name: &str) -> Result<Self> {
+        match record {
+            ExportRecord::Document(exported) => {
+                let mut document = exported.document;
+                if document.id.is_empty() {
+                    anyhow::bail!("Document id is required");
+                }
+                document.db = db_id.to_string();
+                document.table = table_name.to_string();
+                document.vector = exported.vector.as_deref().map(decode_vector).transpose()?;
+                Ok(Self::Document(document))
+            }
+            ExportRecord::Relation(relation) => Ok(Self::Relation(relation)),
+        }
+    }
+
+    /// ID of the document or relation
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Document(document) => &document.id,
+            Self::Relation(relation) => &relation.id,
+        }
+    }
+}
+
+/// Outcome of [`DocumentStore::patch_document`]
+#[derive(Debug, Clone)]
+pub struct PatchOutcome {
+    pub document: Document,
+    /// Whether the content changed, so the document needs chunking and embedding again
+    pub content_changed: bool,
+}
 
+/// Outcome of [`DocumentStore::store_documents`]
+#[derive(Debug, Clone)]
+pub struct BatchWriteResult {
+    /// Whether the transaction committed; if not, nothing was written
+    pub committed: bool,
+    /// Error per item, in input order (`None` for items without one)
+    pub errors: Vec<Option<String>>,
+}
+
+/// Documents, relations and indexes of every database under a base directory
+///
+/// Methods take `&self`, so one store can be shared between tasks. Each database
+/// has a pool of connections in WAL mode: reads run in parallel, and writes wait
+/// for each other in SQLite. The caches below are behind their own locks, which
+/// are never held across an `.await`.
 pub struct DocumentStore {
     base_dir: String,
-    pools: HashMap<String, SqlitePool>,
+    /// Directory backups are written to, one subdirectory per database
+    backup_dir: String,
+    pools: RwLock<HashMap<String, SqlitePool>>,
+    /// Held while a database is opened and migrated, so that happens once
+    opening: tokio::sync::Mutex<()>,
     global_pool: Option<SqlitePool>,
     /// Per-database vector indexes
-    indexes: HashMap<String, Arc<VectorIndex>>,
+    indexes: RwLock<HashMap<String, Arc<VectorIndex>>>,
+    /// Per-index locks held while an index is loaded or built, so concurrent
+    /// searches wait for one build instead of each starting their own
+    building: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Vectors written (`Some`) or removed (`None`) per document while an index
+    /// is loaded or built, replayed on it before it is published
+    pending_index_changes: Mutex<HashMap<String, Vec<IndexChange>>>,
     /// Index configuration
     index_config: IndexConfig,
     /// Whether to use vector indexing
     use_indexing: bool,
     /// Auto-enable threshold (document count)
     index_threshold: usize,
+    /// Whether to save indexes next to the database files
+    persist_indexes: bool,
+    /// Indexes modified since they were last saved
+    dirty_indexes: Mutex<HashSet<String>>,
+    /// Tables whose schema has been created or migrated by this store
+    ensured_tables: Mutex<HashSet<String>>,
+    /// Per-database settings, loaded on first use
+    database_settings: RwLock<HashMap<String, DatabaseSettings>>,
+    /// Per-table settings, loaded on first use
+    table_settings: RwLock<HashMap<String, TableSettings>>,
+    /// Per-table metadata index definitions, loaded on first use
+    metadata_indexes: RwLock<HashMap<String, Vec<MetadataIndex>>>,
+    /// Recent embedding requests per database, for their rate limits
+    embedding_requests: EmbeddingRateLimiter,
 }
 
 impl DocumentStore {
@@ -29,19 +259,31 @@ impl DocumentStore {
             .context("Failed to create base directory")?;
 
         // Create global pool for cache
-        let global_db_path = format!("{}/global.db", base_dir);
-        let global_pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", global_db_path))
+        let global_db_path = format!("{}/{}", base_dir, GLOBAL_DB_FILE);
+        let global_pool = SqlitePool::connect_with(connect_options(&global_db_path))
             .await
             .context("Failed to connect to global database")?;
+        create_catalog(&global_pool).await?;
 
         Ok(Self {
+            backup_dir: format!("{}/{}", base_dir, BACKUP_DIR),
             base_dir,
-            pools: HashMap::new(),
+            pools: RwLock::new(HashMap::new()),
+            opening: tokio::sync::Mutex::new(()),
             global_pool: Some(global_pool),
-            indexes: HashMap::new(),
+            indexes: RwLock::new(HashMap::new()),
+            building: Mutex::new(HashMap::new()),
+            pending_index_changes: Mutex::new(HashMap::new()),
             index_config: IndexConfig::default(),
             use_indexing: false,
             index_threshold: 1000,
+            persist_indexes: false,
+            dirty_indexes: Mutex::new(HashSet::new()),
+            ensured_tables: Mutex::new(HashSet::new()),
+            database_settings: RwLock::new(HashMap::new()),
+            table_settings: RwLock::new(HashMap::new()),
+            metadata_indexes: RwLock::new(HashMap::new()),
+            embedding_requests: EmbeddingRateLimiter::new(),
         })
     }
 
@@ -56,6 +298,18 @@ impl DocumentStore {
         );
     }
 
+    /// Configure the directory backups are written to and restored from
+    pub fn configure_backup_dir(&mut self, dir: String) {
+        tracing::info!("Backup directory: {}", dir);
+        self.backup_dir = dir;
+    }
+
+    /// Configure saving HNSW indexes to disk (loaded lazily on first search)
+    pub fn configure_index_persistence(&mut self, enabled: bool) {
+        self.persist_indexes = enabled;
+        tracing::info!("Vector index persistence: enabled={}", enabled);
+    }
+
     /// Get global pool for cache
     pub async fn get_global_pool(&self) -> Result<SqlitePool> {
         self.global_pool
@@ -64,31 +318,58 @@ impl DocumentStore {
     }
 
     /// Get or create database pool for a specific database
-    pub async fn get_pool(&mut self, db_id: &str) -> Result<&SqlitePool> {
-        if !self.pools.contains_key(db_id) {
-            let db_path = format!("{}/{}.db", self.base_dir, db_id);
-            let connection_string = format!("sqlite://{}?mode=rwc", db_path);
-            let pool = SqlitePool::connect(&connection_string)
-                .await
-                .context("Failed to connect to database")?;
+    pub async fn get_pool(&self, db_id: &str) -> Result<SqlitePool> {
+        if let Some(pool) = self.open_pool(db_id) {
+            return Ok(pool);
+        }
 
-            // Enable foreign keys
-            sqlx::query("PRAGMA foreign_keys = ON")
-                .execute(&pool)
-                .await?;
+        let _opening = self.opening.lock().await;
+        if let Some(pool) = self.open_pool(db_id) {
+            return Ok(pool);
+        }
 
-            self.pools.insert(db_id.to_string(), pool);
+        let db_path = format!("{}/{}.db", self.base_dir, db_id);
+        let pool = SqlitePool::connect_with(connect_options(&db_path))
+            .await
+            .context("Failed to connect to database")?;
+
+        // Bring tables created by older versions up to date before they are read
+        create_change_log(&pool).await?;
+        create_embedding_failures(&pool).await?;
+        create_table_trash(&pool).await?;
+        let tables = user_tables(&mut *pool.acquire().await?).await?;
+        for table_name in tables {
+            add_missing_columns(&pool, db_id, &table_name).await?;
+            create_tag_table(&pool, db_id, &table_name).await?;
+            create_dedup_table(&pool, db_id, &table_name).await?;
+            create_change_triggers(&pool, &table_name).await?;
         }
+        self.register_database(db_id, &db_path).await?;
+
+        self.pools
+            .write()
+            .unwrap()
+            .insert(db_id.to_string(), pool.clone());
+        Ok(pool)
+    }
 
-        Ok(self.pools.get(db_id).unwrap())
+    /// Pool of a database that is already open
+    fn open_pool(&self, db_id: &str) -> Option<SqlitePool> {
+        self.pools.read().unwrap().get(db_id).cloned()
     }
 
     /// Ensure a table exists
-    pub async fn ensure_table(&mut self, db_id: &str, table_name: &str) -> Result<()> {
+    pub async fn ensure_table(&self, db_id: &str, table_name: &str) -> Result<()> {
         if !is_valid_table_name(table_name) {
             anyhow::bail!("Invalid table name: {}", table_name);
         }
 
+        // The DDL below only needs to run once per table
+        let table_key = format!("{}:{}", db_id, table_name);
+        if self.ensured_tables.lock().unwrap().contains(&table_key) {
+            return Ok(());
+        }
+
         let pool = self.get_pool(db_id).await?;
 
         // Create main documents table
@@ -109,13 +390,16 @@ impl DocumentStore {
                 chunk_index INTEGER DEFAULT NULL,
                 token_count INTEGER DEFAULT NULL,
                 is_vectorized INTEGER DEFAULT 0,
+                embedding_model TEXT DEFAULT NULL,
+                deleted_at DATETIME DEFAULT NULL,
+                expires_at DATETIME DEFAULT NULL,
                 FOREIGN KEY (parent_id) REFERENCES "{}"(id) ON DELETE CASCADE
             )
         "#,
             table_name, table_name
         );
 
-        sqlx::query(&create_table).execute(pool).await?;
+        sqlx::query(&create_table).execute(&pool).await?;
 
         // Create FTS5 virtual table
         let create_fts = format!(
@@ -130,14 +414,16 @@ impl DocumentStore {
             table_name, table_name
         );
 
-        sqlx::query(&create_fts).execute(pool).await?;
+        sqlx::query(&create_fts).execute(&pool).await?;
 
-        // Create triggers - re-get pool to work around borrow checker
+        // Create triggers
         self.create_fts_triggers_for_table(db_id, table_name)
             .await?;
 
-        // Create indexes - re-get pool again
-        let pool = self.get_pool(db_id).await?;
+        // Create indexes
+        create_tag_table(&pool, db_id, table_name).await?;
+        create_dedup_table(&pool, db_id, table_name).await?;
+        create_change_triggers(&pool, table_name).await?;
         let indexes = vec![
             format!(
                 r#"CREATE INDEX IF NOT EXISTS idx_{}_created_at ON "{}"(created_at)"#,
@@ -159,19 +445,35 @@ impl DocumentStore {
                 r#"CREATE INDEX IF NOT EXISTS idx_{}_chunks ON "{}"(is_chunk, parent_id) WHERE is_chunk = 1"#,
                 table_name, table_name
             ),
+            format!(
+                r#"CREATE INDEX IF NOT EXISTS idx_{}_deleted ON "{}"(deleted_at) WHERE deleted_at IS NOT NULL"#,
+                table_name, table_name
+            ),
+            format!(
+                r#"CREATE INDEX IF NOT EXISTS idx_{}_expires ON "{}"(expires_at) WHERE expires_at IS NOT NULL"#,
+                table_name, table_name
+            ),
         ];
 
         for index_sql in indexes {
-            sqlx::query(&index_sql).execute(pool).await?;
+            sqlx::query(&index_sql).execute(&pool).await?;
         }
 
         // Create document_relations table (shared for all tables in this db)
         self.create_relations_table(db_id).await?;
 
+        // Generated columns and indexes of declared metadata indexes
+        for index in self.metadata_indexes(db_id, table_name).await? {
+            let pool = self.get_pool(db_id).await?;
+            let mut conn = pool.acquire().await?;
+            apply_metadata_index(&mut conn, table_name, &index).await?;
+        }
+
+        self.ensured_tables.lock().unwrap().insert(table_key);
         Ok(())
     }
 
-    async fn create_relations_table(&mut self, db_id: &str) -> Result<()> {
+    async fn create_relations_table(&self, db_id: &str) -> Result<()> {
         let pool = self.get_pool(db_id).await?;
 
         sqlx::query(
@@ -186,7 +488,7 @@ impl DocumentStore {
             )
         "#,
         )
-        .execute(pool)
+        .execute(&pool)
         .await?;
 
         // Create indexes
@@ -195,7 +497,7 @@ impl DocumentStore {
             CREATE INDEX IF NOT EXISTS idx_relations_source ON document_relations(source_id)
         "#,
         )
-        .execute(pool)
+        .execute(&pool)
         .await?;
 
         sqlx::query(
@@ -203,7 +505,7 @@ impl DocumentStore {
             CREATE INDEX IF NOT EXISTS idx_relations_target ON document_relations(target_id)
         "#,
         )
-        .execute(pool)
+        .execute(&pool)
         .await?;
 
         sqlx::query(
@@ -211,7 +513,7 @@ impl DocumentStore {
             CREATE INDEX IF NOT EXISTS idx_relations_type ON document_relations(relation_type)
         "#,
         )
-        .execute(pool)
+        .execute(&pool)
         .await?;
 
         sqlx::query(
@@ -220,1072 +522,4900 @@ impl DocumentStore {
             ON document_relations(source_id, target_id, relation_type)
         "#,
         )
-        .execute(pool)
+        .execute(&pool)
         .await?;
 
+        // Log relation changes
+        create_change_log(&pool).await?;
+
         Ok(())
     }
 
-    async fn create_fts_triggers_for_table(&mut self, db_id: &str, table_name: &str) -> Result<()> {
+    async fn create_settings_table(&self, db_id: &str) -> Result<()> {
         let pool = self.get_pool(db_id).await?;
-        // Insert trigger
-        let insert_trigger = format!(
-            r#"
-            CREATE TRIGGER IF NOT EXISTS {}_ai AFTER INSERT ON "{}" BEGIN
-                INSERT INTO "{}_fts"(rowid, id, content)
-                VALUES (new.rowid, new.id, new.content);
-            END
-        "#,
-            table_name, table_name, table_name
-        );
 
-        // Delete trigger
-        let delete_trigger = format!(
+        sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS {}_ad AFTER DELETE ON "{}" BEGIN
-                DELETE FROM "{}_fts" WHERE rowid = old.rowid;
-            END
+            CREATE TABLE IF NOT EXISTS table_settings (
+                table_name TEXT PRIMARY KEY,
+                settings TEXT NOT NULL
+            )
         "#,
-            table_name, table_name, table_name
-        );
+        )
+        .execute(&pool)
+        .await?;
 
-        // Update trigger
-        let update_trigger = format!(
+        Ok(())
+    }
+
+    async fn create_metadata_indexes_table(&self, db_id: &str) -> Result<()> {
+        let pool = self.get_pool(db_id).await?;
+
+        sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS {}_au AFTER UPDATE ON "{}" BEGIN
-                UPDATE "{}_fts" SET content = new.content WHERE rowid = old.rowid;
-            END
+            CREATE TABLE IF NOT EXISTS metadata_indexes (
+                table_name TEXT NOT NULL,
+                field TEXT NOT NULL,
+                definition TEXT NOT NULL,
+                PRIMARY KEY (table_name, field)
+            )
         "#,
-            table_name, table_name, table_name
-        );
-
-        sqlx::query(&insert_trigger).execute(pool).await?;
-        sqlx::query(&delete_trigger).execute(pool).await?;
-        sqlx::query(&update_trigger).execute(pool).await?;
+        )
+        .execute(&pool)
+        .await?;
 
         Ok(())
     }
 
-    /// Store a document
-    pub async fn store_document(
-        &mut self,
+    /// Metadata indexes declared on a table, by field
+    pub async fn metadata_indexes(
+        &self,
         db_id: &str,
         table_name: &str,
-        doc: Document,
-    ) -> Result<()> {
-        self.ensure_table(db_id, table_name).await?;
-        let pool = self.get_pool(db_id).await?;
-
-        // Serialize metadata
-        let metadata_json = serde_json::to_string(&doc.metadata)?;
-
-        // Serialize tags
-        let tags_str = doc.tags.join(",");
+    ) -> Result<Vec<MetadataIndex>> {
+        let key = format!("{}:{}", db_id, table_name);
+        if let Some(indexes) = self.metadata_indexes.read().unwrap().get(&key) {
+            return Ok(indexes.clone());
+        }
 
-        // Serialize vector
-        let (vector_bytes, is_embedded, is_vectorized) = if let Some(ref vector) = doc.vector {
-            (Some(serialize_vector(vector)), 1, 1)
-        } else {
-            (None, 0, 0)
-        };
+        self.create_metadata_indexes_table(db_id).await?;
+        let pool = self.get_pool(db_id).await?;
 
-        // Calculate token count if not already set (estimate: 1 token per 4 characters)
-        let token_count = doc.token_count.unwrap_or_else(|| {
-            (doc.content.len() as f32 / 4.0).ceil() as i32
-        });
+        let definitions: Vec<String> = sqlx::query_scalar(
+            "SELECT definition FROM metadata_indexes WHERE table_name = ? ORDER BY field",
+        )
+        .bind(table_name)
+        .fetch_all(&pool)
+        .await?;
+        let indexes = definitions
+            .iter()
+            .map(|json| serde_json::from_str(json))
+            .collect::<serde_json::Result<Vec<MetadataIndex>>>()?;
 
-        let query = format!(
-            r#"
-            INSERT INTO "{}" (id, content, metadata, tags, vector, created_at, updated_at, is_embedded, vectorize, is_chunk, parent_id, chunk_index, token_count, is_vectorized)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(id) DO UPDATE SET
-                content = excluded.content,
-                metadata = excluded.metadata,
-                tags = excluded.tags,
-                vector = excluded.vector,
-                updated_at = excluded.updated_at,
-                is_embedded = excluded.is_embedded,
-                vectorize = excluded.vectorize,
-                is_chunk = excluded.is_chunk,
-                parent_id = excluded.parent_id,
-                chunk_index = excluded.chunk_index,
-                token_count = excluded.token_count,
-                is_vectorized = excluded.is_vectorized
-        "#,
-            table_name
-        );
+        self.metadata_indexes.write().unwrap().insert(key, indexes.clone());
+        Ok(indexes)
+    }
 
-        sqlx::query(&query)
-            .bind(&doc.id)
-            .bind(&doc.content)
-            .bind(&metadata_json)
-            .bind(&tags_str)
-            .bind(&vector_bytes)
-            .bind(doc.created_at)
-            .bind(doc.updated_at)
-            .bind(is_embedded)
-            .bind(if doc.vectorize { 1 } else { 0 })
-            .bind(if doc.is_chunk { 1 } else { 0 })
-            .bind(&doc.parent_id)
-            .bind(doc.chunk_index)
-            .bind(token_count)
-            .bind(is_vectorized)
-            .execute(pool)
-            .await?;
+    /// Declare a metadata index, adding its generated column and B-tree index
+    ///
+    /// Returns false if the field is already indexed with the same type. Changing
+    /// the type of an index requires dropping it first.
+    pub async fn create_metadata_index(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        index: MetadataIndex,
+    ) -> Result<bool> {
+        index.validate()?;
+        self.ensure_table(db_id, table_name).await?;
+        let existing = self.metadata_indexes(db_id, table_name).await?;
+        if let Some(current) = existing.iter().find(|i| i.field == index.field) {
+            if *current == index {
+                return Ok(false);
+            }
+            anyhow::bail!(
+                "Field {} is already indexed as {}",
+                index.field,
+                current.index_type.sql_type().to_lowercase()
+            );
+        }
 
-        Ok(())
+        let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+        sqlx::query(
+            "INSERT INTO metadata_indexes (table_name, field, definition) VALUES (?, ?, ?)",
+        )
+        .bind(table_name)
+        .bind(&index.field)
+        .bind(serde_json::to_string(&index)?)
+        .execute(&mut *tx)
+        .await?;
+        apply_metadata_index(&mut tx, table_name, &index).await?;
+        tx.commit().await?;
+
+        self.metadata_indexes
+            .write()
+            .unwrap()
+            .remove(&format!("{}:{}", db_id, table_name));
+        Ok(true)
     }
 
-    /// Get a document by ID
-    pub async fn get_document(
-        &mut self,
+    /// Drop a metadata index and its generated column, returning false if the
+    /// field was not indexed
+    pub async fn drop_metadata_index(
+        &self,
         db_id: &str,
         table_name: &str,
-        id: &str,
-    ) -> Result<Document> {
-        let pool = self.get_pool(db_id).await?;
+        field: &str,
+    ) -> Result<bool> {
+        let existing = self.metadata_indexes(db_id, table_name).await?;
+        let index = match existing.into_iter().find(|i| i.field == field) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
 
-        let query = format!(
-            r#"
-            SELECT id, content, metadata, tags, vector, created_at, updated_at, is_embedded,
-                   vectorize, is_chunk, parent_id, chunk_index, token_count, is_vectorized
-            FROM "{}"
-            WHERE id = ?
-        "#,
-            table_name
-        );
+        let column = index.column_name();
+        let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
 
-        let row = sqlx::query(&query)
-            .bind(id)
-            .fetch_one(pool)
-            .await
-            .context("Document not found")?;
+        let drop_index = format!(r#"DROP INDEX IF EXISTS "idx_{}_{}""#, table_name, column);
+        sqlx::query(&drop_index).execute(&mut *tx).await?;
+        if has_column(&mut tx, table_name, &column).await? {
+            let drop_column = format!(r#"ALTER TABLE "{}" DROP COLUMN "{}""#, table_name, column);
+            sqlx::query(&drop_column).execute(&mut *tx).await?;
+        }
+        sqlx::query("DELETE FROM metadata_indexes WHERE table_name = ? AND field = ?")
+            .bind(table_name)
+            .bind(field)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
 
-        let metadata_json: String = row.get("metadata");
-        let metadata: HashMap<String, serde_json::Value> =
-            serde_json::from_str(&metadata_json).unwrap_or_default();
+        self.metadata_indexes
+            .write()
+            .unwrap()
+            .remove(&format!("{}:{}", db_id, table_name));
+        Ok(true)
+    }
 
-        let tags_str: String = row.get("tags");
-        let tags: Vec<String> = if tags_str.is_empty() {
-            Vec::new()
-        } else {
-            tags_str.split(',').map(String::from).collect()
-        };
+    /// Settings of a table (defaults if none were saved)
+    pub async fn table_settings(&self, db_id: &str, table_name: &str) -> Result<TableSettings> {
+        let key = format!("{}:{}", db_id, table_name);
+        if let Some(settings) = self.table_settings.read().unwrap().get(&key) {
+            return Ok(settings.clone());
+        }
 
-        let vector_bytes: Option<Vec<u8>> = row.get("vector");
-        let vector = vector_bytes.map(|bytes| deserialize_vector(&bytes));
+        self.create_settings_table(db_id).await?;
+        let pool = self.get_pool(db_id).await?;
 
-        let is_embedded: i32 = row.get("is_embedded");
-        let vectorize: i32 = row.get("vectorize");
-        let is_chunk: i32 = row.get("is_chunk");
-        let is_vectorized: i32 = row.get("is_vectorized");
+        let json: Option<String> =
+            sqlx::query_scalar("SELECT settings FROM table_settings WHERE table_name = ?")
+                .bind(table_name)
+                .fetch_optional(&pool)
+                .await?;
+        let settings = match json {
+            Some(json) => serde_json::from_str(&json)?,
+            None => TableSettings::default(),
+        };
 
-        Ok(Document {
-            id: row.get("id"),
-            db: db_id.to_string(),
-            table: table_name.to_string(),
-            content: row.get("content"),
-            metadata,
-            tags,
-            vector,
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-            is_embedded: is_embedded == 1,
-            vectorize: vectorize == 1,
-            is_chunk: is_chunk == 1,
-            parent_id: row.get("parent_id"),
-            chunk_index: row.get("chunk_index"),
-            token_count: row.get("token_count"),
-            is_vectorized: is_vectorized == 1,
-        })
+        self.table_settings.write().unwrap().insert(key, settings.clone());
+        Ok(settings)
     }
 
-    /// Get documents that need embedding
-    pub async fn get_non_embedded_documents(
-        &mut self,
+    /// Save the settings of a table, creating the table if needed
+    ///
+    /// Turning on history records the current state of every root document as
+    /// its next revision, so later reads have a starting point. Changing the
+    /// embedding model or dimensions clears every vector in the table, client-
+    /// supplied ones included, so the worker embeds its documents again. Any change
+    /// to embeddings or index parameters drops the table's index, which is rebuilt
+    /// on the next search.
+    pub async fn set_table_settings(
+        &self,
         db_id: &str,
         table_name: &str,
-        limit: i32,
-    ) -> Result<Vec<Document>> {
+        settings: TableSettings,
+    ) -> Result<()> {
+        self.ensure_table(db_id, table_name).await?;
+        let previous = self.table_settings(db_id, table_name).await?;
         let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
 
-        let query = format!(
+        if previous.changes_embedding(&settings) {
+            let reset = format!(
+                r#"
+                UPDATE "{}"
+                SET vector = NULL, is_embedded = 0, is_vectorized = 0, embedding_model = NULL
+                WHERE vector IS NOT NULL
+            "#,
+                table_name
+            );
+            let reset = sqlx::query(&reset).execute(&mut *tx).await?.rows_affected();
+            if reset > 0 {
+                tracing::info!(
+                    "Embedding settings of {}.{} changed, {} documents will be embedded again",
+                    db_id,
+                    table_name,
+                    reset
+                );
+            }
+        }
+
+        sqlx::query(
             r#"
-            SELECT id, content, metadata, tags, vector, created_at, updated_at, is_embedded, vectorize, is_chunk, parent_id, chunk_index, token_count, is_vectorized
-            FROM "{}"
-            WHERE is_embedded = 0 AND vectorize = 1
-            ORDER BY created_at ASC
-            LIMIT ?
+            INSERT INTO table_settings (table_name, settings) VALUES (?, ?)
+            ON CONFLICT(table_name) DO UPDATE SET settings = excluded.settings
         "#,
-            table_name
-        );
-
-        let rows = sqlx::query(&query).bind(limit).fetch_all(pool).await?;
-
-        let mut documents = Vec::new();
-        for row in rows {
-            let metadata_json: String = row.get("metadata");
-            let metadata: HashMap<String, serde_json::Value> =
-                serde_json::from_str(&metadata_json).unwrap_or_default();
+        )
+        .bind(table_name)
+        .bind(serde_json::to_string(&settings)?)
+        .execute(&mut *tx)
+        .await?;
 
-            let tags_str: String = row.get("tags");
-            let tags: Vec<String> = if tags_str.is_empty() {
-                Vec::new()
-            } else {
-                tags_str.split(',').map(String::from).collect()
-            };
+        if settings.history {
+            create_history_table(&mut tx, table_name).await?;
+        }
 
-            let vectorize: i32 = row.get("vectorize");
-            let is_chunk: i32 = row.get("is_chunk");
-            let is_vectorized: i32 = row.get("is_vectorized");
+        tx.commit().await?;
 
-            documents.push(Document {
-                id: row.get("id"),
-                db: db_id.to_string(),
-                table: table_name.to_string(),
-                content: row.get("content"),
-                metadata,
-                tags,
-                vector: None,
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                is_embedded: false,
-                vectorize: vectorize == 1,
-                is_chunk: is_chunk == 1,
-                parent_id: row.get("parent_id"),
-                chunk_index: row.get("chunk_index"),
-                token_count: row.get("token_count"),
-                is_vectorized: is_vectorized == 1,
-            });
+        if previous.changes_embedding(&settings) || previous.vector_index != settings.vector_index {
+            self.drop_index(db_id, table_name)?;
         }
 
-        Ok(documents)
+        self.table_settings
+            .write()
+            .unwrap()
+            .insert(format!("{}:{}", db_id, table_name), settings);
+        Ok(())
     }
 
-    /// Get all documents (embedded or not) - for listing endpoints
-    pub async fn get_all_documents(
-        &mut self,
+    /// All recorded revisions of a document, oldest first
+    pub async fn list_revisions(
+        &self,
         db_id: &str,
         table_name: &str,
-        limit: i32,
-    ) -> Result<Vec<Document>> {
-        let pool = self.get_pool(db_id).await?;
-
+        doc_id: &str,
+    ) -> Result<Vec<DocumentRevision>> {
         let query = format!(
-            r#"
-            SELECT id, content, metadata, tags, vector, created_at, updated_at, is_embedded, vectorize, is_chunk, parent_id, chunk_index, token_count, is_vectorized
-            FROM "{}"
-            ORDER BY created_at ASC
-            LIMIT ?
-        "#,
-            table_name
+            r#"SELECT {} FROM "{}_history" WHERE doc_id = ? ORDER BY rev"#,
+            REVISION_COLUMNS, table_name
         );
+        let pool = self.history_pool(db_id, table_name).await?;
+        let rows = sqlx::query(&query).bind(doc_id).fetch_all(&pool).await?;
 
-        let rows = sqlx::query(&query).bind(limit).fetch_all(pool).await?;
-
-        let mut documents = Vec::new();
-        for row in rows {
-            let metadata_json: String = row.get("metadata");
-            let metadata: HashMap<String, serde_json::Value> =
-                serde_json::from_str(&metadata_json).unwrap_or_default();
-
-            let tags_str: String = row.get("tags");
-            let tags: Vec<String> = if tags_str.is_empty() {
-                Vec::new()
-            } else {
-                tags_str.split(',').map(String::from).collect()
-            };
-
-            let vector_bytes: Option<Vec<u8>> = row.get("vector");
-            let vector = vector_bytes.map(|bytes| deserialize_vector(&bytes));
-
-            let is_embedded: i32 = row.get("is_embedded");
-            let vectorize: i32 = row.get("vectorize");
-            let is_chunk: i32 = row.get("is_chunk");
-            let is_vectorized: i32 = row.get("is_vectorized");
+        Ok(rows.iter().map(row_to_revision).collect())
+    }
 
-            documents.push(Document {
-                id: row.get("id"),
-                db: db_id.to_string(),
-                table: table_name.to_string(),
-                content: row.get("content"),
-                metadata,
-                tags,
-                vector,
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                is_embedded: is_embedded == 1,
-                vectorize: vectorize == 1,
-                is_chunk: is_chunk == 1,
-                parent_id: row.get("parent_id"),
-                chunk_index: row.get("chunk_index"),
-                token_count: row.get("token_count"),
-                is_vectorized: is_vectorized == 1,
-            });
-        }
+    /// A specific revision of a document
+    pub async fn get_revision(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        doc_id: &str,
+        rev: i64,
+    ) -> Result<Option<DocumentRevision>> {
+        let query = format!(
+            r#"SELECT {} FROM "{}_history" WHERE doc_id = ? AND rev = ?"#,
+            REVISION_COLUMNS, table_name
+        );
+        let pool = self.history_pool(db_id, table_name).await?;
+        let row = sqlx::query(&query)
+            .bind(doc_id)
+            .bind(rev)
+            .fetch_optional(&pool)
+            .await?;
 
-        Ok(documents)
+        Ok(row.as_ref().map(row_to_revision))
     }
 
-    /// Update document vector
-    pub async fn update_document_vector(
-        &mut self,
+    /// The revision of a document that was current at `as_of`
+    ///
+    /// This may be a deletion marker if the document had been deleted by then.
+    pub async fn get_revision_as_of(
+        &self,
         db_id: &str,
         table_name: &str,
         doc_id: &str,
-        vector: &[f32],
-    ) -> Result<()> {
-        let pool = self.get_pool(db_id).await?;
-        let vector_bytes = serialize_vector(vector);
-
+        as_of: DateTime<Utc>,
+    ) -> Result<Option<DocumentRevision>> {
         let query = format!(
             r#"
-            UPDATE "{}"
-            SET vector = ?, is_embedded = 1, is_vectorized = 1, updated_at = ?
-            WHERE id = ?
+            SELECT {} FROM "{}_history"
+            WHERE doc_id = ? AND recorded_at <= ?
+            ORDER BY rev DESC
+            LIMIT 1
         "#,
-            table_name
+            REVISION_COLUMNS, table_name
         );
-
-        sqlx::query(&query)
-            .bind(&vector_bytes)
-            .bind(Utc::now())
+        let pool = self.history_pool(db_id, table_name).await?;
+        let row = sqlx::query(&query)
             .bind(doc_id)
-            .execute(pool)
+            .bind(as_of)
+            .fetch_optional(&pool)
             .await?;
 
-        // Add to vector index if it exists
-        if self.use_indexing {
-            let index_key = format!("{}:{}", db_id, table_name);
-            if let Some(index) = self.indexes.get(&index_key) {
-                index.add(doc_id.to_string(), vector.to_vec())?;
-            }
+        Ok(row.as_ref().map(row_to_revision))
+    }
+
+    /// Make an old revision the current version of a document, recreating the
+    /// document if it was deleted since
+    ///
+    /// Like a patch, restoring different content drops the chunks and embedding.
+    pub async fn restore_revision(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        doc_id: &str,
+        rev: i64,
+    ) -> Result<PatchOutcome> {
+        let revision = self
+            .get_revision(db_id, table_name, doc_id, rev)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Revision {} of {} not found", rev, doc_id))?;
+        if revision.deleted {
+            anyhow::bail!("Revision {} of {} records its deletion", rev, doc_id);
         }
 
-        Ok(())
+        self.revise_document(db_id, table_name, doc_id, true, true, |current| {
+            Ok(match current {
+                Some(mut doc) => {
+                    let content_changed = doc.replace_content(&revision.content);
+                    doc.metadata = revision.metadata;
+                    doc.tags = revision.tags;
+                    doc.updated_at = Utc::now();
+                    (doc, content_changed)
+                }
+                None => {
+                    let request = crate::models::StoreDocumentRequest {
+                        id: Some(revision.doc_id),
+                        content: revision.content,
+                        metadata: revision.metadata,
+                        tags: revision.tags,
+                        vectorize: true,
+                        vector: None,
+                        model: None,
+                        expires_at: None,
+                        ttl_seconds: None,
+                    };
+                    (Document::from_request(db_id, table_name, request), true)
+                }
+            })
+        })
+        .await
+    }
+
+    /// Pool of a database whose table keeps history
+    async fn history_pool(&self, db_id: &str, table_name: &str) -> Result<SqlitePool> {
+        if !self
+            .table_exists(db_id, &format!("{}_history", table_name))
+            .await?
+        {
+            anyhow::bail!("History is not enabled for table {}", table_name);
+        }
+        self.get_pool(db_id).await
     }
 
-    /// Build HNSW index for a table
-    async fn build_index(&mut self, db_id: &str, table_name: &str) -> Result<()> {
+    async fn create_fts_triggers_for_table(&self, db_id: &str, table_name: &str) -> Result<()> {
         let pool = self.get_pool(db_id).await?;
+        // Insert trigger
+        let insert_trigger = format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS {}_ai AFTER INSERT ON "{}" BEGIN
+                INSERT INTO "{}_fts"(rowid, id, content)
+                VALUES (new.rowid, new.id, new.content);
+            END
+        "#,
+            table_name, table_name, table_name
+        );
 
-        tracing::info!("Building HNSW index for {}.{}", db_id, table_name);
+        // Delete trigger
+        let delete_trigger = format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS {}_ad AFTER DELETE ON "{}" BEGIN
+                DELETE FROM "{}_fts" WHERE rowid = old.rowid;
+            END
+        "#,
+            table_name, table_name, table_name
+        );
 
-        // Fetch all vectors
-        let sql = format!(
+        // Update trigger
+        let update_trigger = format!(
             r#"
-            SELECT id, vector FROM "{}"
-            WHERE is_embedded = 1 AND vector IS NOT NULL
+            CREATE TRIGGER IF NOT EXISTS {}_au AFTER UPDATE ON "{}" BEGIN
+                UPDATE "{}_fts" SET content = new.content WHERE rowid = old.rowid;
+            END
         "#,
-            table_name
+            table_name, table_name, table_name
         );
 
-        let rows = sqlx::query(&sql).fetch_all(pool).await?;
+        sqlx::query(&insert_trigger).execute(&pool).await?;
+        sqlx::query(&delete_trigger).execute(&pool).await?;
+        sqlx::query(&update_trigger).execute(&pool).await?;
 
-        if rows.is_empty() {
-            tracing::warn!("No vectors to index for {}.{}", db_id, table_name);
-            return Ok(());
-        }
+        Ok(())
+    }
 
-        let mut documents = Vec::new();
-        let mut dimensions = 0;
+    /// Store a document
+    pub async fn store_document(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        doc: Document,
+    ) -> Result<()> {
+        self.check_write_quota(db_id, table_name, &[&doc]).await?;
+        self.ensure_table(db_id, table_name).await?;
+        let history = !doc.is_chunk && self.table_settings(db_id, table_name).await?.history;
+        let pool = self.get_pool(db_id).await?;
 
-        for row in rows {
-            let id: String = row.get("id");
-            let vector_bytes: Vec<u8> = row.get("vector");
-            let vector = deserialize_vector(&vector_bytes);
-            if dimensions == 0 {
-                dimensions = vector.len();
-            }
-            documents.push((id, vector));
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+        self.check_storage_quota(&mut tx, db_id, table_name, &[&doc]).await?;
+        upsert_document(&mut tx, table_name, &doc).await?;
+        if history {
+            record_revision(&mut tx, table_name, &doc).await?;
         }
+        tx.commit().await?;
 
-        // Create and build index
-        let index = Arc::new(VectorIndex::new(dimensions, self.index_config.clone()));
-
-        index.build(documents)?;
-
-        let index_key = format!("{}:{}", db_id, table_name);
-        self.indexes.insert(index_key, index);
-
-        tracing::info!("HNSW index built for {}.{}", db_id, table_name);
+        // Keep a loaded index in sync with the stored vector
+        match doc.vector {
+            Some(ref vector) => self.index_vector(db_id, table_name, &doc.id, vector)?,
+            None => self.remove_from_index(db_id, table_name, std::slice::from_ref(&doc.id)),
+        }
 
         Ok(())
     }
 
-    /// List all databases
-    pub async fn list_databases(&self) -> Result<Vec<String>> {
-        let data_dir = Path::new("data");
-        let mut databases = Vec::new();
+    /// Store a batch of documents, with their chunks, in one transaction
+    ///
+    /// In [`BulkMode::Atomic`] the first failing item rolls back the whole batch. In
+    /// [`BulkMode::BestEffort`] each item is written under its own savepoint, so a
+    /// failing item (document or any of its chunks) is skipped and the rest commit.
+    pub async fn store_documents(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        items: Vec<BatchItem>,
+        mode: BulkMode,
+    ) -> Result<BatchWriteResult> {
+        let documents: Vec<&Document> = items.iter().map(|item| &item.document).collect();
+        self.check_write_quota(db_id, table_name, &documents).await?;
+        self.ensure_table(db_id, table_name).await?;
+        let history = self.table_settings(db_id, table_name).await?.history;
+        let pool = self.get_pool(db_id).await?;
 
-        if !data_dir.exists() {
-            return Ok(databases);
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+        self.check_storage_quota(&mut tx, db_id, table_name, &documents).await?;
+        let mut errors = vec![None; items.len()];
+
+        for (position, item) in items.iter().enumerate() {
+            let written = match mode {
+                BulkMode::Atomic => write_batch_item(&mut tx, table_name, item, history).await,
+                BulkMode::BestEffort => {
+                    let mut savepoint = tx.begin().await?;
+                    let written = write_batch_item(&mut savepoint, table_name, item, history).await;
+                    if written.is_ok() {
+                        savepoint.commit().await?;
+                    } else {
+                        savepoint.rollback().await?;
+                    }
+                    written
+                }
+            };
+
+            if let Err(e) = written {
+                errors[position] = Some(e.to_string());
+                if mode == BulkMode::Atomic {
+                    tx.rollback().await?;
+                    return Ok(BatchWriteResult {
+                        committed: false,
+                        errors,
+                    });
+                }
+            }
         }
 
-        let entries = std::fs::read_dir(data_dir)?;
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
+        tx.commit().await?;
 
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == "db" {
-                        if let Some(stem) = path.file_stem() {
-                            let db_name = stem.to_string_lossy().to_string();
-                            // Skip global.db
-                            if db_name != "global" {
-                                databases.push(db_name);
-                            }
-                        }
+        // Keep a loaded index in sync with the committed vectors
+        for (item, error) in items.iter().zip(&errors) {
+            if error.is_some() {
+                continue;
+            }
+            for doc in std::iter::once(&item.document).chain(&item.chunks) {
+                match doc.vector {
+                    Some(ref vector) => self.index_vector(db_id, table_name, &doc.id, vector)?,
+                    None => {
+                        self.remove_from_index(db_id, table_name, std::slice::from_ref(&doc.id))
                     }
                 }
             }
         }
 
-        databases.sort();
-        Ok(databases)
+        Ok(BatchWriteResult {
+            committed: true,
+            errors,
+        })
     }
 
-    /// List all tables in a database
-    pub async fn list_tables(&mut self, db_id: &str) -> Result<Vec<String>> {
+    /// Apply a partial update to a stored document
+    ///
+    /// `created_at` is kept. If the content changed, the document's chunks are
+    /// deleted and it is dropped from the vector index until it is re-embedded;
+    /// otherwise patched metadata and tags are copied to its chunks.
+    pub async fn patch_document(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        id: &str,
+        patch: &PatchDocumentRequest,
+    ) -> Result<PatchOutcome> {
+        self.revise_document(
+            db_id,
+            table_name,
+            id,
+            patch.changes_labels(),
+            patch.vectorize.is_none(),
+            |current| {
+                let mut doc = current.context("Document not found")?;
+                let content_changed = patch.apply(&mut doc)?;
+                Ok((doc, content_changed))
+            },
+        )
+        .await
+    }
+
+    /// Read a document and write back the revision `revise` makes of it, in one
+    /// write transaction so concurrent revisions cannot lose each other's changes.
+    /// `revise` gets `None` for a missing document and says whether the content
+    /// changed.
+    ///
+    /// Changed content deletes its chunks and drops it from the vector index;
+    /// otherwise `relabel` copies its metadata and tags to the chunks.
+    /// `reset_vectorize` turns embedding back on for a document that had been
+    /// chunked, so re-chunking can decide again.
+    async fn revise_document<F>(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        id: &str,
+        relabel: bool,
+        reset_vectorize: bool,
+        revise: F,
+    ) -> Result<PatchOutcome>
+    where
+        F: FnOnce(Option<Document>) -> Result<(Document, bool)>,
+    {
+        let history = self.table_settings(db_id, table_name).await?.history;
         let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
 
-        let rows = sqlx::query(
-            r#"
-            SELECT name FROM sqlite_master
-            WHERE type = 'table'
-            AND name NOT LIKE 'sqlite_%'
-            AND name NOT LIKE '%_fts'
-            AND name NOT LIKE '%_config'
-            AND name NOT LIKE '%_data'
-            AND name NOT LIKE '%_idx'
-            AND name NOT LIKE '%_docsize'
-            ORDER BY name
-            "#,
-        )
-        .fetch_all(pool)
-        .await?;
+        let current = fetch_document(&mut tx, db_id, table_name, id).await?;
+        let (mut doc, content_changed) = revise(current)?;
+        let history = history && !doc.is_chunk;
 
-        let tables: Vec<String> = rows.iter().map(|row| row.get("name")).collect();
+        let mut deleted_chunks = Vec::new();
+        if content_changed {
+            let delete = format!(
+                r#"DELETE FROM "{}" WHERE parent_id = ? AND is_chunk = 1 RETURNING id"#,
+                table_name
+            );
+            deleted_chunks = sqlx::query_scalar(&delete)
+                .bind(&doc.id)
+                .fetch_all(&mut *tx)
+                .await?;
 
-        Ok(tables)
+            if !deleted_chunks.is_empty() && reset_vectorize {
+                doc.vectorize = true;
+            }
+        } else if relabel {
+            let update = format!(
+                r#"UPDATE "{}" SET metadata = ?, tags = ?, updated_at = ? WHERE parent_id = ? AND is_chunk = 1"#,
+                table_name
+            );
+            sqlx::query(&update)
+                .bind(serde_json::to_string(&doc.metadata)?)
+                .bind(encode_tags(&doc.tags))
+                .bind(doc.updated_at)
+                .bind(&doc.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        upsert_document(&mut tx, table_name, &doc).await?;
+        if history {
+            record_revision(&mut tx, table_name, &doc).await?;
+        }
+        tx.commit().await?;
+
+        if content_changed {
+            deleted_chunks.push(doc.id.clone());
+            self.remove_from_index(db_id, table_name, &deleted_chunks);
+        }
+
+        Ok(PatchOutcome {
+            document: doc,
+            content_changed,
+        })
     }
 
-    /// FTS5 full-text search
-    pub async fn search_fts(
-        &mut self,
+    /// Get a document by ID
+    pub async fn get_document(
+        &self,
         db_id: &str,
         table_name: &str,
-        query: &str,
-        limit: usize,
-    ) -> Result<
-        Vec<(
-            String,
-            String,
-            HashMap<String, serde_json::Value>,
-            f64,
-            bool,
-            Option<String>,
-            Option<i32>,
-        )>,
-    > {
+        id: &str,
+    ) -> Result<Document> {
         let pool = self.get_pool(db_id).await?;
+        let mut conn = pool.acquire().await?;
 
-        let _fts_table = format!("{}_fts", table_name);
-        let sql = format!(
+        fetch_document(&mut conn, db_id, table_name, id)
+            .await?
+            .context("Document not found")
+    }
+
+    /// Get documents that need embedding
+    pub async fn get_non_embedded_documents(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        limit: i32,
+    ) -> Result<Vec<Document>> {
+        let pool = self.get_pool(db_id).await?;
+
+        let query = format!(
             r#"
-            SELECT d.id, d.content, d.metadata, fts.rank, d.is_chunk, d.parent_id, d.chunk_index
-            FROM "{0}_fts" AS fts
-            JOIN "{0}" AS d ON fts.rowid = d.rowid
-            WHERE fts.content MATCH ?
-            ORDER BY fts.rank
+            SELECT {}
+            FROM "{}"
+            WHERE is_embedded = 0 AND vectorize = 1 AND {}
+            ORDER BY id IN (SELECT doc_id FROM embedding_failures WHERE table_name = ?),
+                created_at ASC
             LIMIT ?
         "#,
-            table_name
+            DOCUMENT_COLUMNS, table_name, LIVE_ROWS
         );
 
-        let rows = sqlx::query(&sql)
-            .bind(query)
-            .bind(limit as i64)
-            .fetch_all(pool)
+        // Documents that failed before go last, so they cannot hold up the rest
+        let rows = sqlx::query(&query)
+            .bind(table_name)
+            .bind(limit)
+            .fetch_all(&pool)
             .await?;
 
-        let mut results = Vec::new();
-        for row in rows {
-            let id: String = row.get("id");
-            let content: String = row.get("content");
-            let metadata_json: String = row.get("metadata");
-            let rank: f64 = row.get("rank");
-            let is_chunk: i32 = row.get("is_chunk");
-            let parent_id: Option<String> = row.get("parent_id");
-            let chunk_index: Option<i32> = row.get("chunk_index");
+        Ok(rows
+            .iter()
+            .map(|row| row_to_document(row, db_id, table_name))
+            .collect())
+    }
 
-            let metadata: HashMap<String, serde_json::Value> =
-                serde_json::from_str(&metadata_json).unwrap_or_default();
+    /// Get all documents (embedded or not) - for listing endpoints
+    pub async fn get_all_documents(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        limit: i32,
+    ) -> Result<Vec<Document>> {
+        let pool = self.get_pool(db_id).await?;
 
-            results.push((
-                id,
-                content,
-                metadata,
-                rank,
-                is_chunk == 1,
-                parent_id,
-                chunk_index,
-            ));
-        }
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM "{}"
+            WHERE {}
+            ORDER BY created_at ASC
+            LIMIT ?
+        "#,
+            DOCUMENT_COLUMNS, table_name, LIVE_ROWS
+        );
 
-        Ok(results)
+        let rows = sqlx::query(&query).bind(limit).fetch_all(&pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| row_to_document(row, db_id, table_name))
+            .collect())
     }
 
-    /// Vector similarity search (cosine distance)
-    /// Uses HNSW index if available and enabled, otherwise falls back to brute-force
-    pub async fn search_vector(
-        &mut self,
+    /// List one page of a table's documents, using keyset pagination on the sort
+    /// timestamp and ID
+    pub async fn list_documents(
+        &self,
         db_id: &str,
         table_name: &str,
-        query_vector: &[f32],
-        limit: usize,
-    ) -> Result<
-        Vec<(
-            String,
-            String,
-            HashMap<String, serde_json::Value>,
-            f64,
-            bool,
-            Option<String>,
-            Option<i32>,
-        )>,
-    > {
-        // Check if we should use HNSW index
-        let use_index = self.should_use_index(db_id, table_name).await?;
+        options: &ListDocumentsQuery,
+    ) -> Result<DocumentPage> {
+        let limit = options
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let sort_column = options.sort.column();
+        let (direction, comparison) = match options.order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
 
-        if use_index {
-            return self
-                .search_vector_with_index(db_id, table_name, query_vector, limit)
-                .await;
+        let mut conditions = vec![LIVE_ROWS];
+        match options.kind {
+            DocumentKind::Roots => conditions.push("(parent_id IS NULL OR parent_id = '')"),
+            DocumentKind::Chunks => conditions.push("is_chunk = 1"),
+            DocumentKind::All => {}
+        }
+        if options.not_embedded {
+            conditions.push("is_embedded = 0");
         }
 
-        // Fall back to brute-force
-        self.search_vector_brute_force(db_id, table_name, query_vector, limit)
-            .await
-    }
-
-    /// Check if we should use HNSW index
-    async fn should_use_index(&mut self, db_id: &str, table_name: &str) -> Result<bool> {
-        if !self.use_indexing {
-            return Ok(false);
+        let cursor = options
+            .cursor
+            .as_deref()
+            .map(|cursor| PageCursor::decode(cursor, options.sort))
+            .transpose()?;
+        let keyset = format!("({}, id) {} (?, ?)", sort_column, comparison);
+        if cursor.is_some() {
+            conditions.push(&keyset);
         }
 
-        // Get document count
-        let pool = self.get_pool(db_id).await?;
-        let count_query = format!(
+        let where_clause = format!("WHERE {}", conditions.join(" AND "));
+
+        // Fetch one extra row to learn whether there is a next page
+        let query = format!(
             r#"
-            SELECT COUNT(*) as count FROM "{}" WHERE is_embedded = 1
+            SELECT {}, CAST({} AS TEXT) AS sort_key
+            FROM "{}"
+            {}
+            ORDER BY {} {}, id {}
+            LIMIT ?
         "#,
-            table_name
+            document_columns(options.include_content, options.include_vector),
+            sort_column,
+            table_name,
+            where_clause,
+            sort_column,
+            direction,
+            direction
         );
 
-        let row = sqlx::query(&count_query).fetch_one(pool).await?;
-        let count: i64 = row.get("count");
-
-        Ok(count as usize >= self.index_threshold)
-    }
+        let mut sql = sqlx::query(&query);
+        if let Some(ref cursor) = cursor {
+            sql = sql.bind(&cursor.key).bind(&cursor.id);
+        }
 
-    /// Search using HNSW index
-    async fn search_vector_with_index(
-        &mut self,
-        db_id: &str,
-        table_name: &str,
-        query_vector: &[f32],
-        limit: usize,
-    ) -> Result<
-        Vec<(
-            String,
-            String,
-            HashMap<String, serde_json::Value>,
-            f64,
-            bool,
-            Option<String>,
-            Option<i32>,
-        )>,
-    > {
-        let index_key = format!("{}:{}", db_id, table_name);
-
-        // Build index if not exists
-        if !self.indexes.contains_key(&index_key) {
-            self.build_index(db_id, table_name).await?;
-        }
-
-        // Search using index
-        let index = self
-            .indexes
-            .get(&index_key)
-            .ok_or_else(|| anyhow::anyhow!("Index not found"))?;
-
-        let neighbors = index.search(query_vector, limit)?;
-
-        // Fetch document details
         let pool = self.get_pool(db_id).await?;
-        let mut results = Vec::new();
-
-        for (doc_id, similarity) in neighbors {
-            let query = format!(
-                r#"
-                SELECT id, content, metadata, is_chunk, parent_id, chunk_index FROM "{}"
-                WHERE id = ?
-            "#,
-                table_name
-            );
-
-            if let Ok(row) = sqlx::query(&query).bind(&doc_id).fetch_one(pool).await {
-                let content: String = row.get("content");
-                let metadata_json: String = row.get("metadata");
-                let metadata: HashMap<String, serde_json::Value> =
-                    serde_json::from_str(&metadata_json).unwrap_or_default();
-                let is_chunk: i32 = row.get("is_chunk");
-                let parent_id: Option<String> = row.get("parent_id");
-                let chunk_index: Option<i32> = row.get("chunk_index");
-
-                results.push((
-                    doc_id,
-                    content,
-                    metadata,
-                    similarity as f64,
-                    is_chunk == 1,
-                    parent_id,
-                    chunk_index,
-                ));
-            }
-        }
+        let mut rows = sql.bind((limit + 1) as i64).fetch_all(&pool).await?;
+
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|row| {
+                PageCursor {
+                    sort: options.sort,
+                    key: row.get("sort_key"),
+                    id: row.get("id"),
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
 
-        tracing::debug!("HNSW search returned {} results", results.len());
-        Ok(results)
+        Ok(DocumentPage {
+            documents: rows
+                .iter()
+                .map(|row| row_to_document(row, db_id, table_name))
+                .collect(),
+            next_cursor,
+        })
     }
 
-    /// Brute-force vector search
-    async fn search_vector_brute_force(
-        &mut self,
+    /// Dimensions of the vectors already stored in a table, if any
+    pub async fn table_vector_dimensions(
+        &self,
         db_id: &str,
         table_name: &str,
-        query_vector: &[f32],
-        limit: usize,
-    ) -> Result<
-        Vec<(
-            String,
-            String,
-            HashMap<String, serde_json::Value>,
-            f64,
-            bool,
-            Option<String>,
-            Option<i32>,
-        )>,
-    > {
+    ) -> Result<Option<usize>> {
+        if !self.table_exists(db_id, table_name).await? {
+            return Ok(None);
+        }
+
         let pool = self.get_pool(db_id).await?;
 
-        let sql = format!(
-            r#"
-            SELECT id, content, metadata, vector, is_chunk, parent_id, chunk_index
-            FROM "{}"
-            WHERE is_embedded = 1 AND vector IS NOT NULL
-        "#,
+        let query = format!(
+            r#"SELECT length(vector) FROM "{}" WHERE vector IS NOT NULL LIMIT 1"#,
             table_name
         );
 
-        let rows = sqlx::query(&sql).fetch_all(pool).await?;
-
-        let mut results = Vec::new();
-        for row in rows {
-            let id: String = row.get("id");
-            let content: String = row.get("content");
-            let metadata_json: String = row.get("metadata");
-            let vector_bytes: Vec<u8> = row.get("vector");
-            let is_chunk: i32 = row.get("is_chunk");
-            let parent_id: Option<String> = row.get("parent_id");
-            let chunk_index: Option<i32> = row.get("chunk_index");
-
-            let metadata: HashMap<String, serde_json::Value> =
-                serde_json::from_str(&metadata_json).unwrap_or_default();
-
-            // Deserialize vector
-            let doc_vector = deserialize_vector(&vector_bytes);
-
-            // Calculate cosine similarity
-            let similarity = cosine_similarity(query_vector, &doc_vector);
-
-            results.push((
-                id,
-                content,
-                metadata,
-                similarity,
-                is_chunk == 1,
-                parent_id,
-                chunk_index,
-            ));
-        }
-
-        // Sort by similarity (descending)
-        results.sort_by(|a, b| b.3.partial_cmp(&a.3).unwrap());
-
-        // Return top results
-        results.truncate(limit);
-
-        Ok(results)
+        let bytes: Option<i64> = sqlx::query_scalar(&query).fetch_optional(&pool).await?;
+        Ok(bytes.map(|b| b as usize / 4))
     }
 
-    // ===== Document Relations Methods =====
-
-    /// Create a document relation
-    pub async fn create_relation(
-        &mut self,
+    /// Check a client-supplied vector before storing it in a table
+    ///
+    /// The vector must match the dimensions of the vectors already in the table,
+    /// or `default_dimensions` if the table holds none yet.
+    pub async fn validate_vector(
+        &self,
         db_id: &str,
-        relation: crate::models::DocumentRelation,
+        table_name: &str,
+        vector: &[f32],
+        default_dimensions: usize,
     ) -> Result<()> {
-        let pool = self.get_pool(db_id).await?;
-
-        let metadata_json = serde_json::to_string(&relation.metadata)?;
-
-        sqlx::query(r#"
-            INSERT INTO document_relations (id, source_id, target_id, relation_type, metadata, created_at)
-            VALUES (?, ?, ?, ?, ?, ?)
-        "#)
-        .bind(&relation.id)
-        .bind(&relation.source_id)
-        .bind(&relation.target_id)
-        .bind(&relation.relation_type)
-        .bind(&metadata_json)
-        .bind(relation.created_at)
-        .execute(pool)
-        .await?;
+        let expected = self
+            .table_vector_dimensions(db_id, table_name)
+            .await?
+            .unwrap_or(default_dimensions);
 
-        Ok(())
+        check_vector(vector, expected)
     }
 
-    /// Get relation by ID
-    pub async fn get_relation(
-        &mut self,
+    /// Update document vector
+    pub async fn update_document_vector(
+        &self,
         db_id: &str,
-        relation_id: &str,
-    ) -> Result<crate::models::DocumentRelation> {
+        table_name: &str,
+        doc_id: &str,
+        vector: &[f32],
+    ) -> Result<()> {
         let pool = self.get_pool(db_id).await?;
+        let vector_bytes = serialize_vector(vector);
 
-        let row = sqlx::query(
+        let query = format!(
             r#"
-            SELECT id, source_id, target_id, relation_type, metadata, created_at
-            FROM document_relations
+            UPDATE "{}"
+            SET vector = ?, is_embedded = 1, is_vectorized = 1, updated_at = ?
             WHERE id = ?
         "#,
-        )
-        .bind(relation_id)
-        .fetch_one(pool)
-        .await?;
-
-        let metadata_json: String = row.get("metadata");
-        let metadata = serde_json::from_str(&metadata_json).unwrap_or_default();
-
-        Ok(crate::models::DocumentRelation {
-            id: row.get("id"),
-            source_id: row.get("source_id"),
-            target_id: row.get("target_id"),
-            relation_type: row.get("relation_type"),
-            metadata,
-            created_at: row.get("created_at"),
-        })
-    }
+            table_name
+        );
 
-    /// Delete a relation
-    pub async fn delete_relation(&mut self, db_id: &str, relation_id: &str) -> Result<()> {
-        let pool = self.get_pool(db_id).await?;
+        sqlx::query(&query)
+            .bind(&vector_bytes)
+            .bind(Utc::now())
+            .bind(doc_id)
+            .execute(&pool)
+            .await?;
+        sqlx::query("DELETE FROM embedding_failures WHERE table_name = ? AND doc_id = ?")
+            .bind(table_name)
+            .bind(doc_id)
+            .execute(&pool)
+            .await?;
 
-        sqlx::query(
-            r#"
-            DELETE FROM document_relations WHERE id = ?
-        "#,
-        )
-        .bind(relation_id)
-        .execute(pool)
-        .await?;
+        // Add to (or replace in) the vector index if it exists
+        self.index_vector(db_id, table_name, doc_id, vector)?;
 
         Ok(())
     }
 
-    /// Get all relations for a document
-    pub async fn get_document_relations(
-        &mut self,
+    /// Record that the embedding worker failed to embed a document
+    ///
+    /// The document stays pending, but is retried after documents that have not
+    /// failed.
+    pub async fn record_embedding_failure(
+        &self,
         db_id: &str,
+        table_name: &str,
         doc_id: &str,
-    ) -> Result<Vec<crate::models::DocumentRelation>> {
+        error: &str,
+    ) -> Result<()> {
         let pool = self.get_pool(db_id).await?;
-
-        let rows = sqlx::query(
+        sqlx::query(
             r#"
-            SELECT id, source_id, target_id, relation_type, metadata, created_at
-            FROM document_relations
-            WHERE source_id = ? OR target_id = ?
+            INSERT INTO embedding_failures (table_name, doc_id, error, attempts, failed_at)
+            VALUES (?, ?, ?, 1, ?)
+            ON CONFLICT(table_name, doc_id) DO UPDATE SET
+                error = excluded.error,
+                attempts = attempts + 1,
+                failed_at = excluded.failed_at
         "#,
         )
+        .bind(table_name)
         .bind(doc_id)
-        .bind(doc_id)
-        .fetch_all(pool)
+        .bind(error)
+        .bind(Utc::now())
+        .execute(&pool)
         .await?;
 
-        let mut relations = Vec::new();
-        for row in rows {
-            let metadata_json: String = row.get("metadata");
-            let metadata = serde_json::from_str(&metadata_json).unwrap_or_default();
-
-            relations.push(crate::models::DocumentRelation {
-                id: row.get("id"),
-                source_id: row.get("source_id"),
-                target_id: row.get("target_id"),
-                relation_type: row.get("relation_type"),
-                metadata,
-                created_at: row.get("created_at"),
-            });
-        }
-
-        Ok(relations)
+        Ok(())
     }
 
-    /// Get all relations in database (for graph operations)
-    pub async fn get_all_relations(
-        &mut self,
+    /// Add or replace a document's vector in a table's index, if one is loaded
+    /// or being built
+    fn index_vector(
+        &self,
         db_id: &str,
-    ) -> Result<Vec<crate::models::DocumentRelation>> {
-        let pool = self.get_pool(db_id).await?;
+        table_name: &str,
+        doc_id: &str,
+        vector: &[f32],
+    ) -> Result<()> {
+        let index_key = format!("{}:{}", db_id, table_name);
+        if self.record_pending_change(&index_key, doc_id, Some(vector)) {
+            return Ok(());
+        }
+        let Some(index) = self.loaded_index(&index_key) else {
+            return Ok(());
+        };
 
-        let rows = sqlx::query(
-            r#"
-            SELECT id, source_id, target_id, relation_type, metadata, created_at
-            FROM document_relations
-        "#,
-        )
-        .fetch_all(pool)
-        .await?;
+        index.add(doc_id.to_string(), vector.to_vec())?;
+        self.dirty_indexes.lock().unwrap().insert(index_key);
+        schedule_compaction(db_id, table_name, index);
 
-        let mut relations = Vec::new();
-        for row in rows {
-            let metadata_json: String = row.get("metadata");
-            let metadata = serde_json::from_str(&metadata_json).unwrap_or_default();
+        Ok(())
+    }
 
-            relations.push(crate::models::DocumentRelation {
-                id: row.get("id"),
-                source_id: row.get("source_id"),
-                target_id: row.get("target_id"),
-                relation_type: row.get("relation_type"),
-                metadata,
-                created_at: row.get("created_at"),
-            });
+    /// Remove deleted documents from a table's index, if one is loaded or being
+    /// built
+    fn remove_from_index(&self, db_id: &str, table_name: &str, doc_ids: &[String]) {
+        let index_key = format!("{}:{}", db_id, table_name);
+        let mut pending = false;
+        for doc_id in doc_ids {
+            pending |= self.record_pending_change(&index_key, doc_id, None);
+        }
+        if pending {
+            return;
+        }
+        let Some(index) = self.loaded_index(&index_key) else {
+            return;
+        };
+
+        let mut removed = false;
+        for doc_id in doc_ids {
+            removed |= index.delete(doc_id);
         }
 
-        Ok(relations)
+        if removed {
+            self.dirty_indexes.lock().unwrap().insert(index_key);
+            schedule_compaction(db_id, table_name, index);
+        }
     }
 
-    // ===== Chunking Methods =====
+    /// Record a change to an index that is being loaded or built, returning
+    /// `false` if there is no such index
+    ///
+    /// Checked under the same lock that publishes the index, so a change is either
+    /// replayed on it or made to the published index.
+    fn record_pending_change(&self, index_key: &str, doc_id: &str, vector: Option<&[f32]>) -> bool {
+        let mut pending = self.pending_index_changes.lock().unwrap();
+        let Some(changes) = pending.get_mut(index_key) else {
+            return false;
+        };
+        changes.push((doc_id.to_string(), vector.map(<[f32]>::to_vec)));
+        true
+    }
 
-    /// Get all chunks for a parent document
-    pub async fn get_chunks(
-        &mut self,
+    /// Embedded row count and latest `updated_at` of a table, used to detect stale saved indexes
+    async fn index_watermark(
+        &self,
         db_id: &str,
         table_name: &str,
-        parent_id: &str,
-    ) -> Result<Vec<Document>> {
+    ) -> Result<(i64, Option<String>)> {
         let pool = self.get_pool(db_id).await?;
 
-        let query = format!(
+        let sql = format!(
             r#"
-            SELECT id, content, metadata, tags, vector, created_at, updated_at, is_embedded, 
-                   vectorize, is_chunk, parent_id, chunk_index, token_count, is_vectorized
-            FROM "{}"
-            WHERE parent_id = ? AND is_chunk = 1
-            ORDER BY chunk_index ASC
+            SELECT COUNT(*) AS count, CAST(MAX(updated_at) AS TEXT) AS watermark FROM "{}"
+            WHERE is_embedded = 1 AND vector IS NOT NULL AND deleted_at IS NULL
         "#,
             table_name
         );
 
-        let rows = sqlx::query(&query).bind(parent_id).fetch_all(pool).await?;
-
-        let mut documents = Vec::new();
-        for row in rows {
-            let metadata_json: String = row.get("metadata");
-            let metadata = serde_json::from_str(&metadata_json).unwrap_or_default();
+        let row = sqlx::query(&sql).fetch_one(&pool).await?;
+        Ok((row.get("count"), row.get("watermark")))
+    }
 
-            let tags_str: String = row.get("tags");
-            let tags: Vec<String> = if tags_str.is_empty() {
-                Vec::new()
-            } else {
-                tags_str.split(',').map(String::from).collect()
-            };
+    /// Load a saved HNSW index for a table if it is still current
+    ///
+    /// Returns `None` when there is no usable saved index and it must be rebuilt.
+    async fn load_index(&self, db_id: &str, table_name: &str) -> Result<Option<VectorIndex>> {
+        if !self.persist_indexes {
+            return Ok(None);
+        }
 
-            let vector_bytes: Option<Vec<u8>> = row.get("vector");
-            let vector = vector_bytes.map(|bytes| deserialize_vector(&bytes));
+        let (index_config, _) = self.table_index_config(db_id, table_name).await?;
+        let dir = self.base_dir.clone();
+        let basename = index_basename(db_id, table_name);
+        let loaded = tokio::task::spawn_blocking(move || {
+            VectorIndex::load(Path::new(&dir), &basename, index_config)
+        })
+        .await?;
+        let loaded = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to load saved index for {}.{}: {}",
+                    db_id,
+                    table_name,
+                    e
+                );
+                return Ok(None);
+            }
+        };
 
-            let is_embedded: i32 = row.get("is_embedded");
-            let vectorize: i32 = row.get("vectorize");
-            let is_chunk: i32 = row.get("is_chunk");
-            let is_vectorized: i32 = row.get("is_vectorized");
+        let Some((index, manifest)) = loaded else {
+            return Ok(None);
+        };
 
-            documents.push(Document {
-                id: row.get("id"),
-                db: db_id.to_string(),
-                table: table_name.to_string(),
-                content: row.get("content"),
-                metadata,
-                tags,
-                vector,
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                is_embedded: is_embedded == 1,
-                vectorize: vectorize == 1,
-                is_chunk: is_chunk == 1,
-                parent_id: row.get("parent_id"),
-                chunk_index: row.get("chunk_index"),
-                token_count: row.get("token_count"),
-                is_vectorized: is_vectorized == 1,
-            });
+        let (row_count, watermark) = self.index_watermark(db_id, table_name).await?;
+        if manifest.row_count != row_count || manifest.watermark != watermark {
+            tracing::info!(
+                "Saved index for {}.{} is stale ({} rows saved, {} in table), rebuilding",
+                db_id,
+                table_name,
+                manifest.row_count,
+                row_count
+            );
+            return Ok(None);
         }
 
-        Ok(documents)
+        Ok(Some(index))
     }
 
-    /// Delete all chunks for a parent document
-    pub async fn delete_chunks(
-        &mut self,
-        db_id: &str,
-        table_name: &str,
-        parent_id: &str,
-    ) -> Result<()> {
-        let pool = self.get_pool(db_id).await?;
+    /// Save indexes modified since they were last saved (e.g. on shutdown)
+    pub async fn persist_indexes(&self) -> Result<()> {
+        if !self.persist_indexes {
+            return Ok(());
+        }
 
-        let query = format!(
-            r#"
-            DELETE FROM "{}"
-            WHERE parent_id = ? AND is_chunk = 1
-        "#,
-            table_name
-        );
+        let dirty: Vec<String> = self.dirty_indexes.lock().unwrap().drain().collect();
+        for index_key in dirty {
+            let Some(index) = self.loaded_index(&index_key) else {
+                continue;
+            };
+            let Some((db_id, table_name)) = index_key.split_once(':') else {
+                continue;
+            };
+
+            // Only save if the index still covers every embedded row; otherwise
+            // the next load would find it stale anyway
+            let (row_count, watermark) = self.index_watermark(db_id, table_name).await?;
+            if row_count != index.len() as i64 {
+                tracing::info!(
+                    "Index for {}.{} is out of sync with the table, not saving",
+                    db_id,
+                    table_name
+                );
+                continue;
+            }
 
-        sqlx::query(&query).bind(parent_id).execute(pool).await?;
+            // Dumping a large graph blocks, so it runs off the runtime
+            let dir = self.base_dir.clone();
+            let basename = index_basename(db_id, table_name);
+            tokio::task::spawn_blocking(move || {
+                index.save(Path::new(&dir), &basename, row_count, watermark)
+            })
+            .await??;
+        }
 
         Ok(())
     }
 
-    /// Convenience method to add a document from a StoreDocumentRequest
-    /// This provides a cleaner API for adding documents without manually constructing Document structs
-    pub async fn add_document(
-        &mut self,
+    /// In-memory index of a table, by `db:table` key
+    fn loaded_index(&self, index_key: &str) -> Option<Arc<VectorIndex>> {
+        self.indexes.read().unwrap().get(index_key).cloned()
+    }
+
+    /// Load a table's saved index, or build it if missing or stale
+    ///
+    /// Searches of the same table wait for the first one to finish; other tables
+    /// are not held up. Writes to the table in the meantime are replayed on the
+    /// index before it is published. If the index is dropped in the meantime,
+    /// e.g. because the table's settings changed, it serves this search only.
+    async fn load_or_build_index(
+        &self,
         db_id: &str,
         table_name: &str,
-        request: crate::models::StoreDocumentRequest,
-    ) -> Result<Document> {
-        use uuid::Uuid;
+    ) -> Result<Arc<VectorIndex>> {
+        let index_key = format!("{}:{}", db_id, table_name);
+        let building = self
+            .building
+            .lock()
+            .unwrap()
+            .entry(index_key.clone())
+            .or_default()
+            .clone();
+        let _building = building.lock().await;
+
+        if let Some(index) = self.loaded_index(&index_key) {
+            return Ok(index);
+        }
 
-        let doc_id = request.id.unwrap_or_else(|| Uuid::new_v4().to_string());
+        self.pending_index_changes
+            .lock()
+            .unwrap()
+            .insert(index_key.clone(), Vec::new());
+        let index = match self.load_index(db_id, table_name).await {
+            Ok(Some(index)) => Ok(Some(Arc::new(index))),
+            Ok(None) => self.build_index(db_id, table_name).await,
+            Err(e) => Err(e),
+        };
 
-        let doc = Document {
-            id: doc_id.clone(),
-            db: db_id.to_string(),
-            table: table_name.to_string(),
-            content: request.content,
-            metadata: request.metadata,
-            tags: request.tags,
-            vector: None,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-            is_embedded: false,
-            vectorize: request.vectorize,
-            is_chunk: false,
-            parent_id: None,
-            chunk_index: None,
-            token_count: None,
-            is_vectorized: false,
+        let mut pending = self.pending_index_changes.lock().unwrap();
+        let changes = pending.remove(&index_key);
+        let index = index?.ok_or_else(|| anyhow::anyhow!("Index not found"))?;
+        let Some(changes) = changes else {
+            return Ok(index);
         };
 
-        self.store_document(db_id, table_name, doc.clone()).await?;
-        Ok(doc)
+        for (doc_id, vector) in &changes {
+            match vector {
+                Some(vector) => index.add(doc_id.clone(), vector.clone())?,
+                None => {
+                    index.delete(doc_id);
+                }
+            }
+        }
+        let mut dirty = self.dirty_indexes.lock().unwrap();
+        if changes.is_empty() {
+            dirty.remove(&index_key);
+        } else {
+            dirty.insert(index_key.clone());
+        }
+        self.indexes
+            .write()
+            .unwrap()
+            .insert(index_key, index.clone());
+
+        Ok(index)
     }
 
-    /// Convenience method to add a simple document with just content
-    pub async fn add_simple_document(
-        &mut self,
+    /// Build HNSW index for a table, or `None` if it has no vectors
+    async fn build_index(
+        &self,
         db_id: &str,
         table_name: &str,
-        content: impl Into<String>,
-    ) -> Result<Document> {
-        let request = crate::models::StoreDocumentRequest {
-            id: None,
-            content: content.into(),
-            metadata: std::collections::HashMap::new(),
-            tags: Vec::new(),
-            vectorize: true,
+    ) -> Result<Option<Arc<VectorIndex>>> {
+        // Capture the table state before reading vectors so a concurrent write
+        // makes the saved index look stale rather than current
+        let (row_count, watermark) = self.index_watermark(db_id, table_name).await?;
+
+        let pool = self.get_pool(db_id).await?;
+
+        tracing::info!("Building HNSW index for {}.{}", db_id, table_name);
+
+        // Fetch all vectors
+        let sql = format!(
+            r#"
+            SELECT id, vector FROM "{}"
+            WHERE is_embedded = 1 AND vector IS NOT NULL AND deleted_at IS NULL
+        "#,
+            table_name
+        );
+
+        let rows = sqlx::query(&sql).fetch_all(&pool).await?;
+
+        if rows.is_empty() {
+            tracing::warn!("No vectors to index for {}.{}", db_id, table_name);
+            return Ok(None);
+        }
+
+        let mut documents = Vec::new();
+        let mut dimensions = 0;
+
+        for row in rows {
+            let id: String = row.get("id");
+            let vector_bytes: Vec<u8> = row.get("vector");
+            let vector = deserialize_vector(&vector_bytes);
+            if dimensions == 0 {
+                dimensions = vector.len();
+            }
+            documents.push((id, vector));
+        }
+
+        // Create and build index on a blocking thread, so building a large index
+        // does not stall the runtime
+        let (index_config, _) = self.table_index_config(db_id, table_name).await?;
+        let persist = self.persist_indexes.then(|| self.base_dir.clone());
+        let basename = index_basename(db_id, table_name);
+        let label = format!("{}.{}", db_id, table_name);
+        let index = tokio::task::spawn_blocking(move || -> Result<Arc<VectorIndex>> {
+            let index = Arc::new(VectorIndex::new(dimensions, index_config));
+            index.build(documents)?;
+
+            if let Some(dir) = persist {
+                if let Err(e) = index.save(Path::new(&dir), &basename, row_count, watermark) {
+                    tracing::warn!("Failed to save index for {}: {}", label, e);
+                }
+            }
+
+            Ok(index)
+        })
+        .await??;
+
+        tracing::info!("HNSW index built for {}.{}", db_id, table_name);
+
+        Ok(Some(index))
+    }
+
+    /// List all databases
+    pub async fn list_databases(&self) -> Result<Vec<String>> {
+        let data_dir = Path::new(&self.base_dir);
+        let mut databases = Vec::new();
+
+        if !data_dir.exists() {
+            return Ok(databases);
+        }
+
+        let entries = std::fs::read_dir(data_dir)?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() {
+                if let Some(ext) = path.extension() {
+                    if ext == "db" {
+                        if let Some(stem) = path.file_stem() {
+                            let db_name = stem.to_string_lossy().to_string();
+                            // Skip global.db
+                            if db_name != "global" {
+                                databases.push(db_name);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        databases.sort();
+        Ok(databases)
+    }
+
+    /// List all tables in a database
+    pub async fn list_tables(&self, db_id: &str) -> Result<Vec<String>> {
+        let pool = self.get_pool(db_id).await?;
+        user_tables(&mut *pool.acquire().await?).await
+    }
+
+    /// Check if a table exists in a database
+    pub async fn table_exists(&self, db_id: &str, table_name: &str) -> Result<bool> {
+        let pool = self.get_pool(db_id).await?;
+
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?",
+        )
+        .bind(table_name)
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Tags of a table with the number of documents carrying each, most used first
+    ///
+    /// Counts live root documents, or with `ids` only those documents (chunks
+    /// included), e.g. the results of a search.
+    pub async fn tag_counts(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        ids: Option<&[String]>,
+    ) -> Result<Vec<TagCount>> {
+        self.ensure_table(db_id, table_name).await?;
+        let pool = self.get_pool(db_id).await?;
+
+        let scope = match ids {
+            Some(_) => "d.id IN (SELECT value FROM json_each(?))",
+            None => "d.is_chunk = 0",
         };
-        self.add_document(db_id, table_name, request).await
+        let query = format!(
+            r#"
+            SELECT g.tag, COUNT(*) AS count
+            FROM "{t}_tags" g
+            JOIN "{t}" d ON d.id = g.doc_id
+            WHERE {live} AND {scope}
+            GROUP BY g.tag
+            ORDER BY count DESC, g.tag ASC
+        "#,
+            t = table_name,
+            live = LIVE_ROWS,
+            scope = scope
+        );
+
+        let mut query = sqlx::query(&query);
+        if let Some(ids) = ids {
+            query = query.bind(serde_json::to_string(ids)?);
+        }
+        let rows = query.fetch_all(&pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| TagCount {
+                tag: row.get("tag"),
+                count: row.get("count"),
+            })
+            .collect())
     }
 
-    /// Convenience method to delete a document by ID
-    pub async fn delete_document_by_id(
-        &mut self,
+    /// Rename a tag on every document of a table, trashed ones included, and
+    /// return how many root documents changed
+    ///
+    /// A document that already carries `to` keeps one copy of it, so renaming onto
+    /// an existing tag merges the two. Chunks are relabeled along with their
+    /// parents, and live documents get a revision when history is enabled.
+    pub async fn rename_tag(
+        &self,
         db_id: &str,
         table_name: &str,
-        doc_id: &str,
-    ) -> Result<()> {
+        from: &str,
+        to: &str,
+    ) -> Result<usize> {
+        self.ensure_table(db_id, table_name).await?;
+        let history = self.table_settings(db_id, table_name).await?.history;
         let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
 
-        // First, check if this is a child document (has parent_id)
-        let check_query = format!(
-            r#"SELECT parent_id FROM "{}" WHERE id = ?"#,
+        let query = format!(
+            r#"
+            SELECT {c}, deleted_at
+            FROM "{t}"
+            WHERE id IN (SELECT doc_id FROM "{t}_tags" WHERE tag = ?)
+        "#,
+            c = DOCUMENT_COLUMNS,
+            t = table_name
+        );
+        let rows = sqlx::query(&query).bind(from).fetch_all(&mut *tx).await?;
+
+        let update = format!(
+            r#"UPDATE "{}" SET tags = ?, updated_at = ? WHERE id = ?"#,
             table_name
         );
-        
-        let result: Option<(Option<String>,)> = sqlx::query_as(&check_query)
-            .bind(doc_id)
-            .fetch_optional(pool)
+        let now = Utc::now();
+        let mut renamed = 0;
+        for row in &rows {
+            let mut doc = row_to_document(row, db_id, table_name);
+            replace_tag(&mut doc.tags, from, to);
+            doc.updated_at = now;
+
+            sqlx::query(&update)
+                .bind(encode_tags(&doc.tags))
+                .bind(now)
+                .bind(&doc.id)
+                .execute(&mut *tx)
+                .await?;
+
+            if !doc.is_chunk {
+                renamed += 1;
+                let deleted_at: Option<DateTime<Utc>> = row.get("deleted_at");
+                if history && deleted_at.is_none() {
+                    record_revision(&mut tx, table_name, &doc).await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(renamed)
+    }
+
+    /// FTS5 full-text search
+    pub async fn search_fts(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<
+        Vec<(
+            String,
+            String,
+            HashMap<String, serde_json::Value>,
+            f64,
+            bool,
+            Option<String>,
+            Option<i32>,
+        )>,
+    > {
+        self.search_fts_filtered(db_id, table_name, query, limit, &SearchFilter::default())
+            .await
+    }
+
+    /// FTS5 full-text search restricted to documents matching `filter`
+    pub async fn search_fts_filtered(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        query: &str,
+        limit: usize,
+        filter: &SearchFilter,
+    ) -> Result<
+        Vec<(
+            String,
+            String,
+            HashMap<String, serde_json::Value>,
+            f64,
+            bool,
+            Option<String>,
+            Option<i32>,
+        )>,
+    > {
+        let indexes = self.metadata_indexes(db_id, table_name).await?;
+        let pool = self.get_pool(db_id).await?;
+
+        let (filter_sql, filter_params) = filter.to_sql(table_name, "d", &indexes);
+        let sql = format!(
+            r#"
+            SELECT d.id, d.content, d.metadata, fts.rank, d.is_chunk, d.parent_id, d.chunk_index
+            FROM "{0}_fts" AS fts
+            JOIN "{0}" AS d ON fts.rowid = d.rowid
+            WHERE fts.content MATCH ? AND {2}{1}
+            ORDER BY fts.rank
+            LIMIT ?
+        "#,
+            table_name, filter_sql, LIVE_ROWS
+        );
+
+        let rows = bind_params(sqlx::query(&sql).bind(query), &filter_params)
+            .bind(limit as i64)
+            .fetch_all(&pool)
             .await?;
-        
-        if let Some((Some(_parent_id),)) = result {
-            return Err(anyhow::anyhow!(
-                "Cannot delete child document. Delete the parent document instead, which will cascade delete all children."
+
+        let mut results = Vec::new();
+        for row in rows {
+            let id: String = row.get("id");
+            let content: String = row.get("content");
+            let metadata_json: String = row.get("metadata");
+            let rank: f64 = row.get("rank");
+            let is_chunk: i32 = row.get("is_chunk");
+            let parent_id: Option<String> = row.get("parent_id");
+            let chunk_index: Option<i32> = row.get("chunk_index");
+
+            let metadata: HashMap<String, serde_json::Value> =
+                serde_json::from_str(&metadata_json).unwrap_or_default();
+
+            results.push((
+                id,
+                content,
+                metadata,
+                rank,
+                is_chunk == 1,
+                parent_id,
+                chunk_index,
             ));
         }
 
-        // Document is a parent or standalone - proceed with deletion
-        // CASCADE will automatically delete children
-        let query = format!(r#"DELETE FROM "{}" WHERE id = ?"#, table_name);
-        sqlx::query(&query).bind(doc_id).execute(pool).await?;
+        Ok(results)
+    }
+
+    /// Vector similarity search (cosine distance)
+    /// Uses HNSW index if available and enabled, otherwise falls back to brute-force
+    pub async fn search_vector(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        query_vector: &[f32],
+        limit: usize,
+    ) -> Result<
+        Vec<(
+            String,
+            String,
+            HashMap<String, serde_json::Value>,
+            f64,
+            bool,
+            Option<String>,
+            Option<i32>,
+        )>,
+    > {
+        self.search_vector_filtered(
+            db_id,
+            table_name,
+            query_vector,
+            limit,
+            &SearchFilter::default(),
+        )
+        .await
+    }
+
+    /// Vector similarity search restricted to documents matching `filter`
+    pub async fn search_vector_filtered(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        query_vector: &[f32],
+        limit: usize,
+        filter: &SearchFilter,
+    ) -> Result<
+        Vec<(
+            String,
+            String,
+            HashMap<String, serde_json::Value>,
+            f64,
+            bool,
+            Option<String>,
+            Option<i32>,
+        )>,
+    > {
+        // Check if we should use HNSW index
+        let use_index = self.should_use_index(db_id, table_name).await?;
+
+        if use_index {
+            return self
+                .search_vector_with_index(db_id, table_name, query_vector, limit, filter)
+                .await;
+        }
+
+        // Fall back to brute-force
+        self.search_vector_brute_force(db_id, table_name, query_vector, limit, filter)
+            .await
+    }
+
+    /// Check if we should use HNSW index
+    async fn should_use_index(&self, db_id: &str, table_name: &str) -> Result<bool> {
+        if !self.use_indexing {
+            return Ok(false);
+        }
+        let Some(threshold) = self.table_index_config(db_id, table_name).await?.1 else {
+            return Ok(false);
+        };
+
+        // Get document count
+        let pool = self.get_pool(db_id).await?;
+        let count_query = format!(
+            r#"
+            SELECT COUNT(*) as count FROM "{}" WHERE is_embedded = 1 AND deleted_at IS NULL
+        "#,
+            table_name
+        );
+
+        let row = sqlx::query(&count_query).fetch_one(&pool).await?;
+        let count: i64 = row.get("count");
+
+        Ok(count as usize >= threshold)
+    }
+
+    /// HNSW parameters and document threshold of a table's index, with the table's
+    /// overrides applied. There is no threshold if the table is never indexed.
+    async fn table_index_config(
+        &self,
+        db_id: &str,
+        table_name: &str,
+    ) -> Result<(IndexConfig, Option<usize>)> {
+        let overrides = self.table_settings(db_id, table_name).await?.vector_index;
+        let config = IndexConfig {
+            hnsw_m: overrides.hnsw_m.unwrap_or(self.index_config.hnsw_m),
+            hnsw_ef_construction: overrides
+                .hnsw_ef_construction
+                .unwrap_or(self.index_config.hnsw_ef_construction),
+            hnsw_ef_search: overrides
+                .hnsw_ef_search
+                .unwrap_or(self.index_config.hnsw_ef_search),
+            compaction_threshold: overrides
+                .compaction_threshold
+                .unwrap_or(self.index_config.compaction_threshold),
+        };
+        let threshold = match overrides.mode.as_deref() {
+            Some("never") => None,
+            Some("always") => Some(0),
+            _ => Some(overrides.threshold.unwrap_or(self.index_threshold)),
+        };
+
+        Ok((config, threshold))
+    }
+
+    /// Search using HNSW index
+    ///
+    /// Filters are applied after the ANN lookup, so the index is over-fetched
+    /// (and the fetch widened) until `limit` matching documents are found or
+    /// the index is exhausted.
+    async fn search_vector_with_index(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        query_vector: &[f32],
+        limit: usize,
+        filter: &SearchFilter,
+    ) -> Result<
+        Vec<(
+            String,
+            String,
+            HashMap<String, serde_json::Value>,
+            f64,
+            bool,
+            Option<String>,
+            Option<i32>,
+        )>,
+    > {
+        let indexes = self.metadata_indexes(db_id, table_name).await?;
+        let index_key = format!("{}:{}", db_id, table_name);
+
+        // Load saved index, or build it if missing or stale
+        let index = match self.loaded_index(&index_key) {
+            Some(index) => index,
+            None => self.load_or_build_index(db_id, table_name).await?,
+        };
+
+        let (filter_sql, filter_params) = filter.to_sql(table_name, "d", &indexes);
+        let query = format!(
+            r#"
+            SELECT d.id, d.content, d.metadata, d.is_chunk, d.parent_id, d.chunk_index FROM "{}" AS d
+            WHERE d.id = ? AND {}{}
+        "#,
+            table_name, LIVE_ROWS, filter_sql
+        );
+
+        let pool = self.get_pool(db_id).await?;
+        let mut results = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut k = if filter.is_empty() {
+            limit
+        } else {
+            limit * FILTER_OVERFETCH_FACTOR
+        };
+
+        loop {
+            let neighbors = index.search(query_vector, k)?;
+            let exhausted = neighbors.len() < k || k >= index.len();
+
+            // Fetch document details
+            for (doc_id, similarity) in neighbors {
+                if results.len() >= limit {
+                    break;
+                }
+                if !seen.insert(doc_id.clone()) {
+                    continue;
+                }
+
+                let row = bind_params(sqlx::query(&query).bind(&doc_id), &filter_params)
+                    .fetch_optional(&pool)
+                    .await?;
+
+                if let Some(row) = row {
+                    let content: String = row.get("content");
+                    let metadata_json: String = row.get("metadata");
+                    let metadata: HashMap<String, serde_json::Value> =
+                        serde_json::from_str(&metadata_json).unwrap_or_default();
+                    let is_chunk: i32 = row.get("is_chunk");
+                    let parent_id: Option<String> = row.get("parent_id");
+                    let chunk_index: Option<i32> = row.get("chunk_index");
+
+                    results.push((
+                        doc_id,
+                        content,
+                        metadata,
+                        similarity as f64,
+                        is_chunk == 1,
+                        parent_id,
+                        chunk_index,
+                    ));
+                }
+            }
+
+            if results.len() >= limit || exhausted {
+                break;
+            }
+            k *= 2;
+        }
+
+        // Neighbors from a widened fetch may interleave with earlier ones
+        results.sort_by(|a, b| b.3.total_cmp(&a.3));
+
+        tracing::debug!("HNSW search returned {} results", results.len());
+        Ok(results)
+    }
+
+    /// Brute-force vector search
+    async fn search_vector_brute_force(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        query_vector: &[f32],
+        limit: usize,
+        filter: &SearchFilter,
+    ) -> Result<
+        Vec<(
+            String,
+            String,
+            HashMap<String, serde_json::Value>,
+            f64,
+            bool,
+            Option<String>,
+            Option<i32>,
+        )>,
+    > {
+        let indexes = self.metadata_indexes(db_id, table_name).await?;
+        let pool = self.get_pool(db_id).await?;
+
+        let (filter_sql, filter_params) = filter.to_sql(table_name, "d", &indexes);
+        let sql = format!(
+            r#"
+            SELECT d.id, d.content, d.metadata, d.vector, d.is_chunk, d.parent_id, d.chunk_index
+            FROM "{}" AS d
+            WHERE d.is_embedded = 1 AND d.vector IS NOT NULL AND {}{}
+        "#,
+            table_name, LIVE_ROWS, filter_sql
+        );
+
+        let rows = bind_params(sqlx::query(&sql), &filter_params)
+            .fetch_all(&pool)
+            .await?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let id: String = row.get("id");
+            let content: String = row.get("content");
+            let metadata_json: String = row.get("metadata");
+            let vector_bytes: Vec<u8> = row.get("vector");
+            let is_chunk: i32 = row.get("is_chunk");
+            let parent_id: Option<String> = row.get("parent_id");
+            let chunk_index: Option<i32> = row.get("chunk_index");
+
+            let metadata: HashMap<String, serde_json::Value> =
+                serde_json::from_str(&metadata_json).unwrap_or_default();
+
+            // Deserialize vector
+            let doc_vector = deserialize_vector(&vector_bytes);
+
+            // Calculate cosine similarity
+            let similarity = cosine_similarity(query_vector, &doc_vector);
+
+            results.push((
+                id,
+                content,
+                metadata,
+                similarity,
+                is_chunk == 1,
+                parent_id,
+                chunk_index,
+            ));
+        }
+
+        // Sort by similarity (descending)
+        results.sort_by(|a, b| b.3.partial_cmp(&a.3).unwrap());
+
+        // Return top results
+        results.truncate(limit);
+
+        Ok(results)
+    }
+
+    // ===== Document Relations Methods =====
+
+    /// Create a document relation
+    pub async fn create_relation(
+        &self,
+        db_id: &str,
+        relation: crate::models::DocumentRelation,
+    ) -> Result<()> {
+        let pool = self.get_pool(db_id).await?;
+
+        let metadata_json = serde_json::to_string(&relation.metadata)?;
+
+        sqlx::query(r#"
+            INSERT INTO document_relations (id, source_id, target_id, relation_type, metadata, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+        "#)
+        .bind(&relation.id)
+        .bind(&relation.source_id)
+        .bind(&relation.target_id)
+        .bind(&relation.relation_type)
+        .bind(&metadata_json)
+        .bind(relation.created_at)
+        .execute(&pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get relation by ID
+    pub async fn get_relation(
+        &self,
+        db_id: &str,
+        relation_id: &str,
+    ) -> Result<crate::models::DocumentRelation> {
+        let pool = self.get_pool(db_id).await?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT id, source_id, target_id, relation_type, metadata, created_at
+            FROM document_relations
+            WHERE id = ?
+        "#,
+        )
+        .bind(relation_id)
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(row_to_relation(&row))
+    }
+
+    /// Delete a relation
+    pub async fn delete_relation(&self, db_id: &str, relation_id: &str) -> Result<()> {
+        let pool = self.get_pool(db_id).await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM document_relations WHERE id = ?
+        "#,
+        )
+        .bind(relation_id)
+        .execute(&pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get all relations for a document
+    pub async fn get_document_relations(
+        &self,
+        db_id: &str,
+        doc_id: &str,
+    ) -> Result<Vec<crate::models::DocumentRelation>> {
+        let pool = self.get_pool(db_id).await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, source_id, target_id, relation_type, metadata, created_at
+            FROM document_relations
+            WHERE source_id = ? OR target_id = ?
+        "#,
+        )
+        .bind(doc_id)
+        .bind(doc_id)
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(rows.iter().map(row_to_relation).collect())
+    }
+
+    /// Get all relations in database (for graph operations)
+    pub async fn get_all_relations(
+        &self,
+        db_id: &str,
+    ) -> Result<Vec<crate::models::DocumentRelation>> {
+        let pool = self.get_pool(db_id).await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, source_id, target_id, relation_type, metadata, created_at
+            FROM document_relations
+        "#,
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(rows.iter().map(row_to_relation).collect())
+    }
+
+    // ===== Change Log =====
+
+    /// Changes logged after sequence number `since`, oldest first
+    ///
+    /// With `table_name`, only changes to that table's documents are returned.
+    pub async fn changes_since(
+        &self,
+        db_id: &str,
+        since: i64,
+        table_name: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        let pool = self.get_pool(db_id).await?;
+
+        let table_clause = if table_name.is_some() {
+            "AND table_name = ?"
+        } else {
+            ""
+        };
+        let query = format!(
+            r#"
+            SELECT seq, table_name, item_id, parent_id, kind, op, recorded_at
+            FROM document_changes
+            WHERE seq > ? {}
+            ORDER BY seq ASC
+            LIMIT ?
+        "#,
+            table_clause
+        );
+
+        let mut sql = sqlx::query(&query).bind(since);
+        if let Some(table_name) = table_name {
+            sql = sql.bind(table_name);
+        }
+        let rows = sql.bind(limit as i64).fetch_all(&pool).await?;
+
+        rows.iter().map(row_to_change).collect()
+    }
+
+    /// Sequence number of the latest change logged in a database (0 if none)
+    pub async fn last_change_seq(&self, db_id: &str) -> Result<i64> {
+        let pool = self.get_pool(db_id).await?;
+
+        let seq: Option<i64> =
+            sqlx::query_scalar("SELECT seq FROM sqlite_sequence WHERE name = 'document_changes'")
+                .fetch_optional(&pool)
+                .await?;
+        Ok(seq.unwrap_or(0))
+    }
+
+    /// Whether every change after `since` is still in the log
+    ///
+    /// Sequence numbers have no gaps, so changes are missing only if the oldest
+    /// retained one is further on, or if everything up to the latest was pruned.
+    pub async fn changes_retained_since(&self, db_id: &str, since: i64) -> Result<bool> {
+        let pool = self.get_pool(db_id).await?;
+
+        let oldest: Option<i64> = sqlx::query_scalar("SELECT MIN(seq) FROM document_changes")
+            .fetch_one(&pool)
+            .await?;
+        match oldest {
+            Some(oldest) => Ok(oldest <= since + 1),
+            None => Ok(self.last_change_seq(db_id).await? <= since),
+        }
+    }
+
+    /// Attach the current version of each changed document or relation
+    ///
+    /// Deleted items, and items deleted since the change, are left out.
+    pub async fn attach_changed_items(
+        &self,
+        db_id: &str,
+        changes: &mut [Change],
+    ) -> Result<()> {
+        for change in changes.iter_mut() {
+            if change.op == ChangeOp::Delete {
+                continue;
+            }
+            match (change.kind, change.table.as_deref()) {
+                (ChangeKind::Document, Some(table_name)) => {
+                    change.document = self.get_document(db_id, table_name, &change.id).await.ok();
+                }
+                (ChangeKind::Relation, _) => {
+                    change.relation = self.get_relation(db_id, &change.id).await.ok();
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete change log entries recorded before `cutoff`, returning how many
+    pub async fn prune_changes(&self, db_id: &str, cutoff: DateTime<Utc>) -> Result<usize> {
+        let pool = self.get_pool(db_id).await?;
+
+        let result = sqlx::query("DELETE FROM document_changes WHERE recorded_at < ?")
+            .bind(change_time(cutoff))
+            .execute(&pool)
+            .await?;
+        Ok(result.rows_affected() as usize)
+    }
+
+    // ===== Export / Import =====
+
+    /// One page of a table export: live documents, roots before chunks, ordered by
+    /// `(is_chunk, id)` and starting after the last position of the previous page
+    pub async fn export_documents(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        after: Option<(bool, &str)>,
+        limit: usize,
+    ) -> Result<Vec<ExportRecord>> {
+        let pool = self.get_pool(db_id).await?;
+
+        let position = if after.is_some() {
+            "AND (is_chunk, id) > (?, ?)"
+        } else {
+            ""
+        };
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM "{}"
+            WHERE {} {}
+            ORDER BY is_chunk ASC, id ASC
+            LIMIT ?
+        "#,
+            DOCUMENT_COLUMNS, table_name, LIVE_ROWS, position
+        );
+
+        let mut sql = sqlx::query(&query);
+        if let Some((is_chunk, id)) = after {
+            sql = sql.bind(if is_chunk { 1 } else { 0 }).bind(id);
+        }
+        let rows = sql.bind(limit as i64).fetch_all(&pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let document = row_to_document(row, db_id, table_name);
+                ExportRecord::Document(ExportedDocument {
+                    vector: document.vector.as_deref().map(encode_vector),
+                    document,
+                })
+            })
+            .collect())
+    }
+
+    /// Relations with a live document of the table at either end, for an export
+    pub async fn export_relations(
+        &self,
+        db_id: &str,
+        table_name: &str,
+    ) -> Result<Vec<ExportRecord>> {
+        self.create_relations_table(db_id).await?;
+        let pool = self.get_pool(db_id).await?;
+
+        let query = format!(
+            r#"
+            SELECT id, source_id, target_id, relation_type, metadata, created_at
+            FROM document_relations
+            WHERE source_id IN (SELECT id FROM "{0}" WHERE {1})
+               OR target_id IN (SELECT id FROM "{0}" WHERE {1})
+            ORDER BY created_at ASC, id ASC
+        "#,
+            table_name, LIVE_ROWS
+        );
+
+        let rows = sqlx::query(&query).fetch_all(&pool).await?;
+        Ok(rows
+            .iter()
+            .map(|row| ExportRecord::Relation(row_to_relation(row)))
+            .collect())
+    }
+
+    /// Write documents and relations read from an export in one transaction
+    ///
+    /// IDs, timestamps, chunk parentage and vectors are kept as exported, so
+    /// nothing is chunked or embedded again. Items are written in order, so chunks
+    /// must come after their parent. Failing items are handled per `mode` as in
+    /// [`DocumentStore::store_documents`].
+    pub async fn import_records(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        items: Vec<ImportItem>,
+        mode: BulkMode,
+    ) -> Result<BatchWriteResult> {
+        let documents: Vec<&Document> = items
+            .iter()
+            .filter_map(|item| match item {
+                ImportItem::Document(doc) => Some(doc),
+                _ => None,
+            })
+            .collect();
+        self.check_write_quota(db_id, table_name, &documents).await?;
+        self.ensure_table(db_id, table_name).await?;
+        let history = self.table_settings(db_id, table_name).await?.history;
+        let pool = self.get_pool(db_id).await?;
+
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+        self.check_storage_quota(&mut tx, db_id, table_name, &documents).await?;
+        let mut errors = vec![None; items.len()];
+
+        for (position, item) in items.iter().enumerate() {
+            let written = match mode {
+                BulkMode::Atomic => write_import_item(&mut tx, table_name, item, history).await,
+                BulkMode::BestEffort => {
+                    let mut savepoint = tx.begin().await?;
+                    let written =
+                        write_import_item(&mut savepoint, table_name, item, history).await;
+                    if written.is_ok() {
+                        savepoint.commit().await?;
+                    } else {
+                        savepoint.rollback().await?;
+                    }
+                    written
+                }
+            };
+
+            if let Err(e) = written {
+                errors[position] = Some(e.to_string());
+                if mode == BulkMode::Atomic {
+                    tx.rollback().await?;
+                    return Ok(BatchWriteResult {
+                        committed: false,
+                        errors,
+                    });
+                }
+            }
+        }
+
+        tx.commit().await?;
+
+        // Keep a loaded index in sync with the committed vectors
+        for (item, error) in items.iter().zip(&errors) {
+            let ImportItem::Document(doc) = item else {
+                continue;
+            };
+            if error.is_some() {
+                continue;
+            }
+            match doc.vector {
+                Some(ref vector) => self.index_vector(db_id, table_name, &doc.id, vector)?,
+                None => self.remove_from_index(db_id, table_name, std::slice::from_ref(&doc.id)),
+            }
+        }
+
+        Ok(BatchWriteResult {
+            committed: true,
+            errors,
+        })
+    }
+
+    // ===== Duplicate Detection =====
+
+    /// Find a live root document that `doc` duplicates, by the table's dedup
+    /// settings
+    ///
+    /// An exact duplicate has the same content. Near-duplicates are found by the
+    /// embedding `doc` is written with, if any, or by MinHash of its content.
+    /// The oldest match wins, and a stored document never duplicates itself.
+    pub async fn find_duplicate(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        doc: &Document,
+        settings: &DedupSettings,
+    ) -> Result<Option<Duplicate>> {
+        if !self.table_exists(db_id, table_name).await? {
+            return Ok(None);
+        }
+        let pool = self.get_pool(db_id).await?;
+
+        let exact_query = format!(
+            r#"
+            SELECT d.id FROM "{0}_dedup" k JOIN "{0}" d ON d.id = k.doc_id
+            WHERE k.content_hash = ? AND d.id <> ? AND d.is_chunk = 0 AND {1}
+            ORDER BY d.created_at, d.id
+            LIMIT 1
+        "#,
+            table_name, LIVE_ROWS
+        );
+        let exact: Option<String> = sqlx::query_scalar(&exact_query)
+            .bind(hash_content(&doc.content))
+            .bind(&doc.id)
+            .fetch_optional(&pool)
+            .await?;
+        if let Some(id) = exact {
+            return Ok(Some(Duplicate {
+                id,
+                kind: DuplicateKind::Exact,
+                similarity: 1.0,
+            }));
+        }
+
+        let Some(method) = settings.near else {
+            return Ok(None);
+        };
+        let nearest = match method {
+            NearDuplicateMethod::Vector => {
+                let Some(ref vector) = doc.vector else {
+                    return Ok(None);
+                };
+                let filter = SearchFilter::default().roots_only();
+                self.search_vector_filtered(db_id, table_name, vector, 2, &filter)
+                    .await?
+                    .into_iter()
+                    .map(|(id, _, _, similarity, ..)| (id, similarity))
+                    .find(|(id, _)| *id != doc.id)
+            }
+            NearDuplicateMethod::Minhash => {
+                let signature = minhash_signature(&doc.content);
+                let query = format!(
+                    r#"
+                    SELECT d.id, k.minhash FROM "{0}_dedup" k JOIN "{0}" d ON d.id = k.doc_id
+                    WHERE d.id <> ? AND d.is_chunk = 0 AND {1}
+                    ORDER BY d.created_at, d.id
+                "#,
+                    table_name, LIVE_ROWS
+                );
+                let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(&query)
+                    .bind(&doc.id)
+                    .fetch_all(&pool)
+                    .await?;
+                rows.into_iter()
+                    .map(|(id, minhash)| {
+                        let similarity =
+                            minhash_similarity(&signature, &deserialize_signature(&minhash));
+                        (id, similarity)
+                    })
+                    .fold(None, |best: Option<(String, f64)>, (id, similarity)| match best {
+                        Some(best) if best.1 >= similarity => Some(best),
+                        _ => Some((id, similarity)),
+                    })
+            }
+        };
+
+        let threshold = settings.threshold_for(method);
+        Ok(nearest
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .map(|(id, similarity)| Duplicate {
+                id,
+                kind: DuplicateKind::Near,
+                similarity,
+            }))
+    }
+
+    /// Merge a duplicate into the existing document it duplicates
+    ///
+    /// The duplicate's tags are added, and its metadata keys that the existing
+    /// document lacks. Its content is dropped.
+    pub async fn merge_duplicate(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        existing_id: &str,
+        duplicate: &Document,
+    ) -> Result<Document> {
+        let existing = self.get_document(db_id, table_name, existing_id).await?;
+        let metadata: serde_json::Map<String, serde_json::Value> = duplicate
+            .metadata
+            .iter()
+            .filter(|(key, _)| !existing.metadata.contains_key(*key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        let patch = PatchDocumentRequest {
+            metadata: (!metadata.is_empty()).then_some(serde_json::Value::Object(metadata)),
+            add_tags: duplicate.tags.clone(),
+            ..Default::default()
+        };
+        Ok(self
+            .patch_document(db_id, table_name, existing_id, &patch)
+            .await?
+            .document)
+    }
+
+    /// Relate a written document to the existing one it duplicates, by a
+    /// `duplicate_of` relation carrying how it was found
+    pub async fn link_duplicate(
+        &self,
+        db_id: &str,
+        doc_id: &str,
+        duplicate: &Duplicate,
+    ) -> Result<DocumentRelation> {
+        let relation = DocumentRelation {
+            id: uuid::Uuid::new_v4().to_string(),
+            source_id: doc_id.to_string(),
+            target_id: duplicate.id.clone(),
+            relation_type: "duplicate_of".to_string(),
+            metadata: HashMap::from([
+                ("kind".to_string(), serde_json::json!(duplicate.kind)),
+                ("similarity".to_string(), serde_json::json!(duplicate.similarity)),
+            ]),
+            created_at: Utc::now(),
+        };
+        self.create_relation(db_id, relation.clone()).await?;
+        Ok(relation)
+    }
+
+    /// Clusters of live root documents in a table that duplicate each other
+    ///
+    /// Exact duplicates are always clustered. `query` adds near-duplicates, by
+    /// default as the table's dedup settings find them. At most `query.limit`
+    /// clusters are listed, oldest first; `duplicate_count` counts them all.
+    pub async fn duplicate_report(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        query: &DuplicatesQuery,
+    ) -> Result<DuplicateReport> {
+        let settings = self.table_settings(db_id, table_name).await?.dedup;
+        let near = query.near.or(settings.near);
+        let threshold =
+            near.map(|method| query.threshold.unwrap_or(settings.threshold_for(method)));
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let mut report = DuplicateReport {
+            db: db_id.to_string(),
+            table: table_name.to_string(),
+            near,
+            threshold,
+            duplicate_count: 0,
+            clusters: Vec::new(),
+            truncated: false,
+        };
+        if !self.table_exists(db_id, table_name).await? {
+            return Ok(report);
+        }
+
+        let pool = self.get_pool(db_id).await?;
+        let query = format!(
+            r#"
+            SELECT d.id, k.content_hash, k.minhash FROM "{0}" d
+            JOIN "{0}_dedup" k ON k.doc_id = d.id
+            WHERE d.is_chunk = 0 AND {1}
+            ORDER BY d.created_at, d.id
+        "#,
+            table_name, LIVE_ROWS
+        );
+        let rows: Vec<(String, String, Vec<u8>)> = sqlx::query_as(&query).fetch_all(&pool).await?;
+        let mut clusters = Clusters::new(rows.len());
+
+        let mut first_by_hash: HashMap<&str, usize> = HashMap::new();
+        for (position, (_, content_hash, _)) in rows.iter().enumerate() {
+            let first = *first_by_hash.entry(content_hash).or_insert(position);
+            clusters.join(first, position);
+        }
+
+        match (near, threshold) {
+            (Some(NearDuplicateMethod::Minhash), Some(threshold)) => {
+                let signatures: Vec<Vec<u32>> = rows
+                    .iter()
+                    .map(|(_, _, minhash)| deserialize_signature(minhash))
+                    .collect();
+                for (a, b, _) in similar_pairs(&signatures, threshold) {
+                    clusters.join(a, b);
+                }
+            }
+            (Some(NearDuplicateMethod::Vector), Some(threshold)) => {
+                let ids: Vec<&str> = rows.iter().map(|(id, _, _)| id.as_str()).collect();
+                let (pairs, complete) = self
+                    .similar_vector_pairs(db_id, table_name, &ids, threshold)
+                    .await?;
+                for (a, b) in pairs {
+                    clusters.join(a, b);
+                }
+                report.truncated |= !complete;
+            }
+            _ => {}
+        }
+
+        for group in clusters.groups() {
+            report.duplicate_count += group.len() - 1;
+            if report.clusters.len() == limit {
+                report.truncated = true;
+                continue;
+            }
+
+            let exact = group.iter().all(|&position| rows[position].1 == rows[group[0]].1);
+            report.clusters.push(DuplicateCluster {
+                kind: if exact {
+                    DuplicateKind::Exact
+                } else {
+                    DuplicateKind::Near
+                },
+                ids: group.iter().map(|&position| rows[position].0.clone()).collect(),
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Pairs of documents, by position in `ids`, whose embeddings are at least
+    /// `threshold` similar, and whether every embedded document was compared
+    ///
+    /// Tables searched through their HNSW index are paired with each document's
+    /// nearest neighbours from it. Smaller tables are compared pairwise in memory,
+    /// up to [`MAX_PAIRWISE_VECTORS`] oldest documents.
+    async fn similar_vector_pairs(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        ids: &[&str],
+        threshold: f64,
+    ) -> Result<(Vec<(usize, usize)>, bool)> {
+        let positions: HashMap<&str, usize> = ids
+            .iter()
+            .enumerate()
+            .map(|(position, id)| (*id, position))
+            .collect();
+
+        let pool = self.get_pool(db_id).await?;
+        let query = format!(
+            r#"
+            SELECT id, vector FROM "{}"
+            WHERE is_chunk = 0 AND vector IS NOT NULL AND {}
+            ORDER BY created_at, id
+        "#,
+            table_name, LIVE_ROWS
+        );
+        let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(&query).fetch_all(&pool).await?;
+        let vectors: Vec<(usize, Vec<f32>)> = rows
+            .iter()
+            .filter_map(|(id, vector)| {
+                let position = *positions.get(id.as_str())?;
+                Some((position, deserialize_vector(vector)))
+            })
+            .collect();
+
+        if self.should_use_index(db_id, table_name).await? {
+            let index_key = format!("{}:{}", db_id, table_name);
+            let index = match self.loaded_index(&index_key) {
+                Some(index) => index,
+                None => self.load_or_build_index(db_id, table_name).await?,
+            };
+
+            let mut pairs = Vec::new();
+            for (position, vector) in &vectors {
+                for (other, similarity) in index.search(vector, DUPLICATE_NEIGHBOURS)? {
+                    // Chunks and deleted documents have no position
+                    let Some(&other) = positions.get(other.as_str()) else {
+                        continue;
+                    };
+                    if other != *position && similarity as f64 >= threshold {
+                        pairs.push((*position, other));
+                    }
+                }
+            }
+            return Ok((pairs, true));
+        }
+
+        let complete = vectors.len() <= MAX_PAIRWISE_VECTORS;
+        let (positions, vectors): (Vec<usize>, Vec<Vec<f32>>) =
+            vectors.into_iter().take(MAX_PAIRWISE_VECTORS).unzip();
+        let pairs = similar_vectors(&vectors, threshold)
+            .into_iter()
+            .map(|(a, b, _)| (positions[a], positions[b]))
+            .collect();
+        Ok((pairs, complete))
+    }
+
+    // ===== Chunking Methods =====
+
+    /// Get all chunks for a parent document
+    pub async fn get_chunks(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        parent_id: &str,
+    ) -> Result<Vec<Document>> {
+        let pool = self.get_pool(db_id).await?;
+
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM "{}"
+            WHERE parent_id = ? AND is_chunk = 1 AND {}
+            ORDER BY chunk_index ASC
+        "#,
+            DOCUMENT_COLUMNS, table_name, LIVE_ROWS
+        );
+
+        let rows = sqlx::query(&query).bind(parent_id).fetch_all(&pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| row_to_document(row, db_id, table_name))
+            .collect())
+    }
+
+    /// Vector representing a document, for "more like this" searches
+    ///
+    /// Uses the document's own embedding, or the mean of its chunks' embeddings
+    /// for chunked documents (whose parents are not embedded). Returns `None` if
+    /// neither is available yet.
+    pub async fn document_vector(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        doc: &Document,
+    ) -> Result<Option<Vec<f32>>> {
+        if let Some(ref vector) = doc.vector {
+            return Ok(Some(vector.clone()));
+        }
+
+        let chunk_vectors: Vec<Vec<f32>> = self
+            .get_chunks(db_id, table_name, &doc.id)
+            .await?
+            .into_iter()
+            .filter_map(|chunk| chunk.vector)
+            .collect();
+
+        let Some(dimensions) = chunk_vectors.first().map(|v| v.len()) else {
+            return Ok(None);
+        };
+
+        let mut mean = vec![0.0f32; dimensions];
+        let mut count = 0;
+        for vector in chunk_vectors.iter().filter(|v| v.len() == dimensions) {
+            for (sum, value) in mean.iter_mut().zip(vector) {
+                *sum += value;
+            }
+            count += 1;
+        }
+        mean.iter_mut().for_each(|v| *v /= count as f32);
+
+        Ok(Some(mean))
+    }
+
+    /// Delete all chunks for a parent document
+    pub async fn delete_chunks(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        parent_id: &str,
+    ) -> Result<()> {
+        let pool = self.get_pool(db_id).await?;
+
+        let query = format!(
+            r#"
+            DELETE FROM "{}"
+            WHERE parent_id = ? AND is_chunk = 1
+            RETURNING id
+        "#,
+            table_name
+        );
+
+        let deleted: Vec<String> = sqlx::query_scalar(&query)
+            .bind(parent_id)
+            .fetch_all(&pool)
+            .await?;
+
+        self.remove_from_index(db_id, table_name, &deleted);
+
+        Ok(())
+    }
+
+    /// Convenience method to add a document from a StoreDocumentRequest
+    /// This provides a cleaner API for adding documents without manually constructing Document structs
+    pub async fn add_document(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        request: crate::models::StoreDocumentRequest,
+    ) -> Result<Document> {
+        let doc = Document::from_request(db_id, table_name, request);
+
+        self.store_document(db_id, table_name, doc.clone()).await?;
+        Ok(doc)
+    }
+
+    /// Convenience method to add a simple document with just content
+    pub async fn add_simple_document(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        content: impl Into<String>,
+    ) -> Result<Document> {
+        let request = crate::models::StoreDocumentRequest {
+            id: None,
+            content: content.into(),
+            metadata: std::collections::HashMap::new(),
+            tags: Vec::new(),
+            vectorize: true,
+            vector: None,
+            model: None,
+            expires_at: None,
+            ttl_seconds: None,
+        };
+        self.add_document(db_id, table_name, request).await
+    }
+
+    /// Convenience method to delete a document by ID
+    ///
+    /// The document and its chunks are moved to the trash: they are hidden from
+    /// reads and searches until restored with [`Self::undelete_document`], or
+    /// removed for good by [`Self::purge_deleted`].
+    pub async fn delete_document_by_id(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        doc_id: &str,
+    ) -> Result<()> {
+        let history = self.table_settings(db_id, table_name).await?.history;
+        let pool = self.get_pool(db_id).await?;
+
+        // First, check if this is a child document (has parent_id)
+        let check_query = format!(
+            r#"SELECT parent_id FROM "{}" WHERE id = ? AND deleted_at IS NULL"#,
+            table_name
+        );
+
+        let result: Option<(Option<String>,)> = sqlx::query_as(&check_query)
+            .bind(doc_id)
+            .fetch_optional(&pool)
+            .await?;
+
+        if let Some((Some(_parent_id),)) = result {
+            return Err(anyhow::anyhow!(
+                "Cannot delete child document. Delete the parent document instead, which will cascade delete all children."
+            ));
+        }
+
+        // Document is a parent or standalone - trash it with its children
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+        let trash = format!(
+            r#"UPDATE "{}" SET deleted_at = ? WHERE (id = ? OR parent_id = ?) AND deleted_at IS NULL RETURNING id"#,
+            table_name
+        );
+        let deleted: Vec<String> = sqlx::query_scalar(&trash)
+            .bind(Utc::now())
+            .bind(doc_id)
+            .bind(doc_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        if history && result.is_some() {
+            record_deletion(&mut tx, table_name, doc_id).await?;
+        }
+        tx.commit().await?;
+
+        self.remove_from_index(db_id, table_name, &deleted);
+
+        Ok(())
+    }
+
+    /// List the most recently deleted documents in a table's trash
+    pub async fn list_trash(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<TrashedDocument>> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let pool = self.get_pool(db_id).await?;
+
+        let query = format!(
+            r#"
+            SELECT {}, deleted_at
+            FROM "{}"
+            WHERE deleted_at IS NOT NULL AND (parent_id IS NULL OR parent_id = '')
+            ORDER BY deleted_at DESC, id ASC
+            LIMIT ?
+        "#,
+            document_columns(true, false),
+            table_name
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(limit as i64)
+            .fetch_all(&pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| TrashedDocument {
+                document: row_to_document(row, db_id, table_name),
+                deleted_at: row.get("deleted_at"),
+            })
+            .collect())
+    }
+
+    /// Restore a deleted document and its chunks from the trash
+    ///
+    /// Returns `None` if the document is not in the trash.
+    pub async fn undelete_document(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        doc_id: &str,
+    ) -> Result<Option<Document>> {
+        let history = self.table_settings(db_id, table_name).await?.history;
+        let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+
+        let restore = format!(
+            r#"
+            UPDATE "{t}" SET deleted_at = NULL
+            WHERE (id = ? OR parent_id = ?) AND deleted_at IS NOT NULL
+              AND EXISTS (
+                  SELECT 1 FROM "{t}" root
+                  WHERE root.id = ? AND root.deleted_at IS NOT NULL
+                    AND (root.parent_id IS NULL OR root.parent_id = '')
+              )
+            RETURNING id
+        "#,
+            t = table_name
+        );
+        let restored: Vec<String> = sqlx::query_scalar(&restore)
+            .bind(doc_id)
+            .bind(doc_id)
+            .bind(doc_id)
+            .fetch_all(&mut *tx)
+            .await?;
+        if restored.is_empty() {
+            // Release the write lock now rather than when the dropped
+            // transaction is rolled back on the connection's next use
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        let query = format!(
+            r#"SELECT {} FROM "{}" WHERE id = ?"#,
+            DOCUMENT_COLUMNS, table_name
+        );
+        let row = sqlx::query(&query).bind(doc_id).fetch_one(&mut *tx).await?;
+        let doc = row_to_document(&row, db_id, table_name);
+
+        if history {
+            record_revision(&mut tx, table_name, &doc).await?;
+        }
+        tx.commit().await?;
+
+        // Put the restored vectors back into a loaded index
+        if self
+            .loaded_index(&format!("{}:{}", db_id, table_name))
+            .is_some()
+        {
+            let pool = self.get_pool(db_id).await?;
+            let vectors_query = format!(
+                r#"SELECT id, vector FROM "{}" WHERE (id = ? OR parent_id = ?) AND vector IS NOT NULL"#,
+                table_name
+            );
+            let rows = sqlx::query(&vectors_query)
+                .bind(doc_id)
+                .bind(doc_id)
+                .fetch_all(&pool)
+                .await?;
+            for row in rows {
+                let id: String = row.get("id");
+                let vector_bytes: Vec<u8> = row.get("vector");
+                self.index_vector(db_id, table_name, &id, &deserialize_vector(&vector_bytes))?;
+            }
+        }
+
+        Ok(Some(doc))
+    }
+
+    /// Permanently delete documents trashed before `deleted_before`, with their
+    /// chunks, relations and index entries. Returns the number of rows removed.
+    pub async fn purge_deleted(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<usize> {
+        let purged = self
+            .delete_rows_where(db_id, table_name, "deleted_at < ?", deleted_before)
+            .await?;
+        if purged > 0 {
+            tracing::info!(
+                "Purged {} deleted documents from {}.{}",
+                purged,
+                db_id,
+                table_name
+            );
+        }
+
+        Ok(purged)
+    }
+
+    /// Delete documents whose expiry time has passed, with their chunks,
+    /// relations and index entries. Returns the number of rows removed.
+    pub async fn reap_expired(&self, db_id: &str, table_name: &str) -> Result<usize> {
+        let reaped = self
+            .delete_rows_where(db_id, table_name, "expires_at <= ?", Utc::now())
+            .await?;
+        if reaped > 0 {
+            tracing::info!(
+                "Reaped {} expired documents from {}.{}",
+                reaped,
+                db_id,
+                table_name
+            );
+        }
+
+        Ok(reaped)
+    }
+
+    /// Delete the rows matching `condition` (comparing a timestamp column with
+    /// `cutoff`) together with their chunks, relations and index entries, and
+    /// record the deletion of root documents that were still live in their history
+    async fn delete_rows_where(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        condition: &str,
+        cutoff: DateTime<Utc>,
+    ) -> Result<usize> {
+        // Makes sure older tables have the indexes and relations table
+        self.ensure_table(db_id, table_name).await?;
+        let history = self.table_settings(db_id, table_name).await?.history;
+        let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+
+        // Chunks go first: rows removed by the cascade would not be returned
+        let delete_chunks = format!(
+            r#"
+            DELETE FROM "{t}"
+            WHERE parent_id IN (SELECT id FROM "{t}" WHERE {c})
+            RETURNING id
+        "#,
+            t = table_name,
+            c = condition
+        );
+        let mut deleted: Vec<String> = sqlx::query_scalar(&delete_chunks)
+            .bind(cutoff)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let delete = format!(
+            r#"DELETE FROM "{}" WHERE {} RETURNING id, is_chunk, deleted_at"#,
+            table_name, condition
+        );
+        let rows = sqlx::query(&delete)
+            .bind(cutoff)
+            .fetch_all(&mut *tx)
+            .await?;
+        if deleted.is_empty() && rows.is_empty() {
+            // Release the write lock now rather than when the dropped
+            // transaction is rolled back on the connection's next use
+            tx.rollback().await?;
+            return Ok(0);
+        }
+
+        for row in &rows {
+            let id: String = row.get("id");
+            let is_chunk: i64 = row.get("is_chunk");
+            let deleted_at: Option<DateTime<Utc>> = row.get("deleted_at");
+            // Trashed documents already have their deletion recorded
+            if history && is_chunk == 0 && deleted_at.is_none() {
+                record_deletion(&mut tx, table_name, &id).await?;
+            }
+            deleted.push(id);
+        }
+
+        let ids = serde_json::to_string(&deleted)?;
+        sqlx::query(
+            r#"
+            DELETE FROM document_relations
+            WHERE source_id IN (SELECT value FROM json_each(?))
+               OR target_id IN (SELECT value FROM json_each(?))
+        "#,
+        )
+        .bind(&ids)
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        self.remove_from_index(db_id, table_name, &deleted);
+
+        Ok(deleted.len())
+    }
+
+    /// Move a table to the trash
+    ///
+    /// The table and its history are renamed under a trash name, and its
+    /// settings and metadata indexes are kept under that name, so it can be
+    /// restored with [`Self::undelete_table`] until [`Self::purge_deleted_tables`]
+    /// drops it. Its full-text, tag and duplicate detection tables, triggers and
+    /// indexes are dropped and rebuilt on restore, which frees the name for a
+    /// new table meanwhile.
+    pub async fn delete_table(&self, db_id: &str, table_name: &str) -> Result<()> {
+        if !is_valid_table_name(table_name) {
+            anyhow::bail!("Invalid table name: {}", table_name);
+        }
+
+        if self.table_exists(db_id, table_name).await? {
+            self.create_settings_table(db_id).await?;
+            self.create_metadata_indexes_table(db_id).await?;
+            let deleted_at = Utc::now();
+            let trash_name = format!(
+                "{}{}${}",
+                TABLE_TRASH_PREFIX,
+                deleted_at.timestamp_millis(),
+                table_name
+            );
+
+            let pool = self.get_pool(db_id).await?;
+            let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+
+            // Renaming a table fires no triggers, so its documents are logged as deleted
+            let log_deletes = format!(
+                r#"
+                INSERT INTO document_changes
+                    (table_name, item_id, parent_id, kind, op, recorded_at)
+                SELECT '{0}', id, parent_id, 'document', 'delete', {1}
+                FROM "{0}" WHERE deleted_at IS NULL
+            "#,
+                table_name, CHANGE_TIME
+            );
+            sqlx::query(&log_deletes).execute(&mut *tx).await?;
+
+            // Triggers and indexes keep their names when their table is renamed,
+            // and would clash with those of a new table of the same name
+            let history_table = format!("{}_history", table_name);
+            let dependents: Vec<(String, String)> = sqlx::query_as(
+                r#"
+                SELECT type, name FROM sqlite_master
+                WHERE type IN ('trigger', 'index') AND tbl_name IN (?, ?) AND sql IS NOT NULL
+            "#,
+            )
+            .bind(table_name)
+            .bind(&history_table)
+            .fetch_all(&mut *tx)
+            .await?;
+            for (kind, name) in dependents {
+                let drop = format!(r#"DROP {} IF EXISTS "{}""#, kind.to_uppercase(), name);
+                sqlx::query(&drop).execute(&mut *tx).await?;
+            }
+            for side_table in ["fts", "tags", "dedup"] {
+                let drop = format!(r#"DROP TABLE IF EXISTS "{}_{}""#, table_name, side_table);
+                sqlx::query(&drop).execute(&mut *tx).await?;
+            }
+
+            let rename = format!(r#"ALTER TABLE "{}" RENAME TO "{}""#, table_name, trash_name);
+            sqlx::query(&rename).execute(&mut *tx).await?;
+            if has_table(&mut tx, &history_table).await? {
+                let rename = format!(
+                    r#"ALTER TABLE "{}" RENAME TO "{}_history""#,
+                    history_table, trash_name
+                );
+                sqlx::query(&rename).execute(&mut *tx).await?;
+            }
+
+            for catalog in ["table_settings", "metadata_indexes"] {
+                let move_entries =
+                    format!("UPDATE {} SET table_name = ? WHERE table_name = ?", catalog);
+                sqlx::query(&move_entries)
+                    .bind(&trash_name)
+                    .bind(table_name)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            sqlx::query("DELETE FROM embedding_failures WHERE table_name = ?")
+                .bind(table_name)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT INTO trashed_tables (trash_name, table_name, deleted_at) VALUES (?, ?, ?)",
+            )
+            .bind(&trash_name)
+            .bind(table_name)
+            .bind(deleted_at)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        }
+
+        self.forget_table(db_id, table_name)?;
+        Ok(())
+    }
+
+    /// Drop what is cached about a table that was moved to or from the trash
+    fn forget_table(&self, db_id: &str, table_name: &str) -> Result<()> {
+        let table_key = format!("{}:{}", db_id, table_name);
+        self.table_settings.write().unwrap().remove(&table_key);
+        self.metadata_indexes.write().unwrap().remove(&table_key);
+        self.ensured_tables.lock().unwrap().remove(&table_key);
+        self.drop_index(db_id, table_name)
+    }
+
+    /// Tables in a database's trash, most recently deleted first
+    pub async fn list_deleted_tables(&self, db_id: &str) -> Result<Vec<TrashedTable>> {
+        let pool = self.get_pool(db_id).await?;
+        let rows: Vec<(String, String, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT trash_name, table_name, deleted_at FROM trashed_tables
+            ORDER BY deleted_at DESC, table_name ASC
+        "#,
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        let mut tables = Vec::with_capacity(rows.len());
+        for (trash_name, name, deleted_at) in rows {
+            let count_query = format!(
+                r#"SELECT COUNT(*) FROM "{}" WHERE is_chunk = 0 AND deleted_at IS NULL"#,
+                trash_name
+            );
+            let documents: i64 = sqlx::query_scalar(&count_query).fetch_one(&pool).await?;
+            tables.push(TrashedTable {
+                name,
+                deleted_at,
+                documents,
+            });
+        }
+
+        Ok(tables)
+    }
+
+    /// Restore the most recently deleted copy of a table from the trash
+    ///
+    /// Returns `false` if there is none. Its documents are logged as inserted,
+    /// and its full-text, tag and duplicate detection tables are rebuilt.
+    pub async fn undelete_table(&self, db_id: &str, table_name: &str) -> Result<bool> {
+        if !is_valid_table_name(table_name) {
+            anyhow::bail!("Invalid table name: {}", table_name);
+        }
+        if self.table_exists(db_id, table_name).await? {
+            anyhow::bail!("Table {} already exists", table_name);
+        }
+
+        let pool = self.get_pool(db_id).await?;
+        let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+        let trash_name: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT trash_name FROM trashed_tables WHERE table_name = ?
+            ORDER BY deleted_at DESC LIMIT 1
+        "#,
+        )
+        .bind(table_name)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(trash_name) = trash_name else {
+            tx.rollback().await?;
+            return Ok(false);
+        };
+
+        let rename = format!(r#"ALTER TABLE "{}" RENAME TO "{}""#, trash_name, table_name);
+        sqlx::query(&rename).execute(&mut *tx).await?;
+        if has_table(&mut tx, &format!("{}_history", trash_name)).await? {
+            let rename = format!(
+                r#"ALTER TABLE "{}_history" RENAME TO "{}_history""#,
+                trash_name, table_name
+            );
+            sqlx::query(&rename).execute(&mut *tx).await?;
+        }
+
+        for catalog in ["table_settings", "metadata_indexes"] {
+            let move_entries =
+                format!("UPDATE {} SET table_name = ? WHERE table_name = ?", catalog);
+            sqlx::query(&move_entries)
+                .bind(table_name)
+                .bind(&trash_name)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM trashed_tables WHERE trash_name = ?")
+            .bind(&trash_name)
+            .execute(&mut *tx)
+            .await?;
+
+        let log_inserts = format!(
+            r#"
+            INSERT INTO document_changes
+                (table_name, item_id, parent_id, kind, op, recorded_at)
+            SELECT '{0}', id, parent_id, 'document', 'insert', {1}
+            FROM "{0}" WHERE deleted_at IS NULL
+        "#,
+            table_name, CHANGE_TIME
+        );
+        sqlx::query(&log_inserts).execute(&mut *tx).await?;
+        tx.commit().await?;
+
+        // Recreate the triggers, indexes and side tables, then fill the full-text index
+        self.forget_table(db_id, table_name)?;
+        add_missing_columns(&pool, db_id, table_name).await?;
+        self.ensure_table(db_id, table_name).await?;
+        let rebuild = format!(
+            r#"INSERT INTO "{0}_fts"("{0}_fts") VALUES ('rebuild')"#,
+            table_name
+        );
+        sqlx::query(&rebuild).execute(&pool).await?;
+
+        Ok(true)
+    }
+
+    /// Permanently drop tables trashed before `deleted_before`, with their
+    /// history and the relations of their documents. Returns the number of
+    /// tables dropped.
+    pub async fn purge_deleted_tables(
+        &self,
+        db_id: &str,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<usize> {
+        let pool = self.get_pool(db_id).await?;
+        let expired: Vec<(String, String)> = sqlx::query_as(
+            "SELECT trash_name, table_name FROM trashed_tables WHERE deleted_at < ?",
+        )
+        .bind(deleted_before)
+        .fetch_all(&pool)
+        .await?;
+
+        for (trash_name, table_name) in &expired {
+            let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+            if has_table(&mut tx, "document_relations").await? {
+                let drop_relations = format!(
+                    r#"
+                    DELETE FROM document_relations
+                    WHERE source_id IN (SELECT id FROM "{0}")
+                       OR target_id IN (SELECT id FROM "{0}")
+                "#,
+                    trash_name
+                );
+                sqlx::query(&drop_relations).execute(&mut *tx).await?;
+            }
+
+            for table in [trash_name.clone(), format!("{}_history", trash_name)] {
+                let drop = format!(r#"DROP TABLE IF EXISTS "{}""#, table);
+                sqlx::query(&drop).execute(&mut *tx).await?;
+            }
+            for catalog in ["table_settings", "metadata_indexes"] {
+                let delete = format!("DELETE FROM {} WHERE table_name = ?", catalog);
+                sqlx::query(&delete).bind(trash_name).execute(&mut *tx).await?;
+            }
+            sqlx::query("DELETE FROM trashed_tables WHERE trash_name = ?")
+                .bind(trash_name)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            tracing::info!("Purged deleted table {}.{}", db_id, table_name);
+        }
+
+        Ok(expired.len())
+    }
+
+    /// Drop a table's in-memory index and its saved files, and keep an index
+    /// being built from being published
+    fn drop_index(&self, db_id: &str, table_name: &str) -> Result<()> {
+        let index_key = format!("{}:{}", db_id, table_name);
+        self.pending_index_changes.lock().unwrap().remove(&index_key);
+        self.indexes.write().unwrap().remove(&index_key);
+        self.dirty_indexes.lock().unwrap().remove(&index_key);
+        VectorIndex::remove_files(
+            Path::new(&self.base_dir),
+            &index_basename(db_id, table_name),
+        )
+    }
+
+    /// Drop the indexes, cached schema state and pool of a database before its
+    /// file is moved or replaced
+    ///
+    /// The returned guard keeps the database from being opened again until the
+    /// file has been moved.
+    async fn forget_database(&self, db_id: &str) -> Result<tokio::sync::MutexGuard<'_, ()>> {
+        // Drop indexes of every table in this database
+        if self.database_exists(db_id) {
+            for table_name in self.list_tables(db_id).await? {
+                self.drop_index(db_id, &table_name)?;
+            }
+        }
+
+        let db_prefix = format!("{}:", db_id);
+        self.ensured_tables
+            .lock()
+            .unwrap()
+            .retain(|key| !key.starts_with(&db_prefix));
+        self.database_settings.write().unwrap().remove(db_id);
+        self.table_settings
+            .write()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&db_prefix));
+        self.metadata_indexes
+            .write()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&db_prefix));
+
+        // Remove pool from cache; closing waits for connections in use. The
+        // write-ahead log is folded into the file first, so the file can be
+        // moved on its own.
+        let opening = self.opening.lock().await;
+        let pool = self.pools.write().unwrap().remove(db_id);
+        if let Some(pool) = pool {
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&pool)
+                .await?;
+            pool.close().await;
+        }
+
+        Ok(opening)
+    }
+
+    pub async fn delete_database(&self, db_id: &str) -> Result<()> {
+        let db_path = format!("{}/{}.db", self.base_dir, db_id);
+        let _opening = self.forget_database(db_id).await?;
+
+        // Move the database file to the trash, named by its deletion time
+        if std::path::Path::new(&db_path).exists() {
+            let trash_dir = Path::new(&self.base_dir).join(TRASH_DIR);
+            std::fs::create_dir_all(&trash_dir).context("Failed to create trash directory")?;
+            let trashed = trash_dir.join(format!("{}.{}.db", db_id, Utc::now().timestamp_millis()));
+            std::fs::rename(&db_path, trashed).context("Failed to move database to trash")?;
+        }
+        self.embedding_requests.forget(db_id);
+
+        // The catalog entry is kept for an undelete
+        let global_pool = self.get_global_pool().await?;
+        sqlx::query("UPDATE databases SET deleted_at = ? WHERE name = ?")
+            .bind(Utc::now())
+            .bind(db_id)
+            .execute(&global_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Record a database in the catalog when it is first opened
+    ///
+    /// Databases created by older versions are dated by their file. A name
+    /// whose database was deleted starts over.
+    async fn register_database(&self, db_id: &str, db_path: &str) -> Result<()> {
+        let created_at = std::fs::metadata(db_path)
+            .and_then(|metadata| metadata.created().or_else(|_| metadata.modified()))
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+
+        let global_pool = self.get_global_pool().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO databases (name, created_at, settings) VALUES (?, ?, '{}')
+            ON CONFLICT(name) DO UPDATE SET
+                created_at = excluded.created_at,
+                settings = excluded.settings,
+                deleted_at = NULL
+            WHERE deleted_at IS NOT NULL
+        "#,
+        )
+        .bind(db_id)
+        .bind(created_at)
+        .execute(&global_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Creation time and settings of a database from the catalog
+    async fn catalog_entry(&self, db_id: &str) -> Result<(DateTime<Utc>, DatabaseSettings)> {
+        // Opening the database registers it
+        self.get_pool(db_id).await?;
+        let global_pool = self.get_global_pool().await?;
+        let row = sqlx::query("SELECT created_at, settings FROM databases WHERE name = ?")
+            .bind(db_id)
+            .fetch_one(&global_pool)
+            .await?;
+        let settings: String = row.get("settings");
+
+        Ok((row.get("created_at"), serde_json::from_str(&settings)?))
+    }
+
+    /// Settings of a database
+    pub async fn database_settings(&self, db_id: &str) -> Result<DatabaseSettings> {
+        if let Some(settings) = self.database_settings.read().unwrap().get(db_id) {
+            return Ok(settings.clone());
+        }
+
+        let settings = self.catalog_entry(db_id).await?.1;
+        self.database_settings
+            .write()
+            .unwrap()
+            .insert(db_id.to_string(), settings.clone());
+        Ok(settings)
+    }
+
+    /// Save the settings of a database, creating it if needed
+    pub async fn set_database_settings(
+        &self,
+        db_id: &str,
+        settings: &DatabaseSettings,
+    ) -> Result<()> {
+        self.get_pool(db_id).await?;
+        let global_pool = self.get_global_pool().await?;
+        sqlx::query("UPDATE databases SET settings = ? WHERE name = ?")
+            .bind(serde_json::to_string(settings)?)
+            .bind(db_id)
+            .execute(&global_pool)
+            .await?;
+        self.database_settings.write().unwrap().remove(db_id);
+
+        Ok(())
+    }
+
+    /// Catalog entry of a database with totals over its tables
+    pub async fn database_info(&self, db_id: &str) -> Result<DBInfo> {
+        let (created_at, settings) = self.catalog_entry(db_id).await?;
+        let tables = self.list_tables(db_id).await?;
+
+        let pool = self.get_pool(db_id).await?;
+        let last_change: Option<String> =
+            sqlx::query_scalar("SELECT MAX(recorded_at) FROM document_changes")
+                .fetch_one(&pool)
+                .await?;
+
+        let size_bytes = self.database_size(db_id);
+        let limits = settings.limits;
+        let usage = DatabaseUsage {
+            documents: QuotaUsage {
+                used: 0,
+                limit: limits.max_documents,
+            },
+            bytes: QuotaUsage {
+                used: size_bytes,
+                limit: limits.max_bytes,
+            },
+            tables: QuotaUsage {
+                used: tables.len() as u64,
+                limit: limits.max_tables,
+            },
+            embedding_requests_per_minute: QuotaUsage {
+                used: self.embedding_requests.recent(db_id),
+                limit: limits.max_embedding_requests_per_minute,
+            },
+        };
+
+        let mut info = DBInfo {
+            name: db_id.to_string(),
+            tables: Vec::new(),
+            document_count: 0,
+            chunk_count: 0,
+            embedded_count: 0,
+            pending_count: 0,
+            failed_count: 0,
+            total_tokens: 0,
+            created_at,
+            last_updated: parse_recorded_at(last_change).unwrap_or(created_at),
+            size_bytes: size_bytes as i64,
+            settings,
+            usage,
+        };
+        for table_name in tables {
+            let stats = self.table_stats(db_id, &table_name).await?;
+            info.document_count += stats.document_count;
+            info.chunk_count += stats.chunk_count;
+            info.embedded_count += stats.embedded_count;
+            info.pending_count += stats.pending_count;
+            info.failed_count += stats.failed_count;
+            info.total_tokens += stats.total_tokens;
+            info.tables.push(table_name);
+        }
+        info.usage.documents.used = info.document_count as u64;
+
+        Ok(info)
+    }
+
+    /// Size of a database file, including its write-ahead log
+    fn database_size(&self, db_id: &str) -> u64 {
+        let db_path = Path::new(&self.base_dir).join(format!("{}.db", db_id));
+        [db_path.clone(), db_path.with_extension("db-wal")]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Check a write of documents to a table against the database's limits on
+    /// document size and tables, before the table is created
+    ///
+    /// Chunks are written with their parent and count towards no limit.
+    async fn check_write_quota(
+        &self,
+        db_id: &str,
+        table_name: &str,
+        docs: &[&Document],
+    ) -> Result<()> {
+        let limits = self.database_settings(db_id).await?.limits;
+        if limits.max_content_bytes.is_none() && limits.max_tables.is_none() {
+            return Ok(());
+        }
+        let docs: Vec<&Document> = docs.iter().copied().filter(|doc| !doc.is_chunk).collect();
+        if docs.is_empty() {
+            return Ok(());
+        }
+
+        let exceeded = |kind, limit, used| QuotaExceeded {
+            db: db_id.to_string(),
+            kind,
+            limit,
+            used,
+            retry_after: None,
+        };
+
+        if let Some(limit) = limits.max_content_bytes {
+            let largest = docs.iter().map(|doc| doc.content.len() as u64).max();
+            if let Some(size) = largest.filter(|size| *size > limit) {
+                return Err(exceeded(QuotaKind::ContentBytes, limit, size).into());
+            }
+        }
+
+        if let Some(limit) = limits.max_tables {
+            let used = self.list_tables(db_id).await?.len() as u64;
+            if !self.table_exists(db_id, table_name).await? && used >= limit {
+                return Err(exceeded(QuotaKind::Tables, limit, used).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a write of documents to a table against the database's limits on
+    /// documents and size, inside the write transaction so concurrent writes
+    /// cannot all pass
+    ///
+    /// Once the database reaches its size limit, new documents are rejected but
+    /// existing ones can still be updated.
+    async fn check_storage_quota(
+        &self,
+        conn: &mut SqliteConnection,
+        db_id: &str,
+        table_name: &str,
+        docs: &[&Document],
+    ) -> Result<()> {
+        let limits = self.database_settings(db_id).await?.limits;
+        if limits.max_documents.is_none() && limits.max_bytes.is_none() {
+            return Ok(());
+        }
+
+        // Chunks count towards no limit, and documents written again under
+        // their ID are not new
+        let ids: HashSet<&str> = docs
+            .iter()
+            .filter(|doc| !doc.is_chunk)
+            .map(|doc| doc.id.as_str())
+            .collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let query = format!(
+            r#"
+            SELECT COUNT(*) FROM "{}"
+            WHERE id IN (SELECT value FROM json_each(?)) AND is_chunk = 0 AND {}
+        "#,
+            table_name, LIVE_ROWS
+        );
+        let existing: i64 = sqlx::query_scalar(&query)
+            .bind(serde_json::to_string(&ids)?)
+            .fetch_one(&mut *conn)
+            .await?;
+        let added = ids.len() as u64 - existing as u64;
+        if added == 0 {
+            return Ok(());
+        }
+
+        let exceeded = |kind, limit, used| QuotaExceeded {
+            db: db_id.to_string(),
+            kind,
+            limit,
+            used,
+            retry_after: None,
+        };
+
+        if let Some(limit) = limits.max_bytes {
+            let used = self.database_size(db_id);
+            if used >= limit {
+                return Err(exceeded(QuotaKind::Bytes, limit, used).into());
+            }
+        }
+
+        if let Some(limit) = limits.max_documents {
+            let used = live_document_count(conn).await?;
+            if used + added > limit {
+                return Err(exceeded(QuotaKind::Documents, limit, used).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record up to `wanted` embedding requests for a database within its rate
+    /// limit, returning how many may be made
+    pub async fn take_embedding_requests(&self, db_id: &str, wanted: usize) -> Result<usize> {
+        let limits = self.database_settings(db_id).await?.limits;
+        Ok(self
+            .embedding_requests
+            .take(db_id, wanted, limits.max_embedding_requests_per_minute))
+    }
+
+    /// Record one embedding request for a database, failing with
+    /// [`QuotaExceeded`] if it is over its rate limit
+    pub async fn acquire_embedding_request(&self, db_id: &str) -> Result<()> {
+        let limit = self
+            .database_settings(db_id)
+            .await?
+            .limits
+            .max_embedding_requests_per_minute;
+        if self.embedding_requests.take(db_id, 1, limit) == 1 {
+            return Ok(());
+        }
+
+        Err(QuotaExceeded {
+            db: db_id.to_string(),
+            kind: QuotaKind::EmbeddingRequestsPerMinute,
+            limit: limit.unwrap_or_default(),
+            used: self.embedding_requests.recent(db_id),
+            retry_after: Some(self.embedding_requests.retry_after(db_id)),
+        }
+        .into())
+    }
+
+    /// Document, embedding and storage statistics of a table
+    ///
+    /// Expired documents that have not been reaped yet are not counted.
+    pub async fn table_stats(&self, db_id: &str, table_name: &str) -> Result<TableStats> {
+        let vector_dimensions = self.table_vector_dimensions(db_id, table_name).await?;
+        let threshold = self.table_index_config(db_id, table_name).await?.1;
+        let pool = self.get_pool(db_id).await?;
+
+        let counts_query = format!(
+            r#"
+            SELECT
+                COALESCE(SUM(is_chunk = 0), 0) AS documents,
+                COALESCE(SUM(is_chunk = 1), 0) AS chunks,
+                COALESCE(SUM(is_embedded = 1), 0) AS embedded,
+                COALESCE(SUM(is_embedded = 0 AND vectorize = 1 AND f.doc_id IS NULL), 0) AS pending,
+                COALESCE(SUM(is_embedded = 0 AND vectorize = 1 AND f.doc_id IS NOT NULL), 0) AS failed,
+                COALESCE(SUM(CASE WHEN is_chunk = 0 THEN token_count ELSE 0 END), 0) AS tokens
+            FROM "{}" d
+            LEFT JOIN embedding_failures f ON f.table_name = ? AND f.doc_id = d.id
+            WHERE {}
+        "#,
+            table_name, LIVE_ROWS
+        );
+        let counts = sqlx::query(&counts_query)
+            .bind(table_name)
+            .fetch_one(&pool)
+            .await?;
+
+        let deleted_query = format!(
+            r#"SELECT COUNT(*) FROM "{}" WHERE deleted_at IS NOT NULL AND is_chunk = 0"#,
+            table_name
+        );
+        let deleted_count: i64 = sqlx::query_scalar(&deleted_query).fetch_one(&pool).await?;
+
+        let last_change: Option<String> =
+            sqlx::query_scalar("SELECT MAX(recorded_at) FROM document_changes WHERE table_name = ?")
+                .bind(table_name)
+                .fetch_one(&pool)
+                .await?;
+
+        // Pages of the table and of every table and SQL index kept alongside it
+        let size_query = format!(
+            r#"
+            SELECT COALESCE(SUM(s.pgsize), 0) FROM dbstat s
+            JOIN sqlite_master m ON m.name = s.name
+            WHERE m.tbl_name IN (
+                '{0}', '{0}_fts_data', '{0}_fts_idx', '{0}_fts_docsize', '{0}_fts_config',
+                '{0}_tags', '{0}_dedup', '{0}_history'
+            )
+        "#,
+            table_name
+        );
+        let size_bytes: i64 = sqlx::query_scalar(&size_query).fetch_one(&pool).await?;
+
+        let index = self.loaded_index(&format!("{}:{}", db_id, table_name));
+        let index = index.as_deref();
+        let saved_bytes =
+            VectorIndex::saved_size(Path::new(&self.base_dir), &index_basename(db_id, table_name));
+        let index = IndexStatus {
+            enabled: self.use_indexing && threshold.is_some(),
+            threshold: threshold.filter(|_| self.use_indexing),
+            built: index.is_some_and(|index| index.is_built()),
+            entries: index.map_or(0, |index| index.len()),
+            tombstone_ratio: index.map_or(0.0, |index| index.tombstone_ratio()),
+            saved: saved_bytes.is_some(),
+            saved_bytes: saved_bytes.unwrap_or(0) as i64,
+        };
+
+        Ok(TableStats {
+            db: db_id.to_string(),
+            table: table_name.to_string(),
+            document_count: counts.get("documents"),
+            chunk_count: counts.get("chunks"),
+            embedded_count: counts.get("embedded"),
+            pending_count: counts.get("pending"),
+            failed_count: counts.get("failed"),
+            deleted_count,
+            total_tokens: counts.get("tokens"),
+            vector_dimensions,
+            last_updated: parse_recorded_at(last_change),
+            size_bytes,
+            index,
+        })
+    }
+
+    /// Whether a database file exists
+    pub fn database_exists(&self, db_id: &str) -> bool {
+        Path::new(&format!("{}/{}.db", self.base_dir, db_id)).exists()
+    }
+
+    /// Restore the most recently deleted copy of a database from the trash
+    ///
+    /// Returns `false` if there is none.
+    pub async fn undelete_database(&self, db_id: &str) -> Result<bool> {
+        if self.database_exists(db_id) {
+            anyhow::bail!("Database {} already exists", db_id);
+        }
+        let db_path = format!("{}/{}.db", self.base_dir, db_id);
+
+        let latest = self
+            .trashed_databases()?
+            .into_iter()
+            .filter(|(name, _, _)| name == db_id)
+            .max_by_key(|(_, deleted_at, _)| *deleted_at);
+        let Some((_, _, path)) = latest else {
+            return Ok(false);
+        };
+
+        std::fs::rename(path, &db_path).context("Failed to restore database from trash")?;
+        let global_pool = self.get_global_pool().await?;
+        sqlx::query("UPDATE databases SET deleted_at = NULL WHERE name = ?")
+            .bind(db_id)
+            .execute(&global_pool)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Permanently delete database files trashed before `deleted_before`
+    pub async fn purge_deleted_databases(
+        &self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<usize> {
+        let mut purged = 0;
+        for (name, deleted_at, path) in self.trashed_databases()? {
+            if deleted_at < deleted_before.timestamp_millis() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to purge database {}", name))?;
+                tracing::info!("Purged deleted database {}", name);
+                purged += 1;
+            }
+        }
+
+        // Forget databases with no copy left to undelete
+        let trashed: HashSet<String> = self
+            .trashed_databases()?
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect();
+        let global_pool = self.get_global_pool().await?;
+        let deleted: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM databases WHERE deleted_at IS NOT NULL")
+                .fetch_all(&global_pool)
+                .await?;
+        for name in deleted {
+            if !trashed.contains(&name) && !self.database_exists(&name) {
+                sqlx::query("DELETE FROM databases WHERE name = ?")
+                    .bind(&name)
+                    .execute(&global_pool)
+                    .await?;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Write a consistent snapshot of a database with `VACUUM INTO`
+    ///
+    /// Each backup is a directory `{backup_dir}/{db}/{millis}` holding the database
+    /// file, a `backup.json` manifest and, if asked for, the tables' HNSW indexes
+    /// and a copy of the global database with the embedding cache. It is written
+    /// under a temporary name and renamed once complete.
+    pub async fn backup_database(
+        &self,
+        db_id: &str,
+        options: &BackupOptions,
+    ) -> Result<BackupInfo> {
+        if !self.database_exists(db_id) {
+            anyhow::bail!("Database {} does not exist", db_id);
+        }
+
+        let created_at = Utc::now();
+        let id = created_at.timestamp_millis().to_string();
+        let db_dir = Path::new(&self.backup_dir).join(db_id);
+        let tmp_dir = db_dir.join(format!("{}.tmp", id));
+        std::fs::create_dir_all(&tmp_dir).context("Failed to create backup directory")?;
+
+        let info = match self
+            .write_backup(db_id, &id, created_at, &tmp_dir, options)
+            .await
+        {
+            Ok(info) => info,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&tmp_dir);
+                return Err(e);
+            }
+        };
+
+        std::fs::rename(&tmp_dir, db_dir.join(&id)).context("Failed to finish backup")?;
+        tracing::info!(
+            "Backed up database {} ({} bytes) as {}",
+            db_id,
+            info.size_bytes,
+            id
+        );
+
+        Ok(info)
+    }
+
+    /// Write the files of a backup into `dir`
+    async fn write_backup(
+        &self,
+        db_id: &str,
+        id: &str,
+        created_at: DateTime<Utc>,
+        dir: &Path,
+        options: &BackupOptions,
+    ) -> Result<BackupInfo> {
+        let db_file = dir.join(format!("{}.db", db_id));
+        let pool = self.get_pool(db_id).await?;
+        sqlx::query("VACUUM INTO ?")
+            .bind(db_file.to_string_lossy().to_string())
+            .execute(&pool)
+            .await?;
+
+        let tables = self.list_tables(db_id).await?;
+        let mut indexes = Vec::new();
+        if options.include_index {
+            for table_name in &tables {
+                if self.backup_index(db_id, table_name, dir).await? {
+                    indexes.push(table_name.clone());
+                }
+            }
+        }
+
+        if options.include_cache {
+            let global_pool = self.get_global_pool().await?;
+            sqlx::query("VACUUM INTO ?")
+                .bind(dir.join(GLOBAL_DB_FILE).to_string_lossy().to_string())
+                .execute(&global_pool)
+                .await?;
+        }
+
+        let mut size_bytes = 0;
+        for entry in std::fs::read_dir(dir)? {
+            size_bytes += entry?.metadata()?.len();
+        }
+
+        let info = BackupInfo {
+            id: id.to_string(),
+            db: db_id.to_string(),
+            created_at,
+            size_bytes,
+            tables,
+            indexes,
+            includes_cache: options.include_cache,
+        };
+        std::fs::write(dir.join(BACKUP_MANIFEST), serde_json::to_vec_pretty(&info)?)?;
+
+        Ok(info)
+    }
+
+    /// Put a table's HNSW index into a backup directory, if it has a current one
+    async fn backup_index(&self, db_id: &str, table_name: &str, dir: &Path) -> Result<bool> {
+        let basename = index_basename(db_id, table_name);
+        let index_key = format!("{}:{}", db_id, table_name);
+
+        let Some(index) = self.loaded_index(&index_key) else {
+            // Saved files are checked against the table when loaded after a restore
+            return VectorIndex::copy_files(Path::new(&self.base_dir), dir, &basename);
+        };
+
+        let (row_count, watermark) = self.index_watermark(db_id, table_name).await?;
+        if !index.is_built() || row_count != index.len() as i64 {
+            return Ok(false);
+        }
+        let dir = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || index.save(&dir, &basename, row_count, watermark))
+            .await??;
+        Ok(true)
+    }
+
+    /// Backups of a database, newest first
+    pub fn list_backups(&self, db_id: &str) -> Result<Vec<BackupInfo>> {
+        let db_dir = Path::new(&self.backup_dir).join(db_id);
+        if !db_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut backups = Vec::new();
+        for entry in std::fs::read_dir(db_dir)? {
+            // Unfinished backups have no manifest yet
+            let manifest = entry?.path().join(BACKUP_MANIFEST);
+            if let Ok(json) = std::fs::read(&manifest) {
+                match serde_json::from_slice::<BackupInfo>(&json) {
+                    Ok(info) => backups.push(info),
+                    Err(e) => tracing::warn!("Ignoring backup {}: {}", manifest.display(), e),
+                }
+            }
+        }
+
+        backups.sort_by_key(|info| std::cmp::Reverse(info.created_at));
+        Ok(backups)
+    }
+
+    /// Replace a database with a backup (the latest if `backup_id` is `None`)
+    ///
+    /// The current pool is closed once its connections are returned and the
+    /// backed-up file is renamed over the live one, so the database is never
+    /// seen half-restored. Backed-up indexes replace the saved ones, and the
+    /// backed-up embedding cache is merged into the current cache. Returns
+    /// `None` if there is no such backup.
+    pub async fn restore_database(
+        &self,
+        db_id: &str,
+        backup_id: Option<&str>,
+        include_index: bool,
+        include_cache: bool,
+    ) -> Result<Option<BackupInfo>> {
+        let backups = self.list_backups(db_id)?;
+        let info = match backup_id {
+            Some(id) => backups.into_iter().find(|info| info.id == id),
+            None => backups.into_iter().next(),
+        };
+        let Some(info) = info else {
+            return Ok(None);
+        };
+
+        let backup_dir = Path::new(&self.backup_dir).join(db_id).join(&info.id);
+        let base_dir = Path::new(&self.base_dir).to_path_buf();
+
+        // Copy next to the live file first, so the swap itself is a rename
+        let staged = base_dir.join(format!("{}.db.restore", db_id));
+        std::fs::copy(backup_dir.join(format!("{}.db", db_id)), &staged)
+            .context("Failed to copy backup")?;
+
+        let opening = self.forget_database(db_id).await?;
+        std::fs::rename(&staged, base_dir.join(format!("{}.db", db_id)))
+            .context("Failed to replace database")?;
+        drop(opening);
+
+        if include_index {
+            for table_name in &info.indexes {
+                VectorIndex::copy_files(
+                    &backup_dir,
+                    &base_dir,
+                    &index_basename(db_id, table_name),
+                )?;
+            }
+        }
+
+        if include_cache && info.includes_cache {
+            let restored = self
+                .merge_embedding_cache(&backup_dir.join(GLOBAL_DB_FILE))
+                .await?;
+            tracing::info!("Restored {} cached embeddings", restored);
+        }
+
+        tracing::info!("Restored database {} from backup {}", db_id, info.id);
+        Ok(Some(info))
+    }
+
+    /// Copy the embedding cache of a backed-up global database into the live one
+    async fn merge_embedding_cache(&self, backup_file: &Path) -> Result<u64> {
+        let global_pool = self.get_global_pool().await?;
+        let mut conn = global_pool.acquire().await?;
+
+        sqlx::query("ATTACH DATABASE ? AS backup")
+            .bind(backup_file.to_string_lossy().to_string())
+            .execute(&mut *conn)
+            .await?;
+
+        let merged = async {
+            let create: Option<String> = sqlx::query_scalar(
+                "SELECT sql FROM backup.sqlite_master WHERE type = 'table' AND name = 'embedding_cache'",
+            )
+            .fetch_optional(&mut *conn)
+            .await?;
+            let Some(create) = create else {
+                return Ok(0);
+            };
+
+            // The cache table only exists once the cache has been used
+            let exists: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM main.sqlite_master WHERE type = 'table' AND name = 'embedding_cache'",
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            if exists == 0 {
+                sqlx::query(&create).execute(&mut *conn).await?;
+            }
+
+            let result = sqlx::query(
+                "INSERT OR REPLACE INTO main.embedding_cache SELECT * FROM backup.embedding_cache",
+            )
+            .execute(&mut *conn)
+            .await?;
+            anyhow::Ok(result.rows_affected())
+        }
+        .await;
+
+        sqlx::query("DETACH DATABASE backup")
+            .execute(&mut *conn)
+            .await?;
+        merged
+    }
+
+    /// Delete the oldest backups of a database beyond the `keep` newest
+    pub fn prune_backups(&self, db_id: &str, keep: usize) -> Result<usize> {
+        let mut pruned = 0;
+        for info in self.list_backups(db_id)?.into_iter().skip(keep) {
+            let dir = Path::new(&self.backup_dir).join(db_id).join(&info.id);
+            std::fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to delete backup {}", info.id))?;
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+
+    /// Database files in the trash, as `(name, deleted_at millis, path)`
+    fn trashed_databases(&self) -> Result<Vec<(String, i64, std::path::PathBuf)>> {
+        let trash_dir = Path::new(&self.base_dir).join(TRASH_DIR);
+        if !trash_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut databases = Vec::new();
+        for entry in std::fs::read_dir(trash_dir)? {
+            let path = entry?.path();
+            let Some(stem) = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+            else {
+                continue;
+            };
+            if path.extension().is_some_and(|ext| ext == "db") {
+                if let Some((name, millis)) = stem.rsplit_once('.') {
+                    if let Ok(millis) = millis.parse() {
+                        databases.push((name.to_string(), millis, path));
+                    }
+                }
+            }
+        }
+
+        Ok(databases)
+    }
+}
+
+/// Document tables of a database, leaving out FTS, history and other internal tables
+async fn user_tables(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT name FROM sqlite_master
+        WHERE type = 'table'
+        AND name NOT LIKE 'sqlite_%'
+        AND name NOT LIKE 'trash$%'
+        AND name NOT LIKE '%_fts'
+        AND name NOT LIKE '%_config'
+        AND name NOT LIKE '%_data'
+        AND name NOT LIKE '%_idx'
+        AND name NOT LIKE '%_docsize'
+        AND NOT (name LIKE '%\_history' ESCAPE '\' AND substr(name, 1, length(name) - 8) IN (
+            SELECT name FROM sqlite_master WHERE type = 'table'
+        ))
+        AND NOT (name LIKE '%_tags' AND substr(name, 1, length(name) - 5) IN (
+            SELECT name FROM sqlite_master WHERE type = 'table'
+        ))
+        AND NOT (name LIKE '%_dedup' AND substr(name, 1, length(name) - 6) IN (
+            SELECT name FROM sqlite_master WHERE type = 'table'
+        ))
+        AND name NOT IN (
+            'document_relations', 'document_changes', 'table_settings', 'metadata_indexes',
+            'embedding_failures', 'trashed_tables'
+        )
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows.iter().map(|row| row.get("name")).collect())
+}
+
+/// Live root documents across a database's tables
+async fn live_document_count(conn: &mut SqliteConnection) -> Result<u64> {
+    let mut count = 0;
+    for table_name in user_tables(conn).await? {
+        let query = format!(
+            r#"SELECT COUNT(*) FROM "{}" WHERE is_chunk = 0 AND {}"#,
+            table_name, LIVE_ROWS
+        );
+        let table_count: i64 = sqlx::query_scalar(&query).fetch_one(&mut *conn).await?;
+        count += table_count as u64;
+    }
+
+    Ok(count)
+}
+
+/// Options for a connection to a database file, creating it if needed
+///
+/// WAL mode lets reads run while another connection writes.
+fn connect_options(path: &str) -> SqliteConnectOptions {
+    SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .foreign_keys(true)
+        .busy_timeout(BUSY_TIMEOUT)
+}
+
+/// Create the catalog of databases in the global database
+///
+/// Settings are stored as JSON. `deleted_at` is set while the database is in
+/// the trash.
+async fn create_catalog(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS databases (
+            name TEXT PRIMARY KEY,
+            created_at DATETIME NOT NULL,
+            settings TEXT NOT NULL,
+            deleted_at DATETIME DEFAULT NULL
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Create the catalog of a database's deleted tables
+async fn create_table_trash(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS trashed_tables (
+            trash_name TEXT PRIMARY KEY,
+            table_name TEXT NOT NULL,
+            deleted_at DATETIME NOT NULL
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Create the table recording documents the embedding worker failed to embed
+async fn create_embedding_failures(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS embedding_failures (
+            table_name TEXT NOT NULL,
+            doc_id TEXT NOT NULL,
+            error TEXT NOT NULL,
+            attempts INTEGER NOT NULL,
+            failed_at DATETIME NOT NULL,
+            PRIMARY KEY (table_name, doc_id)
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Parse a change log timestamp read as text, e.g. from `MAX(recorded_at)`
+fn parse_recorded_at(recorded_at: Option<String>) -> Option<DateTime<Utc>> {
+    let recorded_at = DateTime::parse_from_rfc3339(&recorded_at?).ok()?;
+    Some(recorded_at.with_timezone(&Utc))
+}
+
+/// Current time as written to the change log, in the layout of [`change_time`]
+const CHANGE_TIME: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+/// A time in the layout change log timestamps are stored in
+fn change_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Create a database's change log, and the triggers logging relation changes if
+/// the relations table exists
+///
+/// Changes are logged by triggers, so each entry is written in the transaction of
+/// the mutation it records. `AUTOINCREMENT` keeps sequence numbers increasing even
+/// after old entries are pruned.
+async fn create_change_log(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS document_changes (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            table_name TEXT,
+            item_id TEXT NOT NULL,
+            parent_id TEXT,
+            kind TEXT NOT NULL,
+            op TEXT NOT NULL,
+            recorded_at DATETIME NOT NULL
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Logs without the parent column were written by triggers that don't fill it;
+    // drop those so they are created again below and by `create_change_triggers`
+    let has_parent: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM pragma_table_info('document_changes') WHERE name = 'parent_id'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if has_parent == 0 {
+        sqlx::query("ALTER TABLE document_changes ADD COLUMN parent_id TEXT")
+            .execute(pool)
+            .await?;
+        let triggers: Vec<String> = sqlx::query_scalar(
+            r"SELECT name FROM sqlite_master
+              WHERE type = 'trigger' AND name LIKE '%\_changes\_a_' ESCAPE '\'",
+        )
+        .fetch_all(pool)
+        .await?;
+        for trigger in triggers {
+            sqlx::query(&format!(r#"DROP TRIGGER IF EXISTS "{}""#, trigger))
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    let relations: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'document_relations'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if relations == 0 {
+        return Ok(());
+    }
+
+    for (suffix, event, row, op) in [
+        ("ai", "INSERT", "NEW", "insert"),
+        ("au", "UPDATE", "NEW", "update"),
+        ("ad", "DELETE", "OLD", "delete"),
+    ] {
+        let trigger = format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS document_relations_changes_{0}
+            AFTER {1} ON document_relations
+            BEGIN
+                INSERT INTO document_changes (table_name, item_id, kind, op, recorded_at)
+                VALUES (NULL, {2}.id, 'relation', '{3}', {4});
+            END
+        "#,
+            suffix, event, row, op, CHANGE_TIME
+        );
+        sqlx::query(&trigger).execute(pool).await?;
+    }
+
+    Ok(())
+}
+
+/// Create the triggers logging changes to a table's documents
+///
+/// Rows in the trash are deleted as far as the log is concerned: trashing a row
+/// logs a delete and restoring it an insert, while updating or purging it logs
+/// nothing.
+async fn create_change_triggers(pool: &SqlitePool, table_name: &str) -> Result<()> {
+    let triggers = [
+        format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS "{0}_changes_ai" AFTER INSERT ON "{0}"
+            WHEN NEW.deleted_at IS NULL
+            BEGIN
+                INSERT INTO document_changes
+                    (table_name, item_id, parent_id, kind, op, recorded_at)
+                VALUES ('{0}', NEW.id, NEW.parent_id, 'document', 'insert', {1});
+            END
+        "#,
+            table_name, CHANGE_TIME
+        ),
+        format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS "{0}_changes_au" AFTER UPDATE ON "{0}"
+            WHEN NEW.deleted_at IS NULL OR OLD.deleted_at IS NULL
+            BEGIN
+                INSERT INTO document_changes
+                    (table_name, item_id, parent_id, kind, op, recorded_at)
+                VALUES ('{0}', NEW.id, NEW.parent_id, 'document',
+                    CASE
+                        WHEN NEW.deleted_at IS NOT NULL THEN 'delete'
+                        WHEN OLD.deleted_at IS NOT NULL THEN 'insert'
+                        ELSE 'update'
+                    END,
+                    {1});
+            END
+        "#,
+            table_name, CHANGE_TIME
+        ),
+        format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS "{0}_changes_ad" AFTER DELETE ON "{0}"
+            WHEN OLD.deleted_at IS NULL
+            BEGIN
+                INSERT INTO document_changes
+                    (table_name, item_id, parent_id, kind, op, recorded_at)
+                VALUES ('{0}', OLD.id, OLD.parent_id, 'document', 'delete', {1});
+            END
+        "#,
+            table_name, CHANGE_TIME
+        ),
+    ];
+
+    for trigger in triggers {
+        sqlx::query(&trigger).execute(pool).await?;
+    }
+
+    Ok(())
+}
+
+/// Build a [`Change`] from a `document_changes` row
+fn row_to_change(row: &SqliteRow) -> Result<Change> {
+    let kind = match row.get::<String, _>("kind").as_str() {
+        "document" => ChangeKind::Document,
+        "relation" => ChangeKind::Relation,
+        other => anyhow::bail!("Unknown change kind: {}", other),
+    };
+    let op = match row.get::<String, _>("op").as_str() {
+        "insert" => ChangeOp::Insert,
+        "update" => ChangeOp::Update,
+        "delete" => ChangeOp::Delete,
+        other => anyhow::bail!("Unknown change op: {}", other),
+    };
+
+    Ok(Change {
+        seq: row.get("seq"),
+        table: row.get("table_name"),
+        id: row.get("item_id"),
+        parent_id: row.get("parent_id"),
+        kind,
+        op,
+        recorded_at: row.get("recorded_at"),
+        document: None,
+        relation: None,
+    })
+}
+
+/// Add any of [`ADDED_COLUMNS`] missing from an existing table
+async fn add_missing_columns(pool: &SqlitePool, db_id: &str, table_name: &str) -> Result<()> {
+    let pragma = format!(r#"SELECT name FROM pragma_table_info('{}')"#, table_name);
+    let existing: HashSet<String> = sqlx::query_scalar(&pragma)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    for (column, definition) in ADDED_COLUMNS {
+        if existing.contains(*column) {
+            continue;
+        }
+
+        tracing::info!("Adding column {} to {}.{}", column, db_id, table_name);
+        let alter = format!(
+            r#"ALTER TABLE "{}" ADD COLUMN {} {}"#,
+            table_name, column, definition
+        );
+        sqlx::query(&alter).execute(pool).await?;
+    }
+
+    Ok(())
+}
+
+/// Create the side table holding the content hash and MinHash signature of each
+/// root document, used to detect duplicates
+///
+/// When the side table is new, the keys of existing documents are computed.
+async fn create_dedup_table(pool: &SqlitePool, db_id: &str, table_name: &str) -> Result<()> {
+    let dedup_table = format!("{}_dedup", table_name);
+    let exists: Option<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(&dedup_table)
+            .fetch_optional(pool)
+            .await?;
+    if exists.is_some() {
+        return Ok(());
+    }
+
+    let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+    let statements = [
+        format!(
+            r#"
+            CREATE TABLE "{d}" (
+                doc_id TEXT PRIMARY KEY,
+                content_hash TEXT NOT NULL,
+                minhash BLOB NOT NULL
+            ) WITHOUT ROWID
+        "#,
+            d = dedup_table
+        ),
+        format!(
+            r#"CREATE INDEX IF NOT EXISTS idx_{t}_dedup_hash ON "{d}"(content_hash)"#,
+            t = table_name,
+            d = dedup_table
+        ),
+        format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS {t}_dedup_ad AFTER DELETE ON "{t}" BEGIN
+                DELETE FROM "{d}" WHERE doc_id = old.id;
+            END
+        "#,
+            t = table_name,
+            d = dedup_table
+        ),
+    ];
+    for statement in &statements {
+        sqlx::query(statement).execute(&mut *tx).await?;
+    }
+
+    let roots_query = format!(r#"SELECT id, content FROM "{}" WHERE is_chunk = 0"#, table_name);
+    let roots: Vec<(String, String)> = sqlx::query_as(&roots_query).fetch_all(&mut *tx).await?;
+    if !roots.is_empty() {
+        tracing::info!(
+            "Hashing {} documents in {}.{} for duplicate detection",
+            roots.len(),
+            db_id,
+            table_name
+        );
+    }
+    for (id, content) in &roots {
+        upsert_dedup_keys(&mut tx, table_name, id, content).await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Create the tag side table of a table, with triggers that keep it in sync with
+/// the `tags` column
+///
+/// When the side table is new, tags written comma-joined by older versions are
+/// converted to JSON first and every row's tags are copied in.
+async fn create_tag_table(pool: &SqlitePool, db_id: &str, table_name: &str) -> Result<()> {
+    let tag_table = format!("{}_tags", table_name);
+    let exists: Option<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(&tag_table)
+            .fetch_optional(pool)
+            .await?;
+    if exists.is_some() {
+        return Ok(());
+    }
+
+    let mut tx = pool.begin_with(BEGIN_WRITE).await?;
+
+    let legacy_query = format!(
+        r#"
+        SELECT id, tags FROM "{}"
+        WHERE tags IS NOT NULL
+          AND CASE WHEN json_valid(tags) THEN json_type(tags) <> 'array' ELSE 1 END
+    "#,
+        table_name
+    );
+    let legacy = sqlx::query(&legacy_query).fetch_all(&mut *tx).await?;
+    if !legacy.is_empty() {
+        tracing::info!(
+            "Converting tags of {} documents in {}.{}",
+            legacy.len(),
+            db_id,
+            table_name
+        );
+    }
+    let update = format!(r#"UPDATE "{}" SET tags = ? WHERE id = ?"#, table_name);
+    for row in &legacy {
+        let id: String = row.get("id");
+        let tags: String = row.get("tags");
+        sqlx::query(&update)
+            .bind(encode_tags(&decode_tags(Some(&tags))))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let statements = [
+        format!(
+            r#"
+            CREATE TABLE "{g}" (
+                doc_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (doc_id, tag)
+            ) WITHOUT ROWID
+        "#,
+            g = tag_table
+        ),
+        format!(
+            r#"CREATE INDEX IF NOT EXISTS idx_{t}_tags_tag ON "{g}"(tag)"#,
+            t = table_name,
+            g = tag_table
+        ),
+        format!(
+            r#"
+            INSERT OR IGNORE INTO "{g}" (doc_id, tag)
+            SELECT d.id, j.value FROM "{t}" d, json_each(d.tags) j
+            WHERE json_valid(d.tags)
+        "#,
+            t = table_name,
+            g = tag_table
+        ),
+        format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS {t}_tags_ai AFTER INSERT ON "{t}" BEGIN
+                INSERT OR IGNORE INTO "{g}" (doc_id, tag)
+                SELECT new.id, value FROM json_each(new.tags);
+            END
+        "#,
+            t = table_name,
+            g = tag_table
+        ),
+        format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS {t}_tags_ad AFTER DELETE ON "{t}" BEGIN
+                DELETE FROM "{g}" WHERE doc_id = old.id;
+            END
+        "#,
+            t = table_name,
+            g = tag_table
+        ),
+        format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS {t}_tags_au AFTER UPDATE OF tags ON "{t}"
+            WHEN new.tags IS NOT old.tags BEGIN
+                DELETE FROM "{g}" WHERE doc_id = old.id;
+                INSERT OR IGNORE INTO "{g}" (doc_id, tag)
+                SELECT new.id, value FROM json_each(new.tags);
+            END
+        "#,
+            t = table_name,
+            g = tag_table
+        ),
+    ];
+    for statement in &statements {
+        sqlx::query(statement).execute(&mut *tx).await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Whether a database has a table
+async fn has_table(conn: &mut SqliteConnection, table_name: &str) -> Result<bool> {
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(table_name)
+            .fetch_one(&mut *conn)
+            .await?;
+    Ok(count > 0)
+}
+
+/// Whether a table has a column, generated columns included
+async fn has_column(conn: &mut SqliteConnection, table_name: &str, column: &str) -> Result<bool> {
+    let pragma = format!(
+        r#"SELECT COUNT(*) FROM pragma_table_xinfo('{}') WHERE name = ?"#,
+        table_name
+    );
+    let count: i64 = sqlx::query_scalar(&pragma)
+        .bind(column)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(count > 0)
+}
+
+/// Add the generated column and B-tree index of a metadata index if missing
+///
+/// The column holds the field's value when it has the indexed JSON type and is
+/// NULL otherwise, so lookups through it match what `json_extract` would.
+async fn apply_metadata_index(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    index: &MetadataIndex,
+) -> Result<()> {
+    let column = index.column_name();
+    if !has_column(&mut *conn, table_name, &column).await? {
+        // Field paths are validated, so the JSON path can be inlined
+        let path = json_path(&index.field);
+        let alter = format!(
+            r#"
+            ALTER TABLE "{t}" ADD COLUMN "{c}" {ty} GENERATED ALWAYS AS (
+                CASE WHEN json_type(metadata, '{p}') IN ({types})
+                THEN json_extract(metadata, '{p}') END
+            ) VIRTUAL
+        "#,
+            t = table_name,
+            c = column,
+            ty = index.index_type.sql_type(),
+            p = path,
+            types = index.index_type.json_types()
+        );
+        sqlx::query(&alter).execute(&mut *conn).await?;
+    }
+
+    let create_index = format!(
+        r#"CREATE INDEX IF NOT EXISTS "idx_{t}_{c}" ON "{t}"("{c}")"#,
+        t = table_name,
+        c = column
+    );
+    sqlx::query(&create_index).execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Check that a vector is finite and has the expected number of dimensions
+pub fn check_vector(vector: &[f32], dimensions: usize) -> Result<()> {
+    if vector.iter().any(|v| !v.is_finite()) {
+        anyhow::bail!("Vector contains NaN or infinite values");
+    }
+
+    if vector.len() != dimensions {
+        anyhow::bail!(
+            "Vector dimension mismatch: {} (expected {})",
+            vector.len(),
+            dimensions
+        );
+    }
+
+    Ok(())
+}
+
+/// Columns selected when loading revisions (see [`row_to_revision`])
+const REVISION_COLUMNS: &str = "doc_id, rev, content, metadata, tags, recorded_at, deleted";
+
+/// Create the history table of a table
+async fn create_history_table(conn: &mut SqliteConnection, table_name: &str) -> Result<()> {
+    let create = format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS "{}_history" (
+            doc_id TEXT NOT NULL,
+            rev INTEGER NOT NULL,
+            content TEXT NOT NULL,
+            metadata TEXT,
+            tags TEXT,
+            recorded_at DATETIME NOT NULL,
+            deleted INTEGER DEFAULT 0,
+            PRIMARY KEY (doc_id, rev)
+        )
+    "#,
+        table_name
+    );
+    sqlx::query(&create).execute(&mut *conn).await?;
+
+    // Start from the current state of root documents not yet recorded as-is
+    let snapshot = format!(
+        r#"
+        INSERT INTO "{h}" (doc_id, rev, content, metadata, tags, recorded_at)
+        SELECT d.id,
+               COALESCE((SELECT MAX(rev) FROM "{h}" WHERE doc_id = d.id), 0) + 1,
+               d.content, d.metadata, d.tags, d.updated_at
+        FROM "{t}" d
+        WHERE d.is_chunk = 0 AND NOT EXISTS (
+            SELECT 1 FROM "{h}" latest
+            WHERE latest.doc_id = d.id
+              AND latest.rev = (SELECT MAX(rev) FROM "{h}" WHERE doc_id = d.id)
+              AND latest.deleted = 0
+              AND latest.content = d.content
+              AND latest.metadata IS d.metadata
+              AND latest.tags IS d.tags
+        )
+    "#,
+        h = format!("{}_history", table_name),
+        t = table_name
+    );
+    sqlx::query(&snapshot).execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Record a document's state as its next revision, unless it matches the latest one
+async fn record_revision(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    doc: &Document,
+) -> Result<()> {
+    let latest_query = format!(
+        r#"SELECT {} FROM "{}_history" WHERE doc_id = ? ORDER BY rev DESC LIMIT 1"#,
+        REVISION_COLUMNS, table_name
+    );
+    let latest = sqlx::query(&latest_query)
+        .bind(&doc.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|row| row_to_revision(&row));
+
+    if let Some(ref latest) = latest {
+        if !latest.deleted
+            && latest.content == doc.content
+            && latest.metadata == doc.metadata
+            && latest.tags == doc.tags
+        {
+            return Ok(());
+        }
+    }
+
+    let insert = format!(
+        r#"INSERT INTO "{}_history" (doc_id, rev, content, metadata, tags, recorded_at) VALUES (?, ?, ?, ?, ?, ?)"#,
+        table_name
+    );
+    sqlx::query(&insert)
+        .bind(&doc.id)
+        .bind(latest.map_or(1, |latest| latest.rev + 1))
+        .bind(&doc.content)
+        .bind(serde_json::to_string(&doc.metadata)?)
+        .bind(encode_tags(&doc.tags))
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Record the deletion of a document as its next revision
+async fn record_deletion(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    doc_id: &str,
+) -> Result<()> {
+    let insert = format!(
+        r#"
+        INSERT INTO "{h}" (doc_id, rev, content, recorded_at, deleted)
+        SELECT ?, COALESCE(MAX(rev), 0) + 1, '', ?, 1 FROM "{h}" WHERE doc_id = ?
+    "#,
+        h = format!("{}_history", table_name)
+    );
+    sqlx::query(&insert)
+        .bind(doc_id)
+        .bind(Utc::now())
+        .bind(doc_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Build a [`DocumentRevision`] from a row selected with [`REVISION_COLUMNS`]
+fn row_to_revision(row: &SqliteRow) -> DocumentRevision {
+    let metadata_json: Option<String> = row.get("metadata");
+    let tags: Option<String> = row.get("tags");
+
+    DocumentRevision {
+        doc_id: row.get("doc_id"),
+        rev: row.get("rev"),
+        content: row.get("content"),
+        metadata: metadata_json
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        tags: decode_tags(tags.as_deref()),
+        recorded_at: row.get("recorded_at"),
+        deleted: row.get::<i64, _>("deleted") != 0,
+    }
+}
+
+/// Write a batch item's document and then its chunks
+async fn write_batch_item(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    item: &BatchItem,
+    history: bool,
+) -> Result<()> {
+    upsert_document(conn, table_name, &item.document).await?;
+    if history && !item.document.is_chunk {
+        record_revision(&mut *conn, table_name, &item.document).await?;
+    }
+    for chunk in &item.chunks {
+        upsert_document(conn, table_name, chunk).await?;
+    }
+    Ok(())
+}
+
+/// Write one imported item, keeping the exported `created_at` of documents
+async fn write_import_item(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    item: &ImportItem,
+    history: bool,
+) -> Result<()> {
+    match item {
+        ImportItem::Document(doc) => {
+            upsert_document(conn, table_name, doc).await?;
+            // An upsert keeps the created_at of an existing row
+            let query = format!(r#"UPDATE "{}" SET created_at = ? WHERE id = ?"#, table_name);
+            sqlx::query(&query)
+                .bind(doc.created_at)
+                .bind(&doc.id)
+                .execute(&mut *conn)
+                .await?;
+            if history && !doc.is_chunk {
+                record_revision(&mut *conn, table_name, doc).await?;
+            }
+        }
+        ImportItem::Relation(relation) => {
+            sqlx::query(
+                r#"
+                INSERT INTO document_relations (id, source_id, target_id, relation_type, metadata, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                ON CONFLICT(id) DO UPDATE SET
+                    source_id = excluded.source_id,
+                    target_id = excluded.target_id,
+                    relation_type = excluded.relation_type,
+                    metadata = excluded.metadata,
+                    created_at = excluded.created_at
+            "#,
+            )
+            .bind(&relation.id)
+            .bind(&relation.source_id)
+            .bind(&relation.target_id)
+            .bind(&relation.relation_type)
+            .bind(serde_json::to_string(&relation.metadata)?)
+            .bind(relation.created_at)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// A live document by ID
+async fn fetch_document(
+    conn: &mut SqliteConnection,
+    db_id: &str,
+    table_name: &str,
+    id: &str,
+) -> Result<Option<Document>> {
+    let query = format!(
+        r#"
+        SELECT {}
+        FROM "{}"
+        WHERE id = ? AND {}
+    "#,
+        DOCUMENT_COLUMNS, table_name, LIVE_ROWS
+    );
+
+    let row = sqlx::query(&query).bind(id).fetch_optional(&mut *conn).await?;
+    Ok(row.map(|row| row_to_document(&row, db_id, table_name)))
+}
+
+/// Insert or update a document row, with the duplicate detection keys of a root
+/// document
+///
+/// Storing a document that is in the trash brings it back.
+async fn upsert_document(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    doc: &Document,
+) -> Result<()> {
+    // Serialize metadata
+    let metadata_json = serde_json::to_string(&doc.metadata)?;
+
+    // Serialize tags
+    let tags_json = encode_tags(&doc.tags);
+
+    // Serialize vector
+    let (vector_bytes, is_embedded, is_vectorized) = if let Some(ref vector) = doc.vector {
+        (Some(serialize_vector(vector)), 1, 1)
+    } else {
+        (None, 0, 0)
+    };
+
+    // Calculate token count if not already set (estimate: 1 token per 4 characters)
+    let token_count = doc
+        .token_count
+        .unwrap_or_else(|| (doc.content.len() as f32 / 4.0).ceil() as i32);
+
+    let query = format!(
+        r#"
+        INSERT INTO "{}" (id, content, metadata, tags, vector, created_at, updated_at, is_embedded, vectorize, is_chunk, parent_id, chunk_index, token_count, is_vectorized, embedding_model, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            content = excluded.content,
+            metadata = excluded.metadata,
+            tags = excluded.tags,
+            vector = excluded.vector,
+            updated_at = excluded.updated_at,
+            is_embedded = excluded.is_embedded,
+            vectorize = excluded.vectorize,
+            is_chunk = excluded.is_chunk,
+            parent_id = excluded.parent_id,
+            chunk_index = excluded.chunk_index,
+            token_count = excluded.token_count,
+            is_vectorized = excluded.is_vectorized,
+            embedding_model = excluded.embedding_model,
+            expires_at = excluded.expires_at,
+            deleted_at = NULL
+    "#,
+        table_name
+    );
+
+    sqlx::query(&query)
+        .bind(&doc.id)
+        .bind(&doc.content)
+        .bind(&metadata_json)
+        .bind(&tags_json)
+        .bind(&vector_bytes)
+        .bind(doc.created_at)
+        .bind(doc.updated_at)
+        .bind(is_embedded)
+        .bind(if doc.vectorize { 1 } else { 0 })
+        .bind(if doc.is_chunk { 1 } else { 0 })
+        .bind(&doc.parent_id)
+        .bind(doc.chunk_index)
+        .bind(token_count)
+        .bind(is_vectorized)
+        .bind(&doc.embedding_model)
+        .bind(doc.expires_at)
+        .execute(&mut *conn)
+        .await?;
+
+    if !doc.is_chunk {
+        upsert_dedup_keys(conn, table_name, &doc.id, &doc.content).await?;
+    }
+
+    Ok(())
+}
+
+/// Insert or update the content hash and MinHash signature of a root document
+async fn upsert_dedup_keys(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    doc_id: &str,
+    content: &str,
+) -> Result<()> {
+    let query = format!(
+        r#"
+        INSERT INTO "{}_dedup" (doc_id, content_hash, minhash) VALUES (?, ?, ?)
+        ON CONFLICT(doc_id) DO UPDATE SET
+            content_hash = excluded.content_hash,
+            minhash = excluded.minhash
+    "#,
+        table_name
+    );
+    sqlx::query(&query)
+        .bind(doc_id)
+        .bind(hash_content(content))
+        .bind(serialize_signature(&minhash_signature(content)))
+        .execute(conn)
+        .await?;
 
-        Ok(())
-    }
+    Ok(())
+}
 
-    pub async fn delete_table(&mut self, db_id: &str, table_name: &str) -> Result<()> {
-        if !is_valid_table_name(table_name) {
-            anyhow::bail!("Invalid table name: {}", table_name);
-        }
+/// Tags are stored as a JSON array, mirrored into the `{table}_tags` side table
+fn encode_tags(tags: &[String]) -> String {
+    serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string())
+}
 
-        let pool = self.get_pool(db_id).await?;
+/// Replace `from` with `to` in place, or drop it if `to` is already present
+fn replace_tag(tags: &mut Vec<String>, from: &str, to: &str) {
+    if tags.iter().any(|tag| tag == to) {
+        tags.retain(|tag| tag != from);
+    } else {
+        for tag in tags.iter_mut().filter(|tag| *tag == from) {
+            *tag = to.to_string();
+        }
+    }
+}
 
-        // Drop the main table
-        let drop_table = format!(r#"DROP TABLE IF EXISTS "{}""#, table_name);
-        sqlx::query(&drop_table).execute(pool).await?;
+/// Read a stored tags value, accepting the comma-joined format of older versions
+fn decode_tags(stored: Option<&str>) -> Vec<String> {
+    match stored {
+        Some(stored) if !stored.is_empty() => serde_json::from_str(stored)
+            .unwrap_or_else(|_| stored.split(',').map(String::from).collect()),
+        _ => Vec::new(),
+    }
+}
 
-        // Drop the FTS table
-        let drop_fts = format!(r#"DROP TABLE IF EXISTS "{}_fts""#, table_name);
-        sqlx::query(&drop_fts).execute(pool).await?;
+/// Build a [`Document`] from a row selected with [`DOCUMENT_COLUMNS`]
+fn row_to_document(row: &SqliteRow, db_id: &str, table_name: &str) -> Document {
+    let metadata_json: Option<String> = row.get("metadata");
+    let metadata: HashMap<String, serde_json::Value> = metadata_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+
+    let tags_json: Option<String> = row.get("tags");
+    let tags = decode_tags(tags_json.as_deref());
+
+    let vector_bytes: Option<Vec<u8>> = row.get("vector");
+    let vector = vector_bytes.map(|bytes| deserialize_vector(&bytes));
+
+    let is_embedded: i32 = row.get("is_embedded");
+    let vectorize: i32 = row.get("vectorize");
+    let is_chunk: i32 = row.get("is_chunk");
+    let is_vectorized: i32 = row.get("is_vectorized");
+
+    Document {
+        id: row.get("id"),
+        db: db_id.to_string(),
+        table: table_name.to_string(),
+        content: row.get("content"),
+        metadata,
+        tags,
+        vector,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        is_embedded: is_embedded == 1,
+        vectorize: vectorize == 1,
+        is_chunk: is_chunk == 1,
+        parent_id: row.get("parent_id"),
+        chunk_index: row.get("chunk_index"),
+        token_count: row.get("token_count"),
+        is_vectorized: is_vectorized == 1,
+        embedding_model: row.get("embedding_model"),
+        expires_at: row.get("expires_at"),
+    }
+}
 
-        Ok(())
+/// Build a [`DocumentRelation`] from a `document_relations` row
+fn row_to_relation(row: &SqliteRow) -> DocumentRelation {
+    let metadata_json: String = row.get("metadata");
+    let metadata = serde_json::from_str(&metadata_json).unwrap_or_default();
+
+    DocumentRelation {
+        id: row.get("id"),
+        source_id: row.get("source_id"),
+        target_id: row.get("target_id"),
+        relation_type: row.get("relation_type"),
+        metadata,
+        created_at: row.get("created_at"),
     }
+}
 
-    pub async fn delete_database(&mut self, db_id: &str) -> Result<()> {
-        // Remove pool from cache
-        self.pools.remove(db_id);
+/// Compact an index on a blocking thread once enough of it is tombstoned
+fn schedule_compaction(db_id: &str, table_name: &str, index: Arc<VectorIndex>) {
+    if !index.needs_compaction() {
+        return;
+    }
 
-        // Delete the database file
-        let db_path = format!("{}/{}.db", self.base_dir, db_id);
-        if std::path::Path::new(&db_path).exists() {
-            std::fs::remove_file(&db_path)
-                .context("Failed to delete database file")?;
+    let label = format!("{}.{}", db_id, table_name);
+    tracing::info!(
+        "Scheduling compaction of index {} ({:.0}% tombstones)",
+        label,
+        index.tombstone_ratio() * 100.0
+    );
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = index.compact() {
+            tracing::warn!("Failed to compact index {}: {}", label, e);
         }
+    });
+}
 
-        Ok(())
-    }
+/// Base file name of a table's saved index (`{db}.{table}.hnsw.*` next to `{db}.db`)
+fn index_basename(db_id: &str, table_name: &str) -> String {
+    format!("{}.{}", db_id, table_name)
 }
 
 /// Serialize vector to bytes (little-endian Float32)
@@ -1307,6 +5437,25 @@ fn deserialize_vector(bytes: &[u8]) -> Vec<f32> {
     vector
 }
 
+/// Encode a vector for export as base64 of its stored bytes
+pub fn encode_vector(vector: &[f32]) -> String {
+    BASE64_STANDARD.encode(serialize_vector(vector))
+}
+
+/// Decode a vector written by [`encode_vector`]
+pub fn decode_vector(encoded: &str) -> Result<Vec<f32>> {
+    let bytes = BASE64_STANDARD
+        .decode(encoded)
+        .context("Invalid vector encoding")?;
+    if bytes.len() % 4 != 0 {
+        anyhow::bail!(
+            "Invalid vector: {} bytes is not a whole number of f32 values",
+            bytes.len()
+        );
+    }
+    Ok(deserialize_vector(&bytes))
+}
+
 /// Calculate cosine similarity between two vectors
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
     if a.len() != b.len() {
@@ -1334,3 +5483,1236 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
 fn is_valid_table_name(name: &str) -> bool {
     !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DatabaseLimits, DedupPolicy, VectorIndexSettings};
+    use serde_json::json;
+
+    fn doc(id: &str, content: &str) -> Document {
+        Document::from_request(
+            "db",
+            "docs",
+            crate::models::StoreDocumentRequest {
+                id: Some(id.to_string()),
+                content: content.to_string(),
+                metadata: HashMap::new(),
+                tags: Vec::new(),
+                vectorize: true,
+                vector: None,
+                model: None,
+                expires_at: None,
+                ttl_seconds: None,
+            },
+        )
+    }
+
+    async fn test_store() -> DocumentStore {
+        let dir = std::env::temp_dir().join(format!("kuiperdb-store-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = DocumentStore::new(dir.to_string_lossy().to_string())
+            .await
+            .unwrap();
+
+        store.ensure_table("db", "docs").await.unwrap();
+        store
+    }
+
+    /// Store with a `docs` table that rejects documents whose content is "bad"
+    async fn store_rejecting_bad_content() -> DocumentStore {
+        let store = test_store().await;
+        let pool = store.get_pool("db").await.unwrap();
+        sqlx::query(
+            r#"CREATE TRIGGER reject_bad BEFORE INSERT ON "docs"
+               WHEN NEW.content = 'bad' BEGIN SELECT RAISE(ABORT, 'rejected'); END"#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        store
+    }
+
+    fn batch() -> Vec<BatchItem> {
+        let mut parent = BatchItem::from(doc("a", "first"));
+        let mut chunk = doc("a-0", "bad");
+        chunk.is_chunk = true;
+        chunk.parent_id = Some("a".to_string());
+        parent.chunks.push(chunk);
+
+        vec![doc("b", "second").into(), parent, doc("c", "third").into()]
+    }
+
+    async fn stored_ids(store: &DocumentStore) -> Vec<String> {
+        let mut ids: Vec<String> = store
+            .get_all_documents("db", "docs", 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|doc| doc.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_store_documents_atomic_rolls_back() {
+        let store = store_rejecting_bad_content().await;
+
+        let result = store
+            .store_documents("db", "docs", batch(), BulkMode::Atomic)
+            .await
+            .unwrap();
+
+        assert!(!result.committed);
+        assert!(result.errors[0].is_none());
+        assert!(result.errors[1].as_deref().unwrap().contains("rejected"));
+        assert!(stored_ids(&store).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_documents_best_effort_skips_failed_item() {
+        let store = store_rejecting_bad_content().await;
+
+        let result = store
+            .store_documents("db", "docs", batch(), BulkMode::BestEffort)
+            .await
+            .unwrap();
+
+        assert!(result.committed);
+        let failed: Vec<bool> = result.errors.iter().map(Option::is_some).collect();
+        assert_eq!(failed, vec![false, true, false]);
+        // The failing chunk also discards its parent document
+        assert_eq!(stored_ids(&store).await, vec!["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_documents_pages_with_cursor() {
+        let store = test_store().await;
+
+        // Same timestamp for every root, so pages are ordered by the ID tie-breaker
+        let created_at = chrono::Utc::now();
+        let mut items: Vec<BatchItem> = ["d", "a", "c", "e", "b"]
+            .into_iter()
+            .map(|id| {
+                let mut root = doc(id, id);
+                root.created_at = created_at;
+                root.into()
+            })
+            .collect();
+        let mut chunk = doc("a-0", "chunk");
+        chunk.is_chunk = true;
+        chunk.parent_id = Some("a".to_string());
+        chunk.vector = Some(vec![1.0, 0.0]);
+        items[1].chunks.push(chunk);
+        store
+            .store_documents("db", "docs", items, BulkMode::Atomic)
+            .await
+            .unwrap();
+
+        for (order, expected) in [
+            (SortOrder::Asc, ["a", "b", "c", "d", "e"]),
+            (SortOrder::Desc, ["e", "d", "c", "b", "a"]),
+        ] {
+            let mut options = ListDocumentsQuery {
+                limit: Some(2),
+                order,
+                ..Default::default()
+            };
+            let mut ids = Vec::new();
+            loop {
+                let page = store.list_documents("db", "docs", &options).await.unwrap();
+                assert!(page.documents.len() <= 2);
+                ids.extend(page.documents.into_iter().map(|doc| doc.id));
+                match page.next_cursor {
+                    Some(cursor) => options.cursor = Some(cursor),
+                    None => break,
+                }
+            }
+            assert_eq!(ids, expected);
+        }
+
+        let chunks = ListDocumentsQuery {
+            kind: DocumentKind::Chunks,
+            include_content: false,
+            ..Default::default()
+        };
+        let page = store.list_documents("db", "docs", &chunks).await.unwrap();
+        assert_eq!(page.documents.len(), 1);
+        assert_eq!(page.documents[0].content, "");
+        assert!(page.documents[0].vector.is_none());
+
+        let not_embedded = ListDocumentsQuery {
+            kind: DocumentKind::All,
+            not_embedded: true,
+            ..Default::default()
+        };
+        let page = store
+            .list_documents("db", "docs", &not_embedded)
+            .await
+            .unwrap();
+        assert_eq!(page.documents.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_patch_document_updates_or_drops_chunks() {
+        let store = test_store().await;
+
+        let mut parent = doc("a", "long text");
+        parent.vectorize = false;
+        let created_at = parent.created_at;
+        let mut item = BatchItem::from(parent);
+        let mut chunk = doc("a-0", "long");
+        chunk.is_chunk = true;
+        chunk.parent_id = Some("a".to_string());
+        item.chunks.push(chunk);
+        store
+            .store_documents("db", "docs", vec![item], BulkMode::Atomic)
+            .await
+            .unwrap();
+
+        let retag = PatchDocumentRequest {
+            add_tags: vec!["new".to_string()],
+            ..Default::default()
+        };
+        let outcome = store
+            .patch_document("db", "docs", "a", &retag)
+            .await
+            .unwrap();
+        assert!(!outcome.content_changed);
+        let chunks = store.get_chunks("db", "docs", "a").await.unwrap();
+        assert_eq!(chunks[0].tags, vec!["new"]);
+
+        let rewrite = PatchDocumentRequest {
+            content: Some("short".to_string()),
+            ..Default::default()
+        };
+        let outcome = store
+            .patch_document("db", "docs", "a", &rewrite)
+            .await
+            .unwrap();
+        assert!(outcome.content_changed);
+        let chunks = store.get_chunks("db", "docs", "a").await.unwrap();
+        assert!(chunks.is_empty());
+
+        let stored = store.get_document("db", "docs", "a").await.unwrap();
+        assert_eq!(stored.content, "short");
+        assert!(stored.vectorize);
+        assert_eq!(stored.created_at, created_at);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_patches_keep_each_others_changes() {
+        let store = test_store().await;
+        store
+            .store_document("db", "docs", doc("a", "text"))
+            .await
+            .unwrap();
+
+        let patches: Vec<PatchDocumentRequest> = (0..8)
+            .map(|n| PatchDocumentRequest {
+                add_tags: vec![format!("tag{}", n)],
+                ..Default::default()
+            })
+            .collect();
+        let outcomes = futures::future::join_all(
+            patches
+                .iter()
+                .map(|patch| store.patch_document("db", "docs", "a", patch)),
+        )
+        .await;
+        assert!(outcomes.iter().all(|outcome| outcome.is_ok()));
+
+        let mut tags = store.get_document("db", "docs", "a").await.unwrap().tags;
+        tags.sort();
+        let expected: Vec<String> = (0..8).map(|n| format!("tag{}", n)).collect();
+        assert_eq!(tags, expected);
+    }
+
+    #[tokio::test]
+    async fn test_history_records_revisions_and_restores() {
+        let store = test_store().await;
+        store
+            .store_document("db", "docs", doc("a", "first"))
+            .await
+            .unwrap();
+
+        let history = TableSettings {
+            history: true,
+            ..Default::default()
+        };
+        store
+            .set_table_settings("db", "docs", history)
+            .await
+            .unwrap();
+        let before_edit = Utc::now();
+
+        // Unchanged writes do not add revisions
+        let stored = store.get_document("db", "docs", "a").await.unwrap();
+        store.store_document("db", "docs", stored).await.unwrap();
+
+        let edit = PatchDocumentRequest {
+            content: Some("second".to_string()),
+            ..Default::default()
+        };
+        store
+            .patch_document("db", "docs", "a", &edit)
+            .await
+            .unwrap();
+        store
+            .delete_document_by_id("db", "docs", "a")
+            .await
+            .unwrap();
+
+        let revisions = store.list_revisions("db", "docs", "a").await.unwrap();
+        let summary: Vec<(i64, &str, bool)> = revisions
+            .iter()
+            .map(|r| (r.rev, r.content.as_str(), r.deleted))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(1, "first", false), (2, "second", false), (3, "", true)]
+        );
+
+        let then = store
+            .get_revision_as_of("db", "docs", "a", before_edit)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(then.content, "first");
+
+        assert!(store.restore_revision("db", "docs", "a", 3).await.is_err());
+        let restored = store.restore_revision("db", "docs", "a", 1).await.unwrap();
+        assert!(restored.content_changed);
+        let current = store.get_document("db", "docs", "a").await.unwrap();
+        assert_eq!(current.content, "first");
+        let latest = store.get_revision("db", "docs", "a", 4).await.unwrap();
+        assert_eq!(latest.unwrap().content, "first");
+    }
+
+    #[tokio::test]
+    async fn test_history_tables_hidden_but_user_tables_named_history_listed() {
+        let store = test_store().await;
+        let history = TableSettings {
+            history: true,
+            ..Default::default()
+        };
+        store
+            .set_table_settings("db", "docs", history)
+            .await
+            .unwrap();
+        store.ensure_table("db", "chat_history").await.unwrap();
+        store.ensure_table("db", "orderhistory").await.unwrap();
+
+        assert_eq!(
+            store.list_tables("db").await.unwrap(),
+            vec![
+                "chat_history".to_string(),
+                "docs".to_string(),
+                "orderhistory".to_string()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_table_settings_override_index_and_embedding() {
+        let mut store = test_store().await;
+        store.configure_indexing(true, 1000, IndexConfig::default());
+        let mut embedded = doc("a", "first");
+        embedded.vector = Some(vec![1.0, 0.0, 0.0]);
+        embedded.is_embedded = true;
+        store.store_document("db", "docs", embedded).await.unwrap();
+        assert!(!store.should_use_index("db", "docs").await.unwrap());
+
+        let always = TableSettings {
+            vector_index: VectorIndexSettings {
+                mode: Some("always".to_string()),
+                hnsw_m: Some(32),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        store
+            .set_table_settings("db", "docs", always.clone())
+            .await
+            .unwrap();
+        assert!(store.should_use_index("db", "docs").await.unwrap());
+        let (config, _) = store.table_index_config("db", "docs").await.unwrap();
+        assert_eq!(config.hnsw_m, 32);
+        // Index settings alone leave the vectors in place
+        let stored = store.get_document("db", "docs", "a").await.unwrap();
+        assert!(stored.vector.is_some());
+
+        let model = TableSettings {
+            embedding_model: Some("other-model".to_string()),
+            ..always
+        };
+        store.set_table_settings("db", "docs", model).await.unwrap();
+        let stored = store.get_document("db", "docs", "a").await.unwrap();
+        assert!(stored.vector.is_none());
+        assert!(!stored.is_embedded);
+        assert_eq!(
+            store.table_vector_dimensions("db", "docs").await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_embedded_documents_are_searchable_through_index() {
+        let mut store = test_store().await;
+        store.configure_indexing(true, 0, IndexConfig::default());
+        let mut embedded = doc("a", "first");
+        embedded.vector = Some(vec![1.0, 0.0, 0.0]);
+        embedded.is_embedded = true;
+        store.store_document("db", "docs", embedded).await.unwrap();
+
+        // The first search builds the index
+        let results = store
+            .search_vector("db", "docs", &[1.0, 0.0, 0.0], 5)
+            .await
+            .unwrap();
+        assert_eq!(results[0].0, "a");
+        assert!(store.loaded_index("db:docs").is_some());
+
+        // A vector from the embedding worker goes into the built index
+        store
+            .store_document("db", "docs", doc("b", "second"))
+            .await
+            .unwrap();
+        store
+            .update_document_vector("db", "docs", "b", &[0.0, 1.0, 0.0])
+            .await
+            .unwrap();
+        assert_eq!(store.loaded_index("db:docs").unwrap().len(), 2);
+        let results = store
+            .search_vector("db", "docs", &[0.0, 1.0, 0.0], 1)
+            .await
+            .unwrap();
+        assert_eq!(results[0].0, "b");
+
+        // Changing the content takes it out again until it is re-embedded
+        let patch = PatchDocumentRequest {
+            content: Some("changed".to_string()),
+            ..Default::default()
+        };
+        store.patch_document("db", "docs", "b", &patch).await.unwrap();
+        assert_eq!(store.loaded_index("db:docs").unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_documents_go_to_trash_until_purged() {
+        let store = test_store().await;
+        let mut chunk = doc("a-0", "first chunk");
+        chunk.is_chunk = true;
+        chunk.parent_id = Some("a".to_string());
+        let mut item = BatchItem::from(doc("a", "first"));
+        item.chunks.push(chunk);
+        store
+            .store_documents(
+                "db",
+                "docs",
+                vec![item, doc("b", "second").into()],
+                BulkMode::Atomic,
+            )
+            .await
+            .unwrap();
+        let relation = crate::models::DocumentRelation {
+            id: "r".to_string(),
+            source_id: "b".to_string(),
+            target_id: "a".to_string(),
+            relation_type: "references".to_string(),
+            metadata: HashMap::new(),
+            created_at: Utc::now(),
+        };
+        store.create_relation("db", relation).await.unwrap();
+
+        store
+            .delete_document_by_id("db", "docs", "a")
+            .await
+            .unwrap();
+
+        assert!(store.get_document("db", "docs", "a").await.is_err());
+        let chunks = store.get_chunks("db", "docs", "a").await.unwrap();
+        assert!(chunks.is_empty());
+        assert_eq!(stored_ids(&store).await, vec!["b"]);
+        let hits = store.search_fts("db", "docs", "first", 10).await.unwrap();
+        assert!(hits.is_empty());
+        let trash = store.list_trash("db", "docs", None).await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].document.id, "a");
+
+        // Restoring brings back the chunks as well
+        let restored = store.undelete_document("db", "docs", "a").await.unwrap();
+        assert_eq!(restored.unwrap().content, "first");
+        assert_eq!(store.get_chunks("db", "docs", "a").await.unwrap().len(), 1);
+        assert!(store
+            .undelete_document("db", "docs", "a")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Only documents deleted before the cutoff are purged
+        store
+            .delete_document_by_id("db", "docs", "a")
+            .await
+            .unwrap();
+        let before_delete = Utc::now() - chrono::Duration::hours(1);
+        let purged = store
+            .purge_deleted("db", "docs", before_delete)
+            .await
+            .unwrap();
+        assert_eq!(purged, 0);
+        let purged = store.purge_deleted("db", "docs", Utc::now()).await.unwrap();
+        assert_eq!(purged, 2);
+        let trash = store.list_trash("db", "docs", None).await.unwrap();
+        assert!(trash.is_empty());
+        let relations = store.get_all_relations("db").await.unwrap();
+        assert!(relations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expired_documents_are_hidden_then_reaped() {
+        let store = test_store().await;
+        let mut expired = BatchItem::from(doc("old", "stale session"));
+        expired.document.expires_at = Some(Utc::now() - chrono::Duration::seconds(1));
+        let mut chunk = doc("old-0", "stale chunk");
+        chunk.is_chunk = true;
+        chunk.parent_id = Some("old".to_string());
+        chunk.expires_at = expired.document.expires_at;
+        expired.chunks.push(chunk);
+        let mut fresh = doc("new", "fresh session");
+        fresh.expires_at = Some(Utc::now() + chrono::Duration::hours(1));
+        store
+            .store_documents("db", "docs", vec![expired, fresh.into()], BulkMode::Atomic)
+            .await
+            .unwrap();
+
+        assert!(store.get_document("db", "docs", "old").await.is_err());
+        assert!(store.get_document("db", "docs", "new").await.is_ok());
+        assert_eq!(stored_ids(&store).await, vec!["new"]);
+        let hits = store.search_fts("db", "docs", "session", 10).await.unwrap();
+        let ids: Vec<&str> = hits.iter().map(|hit| hit.0.as_str()).collect();
+        assert_eq!(ids, vec!["new"]);
+
+        assert_eq!(store.reap_expired("db", "docs").await.unwrap(), 2);
+        assert_eq!(store.reap_expired("db", "docs").await.unwrap(), 0);
+        let pool = store.get_pool("db").await.unwrap();
+        let rows: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM "docs""#)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[tokio::test]
+    async fn test_tags_are_counted_renamed_and_migrated() {
+        let store = test_store().await;
+        let mut a = doc("a", "first");
+        a.tags = vec!["ml".to_string(), "a,b".to_string()];
+        let mut b = doc("b", "second");
+        b.tags = vec!["ml".to_string(), "nlp".to_string()];
+        store.store_document("db", "docs", a).await.unwrap();
+        store.store_document("db", "docs", b).await.unwrap();
+
+        // Tags may contain commas, and filters use the side table
+        let stored = store.get_document("db", "docs", "a").await.unwrap();
+        assert_eq!(stored.tags, vec!["ml", "a,b"]);
+        let filter = SearchFilter {
+            conditions: vec![crate::filter::FilterExpr::Tag("a,b".to_string())],
+        };
+        let hits = store
+            .search_fts_filtered("db", "docs", "first OR second", 10, &filter)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+
+        let counts = store.tag_counts("db", "docs", None).await.unwrap();
+        let expected = |tag: &str, count| TagCount {
+            tag: tag.to_string(),
+            count,
+        };
+        assert_eq!(
+            counts,
+            vec![expected("ml", 2), expected("a,b", 1), expected("nlp", 1)]
+        );
+
+        // Renaming onto a tag the document already has merges the two
+        let renamed = store.rename_tag("db", "docs", "nlp", "ml").await.unwrap();
+        assert_eq!(renamed, 1);
+        let merged = store.get_document("db", "docs", "b").await.unwrap();
+        assert_eq!(merged.tags, vec!["ml"]);
+        store.rename_tag("db", "docs", "a,b", "ab").await.unwrap();
+        let counts = store
+            .tag_counts("db", "docs", Some(&["a".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(counts, vec![expected("ab", 1), expected("ml", 1)]);
+
+        // Comma-joined tags of older versions are converted on open
+        let pool = store.get_pool("db").await.unwrap();
+        for statement in [
+            "DROP TRIGGER docs_tags_ai",
+            "DROP TRIGGER docs_tags_ad",
+            "DROP TRIGGER docs_tags_au",
+            "DROP TABLE docs_tags",
+            "UPDATE docs SET tags = 'x,y' WHERE id = 'b'",
+        ] {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+        let reopened = DocumentStore::new(store.base_dir.clone()).await.unwrap();
+        let migrated = reopened.get_document("db", "docs", "b").await.unwrap();
+        assert_eq!(migrated.tags, vec!["x", "y"]);
+        let counts = reopened.tag_counts("db", "docs", None).await.unwrap();
+        assert_eq!(counts.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_metadata_index_is_used_by_filters() {
+        let store = test_store().await;
+        for (id, year) in [("a", json!(2024)), ("b", json!(2019)), ("c", json!("2024"))] {
+            let mut doc = doc(id, "paper");
+            doc.metadata.insert("year".to_string(), year);
+            store.store_document("db", "docs", doc).await.unwrap();
+        }
+
+        let index = MetadataIndex {
+            field: "year".to_string(),
+            index_type: crate::models::MetadataIndexType::Integer,
+        };
+        assert!(store
+            .create_metadata_index("db", "docs", index.clone())
+            .await
+            .unwrap());
+        assert!(!store
+            .create_metadata_index("db", "docs", index.clone())
+            .await
+            .unwrap());
+        let mut other_type = index.clone();
+        other_type.index_type = crate::models::MetadataIndexType::Text;
+        assert!(store
+            .create_metadata_index("db", "docs", other_type)
+            .await
+            .is_err());
+
+        let search = |value: serde_json::Value| {
+            let filters = HashMap::from([("year".to_string(), value)]);
+            SearchFilter::parse(&filters).unwrap()
+        };
+        let hits = store
+            .search_fts_filtered("db", "docs", "paper", 10, &search(json!({"$gte": 2020})))
+            .await
+            .unwrap();
+        let ids: Vec<&str> = hits.iter().map(|hit| hit.0.as_str()).collect();
+        assert_eq!(ids, vec!["a"]);
+        let hits = store
+            .search_fts_filtered("db", "docs", "paper", 10, &search(json!("2024")))
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+
+        let pool = store.get_pool("db").await.unwrap();
+        let plan = sqlx::query(
+            r#"EXPLAIN QUERY PLAN SELECT id FROM docs AS d WHERE d."meta.year" >= 2020"#,
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let uses_index = plan.iter().any(|step| {
+            let detail: String = step.get("detail");
+            detail.contains("idx_docs_meta.year")
+        });
+        assert!(uses_index);
+
+        assert!(store
+            .drop_metadata_index("db", "docs", "year")
+            .await
+            .unwrap());
+        let indexes = store.metadata_indexes("db", "docs").await.unwrap();
+        assert!(indexes.is_empty());
+        let pool = store.get_pool("db").await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        assert!(!has_column(&mut conn, "docs", "meta.year").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_database_can_be_restored_until_purged() {
+        let store = test_store().await;
+        store
+            .store_document("db", "docs", doc("a", "first"))
+            .await
+            .unwrap();
+
+        store.delete_database("db").await.unwrap();
+        assert!(!store.database_exists("db"));
+        assert!(store.undelete_database("db").await.unwrap());
+        let restored = store.get_document("db", "docs", "a").await.unwrap();
+        assert_eq!(restored.content, "first");
+
+        store.delete_database("db").await.unwrap();
+        let later = Utc::now() + chrono::Duration::seconds(1);
+        let purged = store.purge_deleted_databases(later).await.unwrap();
+        assert_eq!(purged, 1);
+        assert!(!store.undelete_database("db").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_table_can_be_restored_until_purged() {
+        let store = test_store().await;
+        let history = TableSettings {
+            history: true,
+            ..Default::default()
+        };
+        store
+            .set_table_settings("db", "docs", history)
+            .await
+            .unwrap();
+        let index = MetadataIndex {
+            field: "year".to_string(),
+            index_type: crate::models::MetadataIndexType::Integer,
+        };
+        store
+            .create_metadata_index("db", "docs", index.clone())
+            .await
+            .unwrap();
+        let mut first = doc("a", "quantum paper");
+        first.tags = vec!["physics".to_string()];
+        first.metadata.insert("year".to_string(), json!(2024));
+        store.store_document("db", "docs", first).await.unwrap();
+
+        store.delete_table("db", "docs").await.unwrap();
+        assert!(store.list_tables("db").await.unwrap().is_empty());
+        assert!(store.get_document("db", "docs", "a").await.is_err());
+        let trashed = store.list_deleted_tables("db").await.unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!((trashed[0].name.as_str(), trashed[0].documents), ("docs", 1));
+
+        // Restoring brings back documents, settings, history, tags and search
+        assert!(store.undelete_table("db", "docs").await.unwrap());
+        assert!(store.list_deleted_tables("db").await.unwrap().is_empty());
+        let restored = store.get_document("db", "docs", "a").await.unwrap();
+        assert_eq!(restored.content, "quantum paper");
+        assert!(store.table_settings("db", "docs").await.unwrap().history);
+        assert_eq!(store.metadata_indexes("db", "docs").await.unwrap(), vec![index]);
+        assert_eq!(store.list_revisions("db", "docs", "a").await.unwrap().len(), 1);
+        let tags = store.tag_counts("db", "docs", None).await.unwrap();
+        assert_eq!(tags[0].tag, "physics");
+        let hits = store
+            .search_fts_filtered("db", "docs", "quantum", 10, &SearchFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+
+        // A deleted table's name is free for a new table meanwhile
+        store.delete_table("db", "docs").await.unwrap();
+        store
+            .store_document("db", "docs", doc("b", "second"))
+            .await
+            .unwrap();
+        assert!(store.undelete_table("db", "docs").await.is_err());
+        store.delete_table("db", "docs").await.unwrap();
+        assert_eq!(store.list_deleted_tables("db").await.unwrap().len(), 2);
+
+        let later = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(store.purge_deleted_tables("db", later).await.unwrap(), 2);
+        assert!(store.list_deleted_tables("db").await.unwrap().is_empty());
+        assert!(!store.undelete_table("db", "docs").await.unwrap());
+        let pool = store.get_pool("db").await.unwrap();
+        let leftovers: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE name LIKE 'trash$%'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_change_log_records_mutations_in_order() {
+        let store = test_store().await;
+        store
+            .store_document("db", "docs", doc("a", "one"))
+            .await
+            .unwrap();
+        store
+            .store_document("db", "docs", doc("a", "two"))
+            .await
+            .unwrap();
+        store
+            .delete_document_by_id("db", "docs", "a")
+            .await
+            .unwrap();
+        store.undelete_document("db", "docs", "a").await.unwrap();
+        let relation = DocumentRelation {
+            id: "r1".to_string(),
+            source_id: "a".to_string(),
+            target_id: "a".to_string(),
+            relation_type: "self".to_string(),
+            metadata: HashMap::new(),
+            created_at: Utc::now(),
+        };
+        store.create_relation("db", relation).await.unwrap();
+        store.delete_relation("db", "r1").await.unwrap();
+        store.delete_table("db", "docs").await.unwrap();
+
+        let changes = store.changes_since("db", 0, None, 100).await.unwrap();
+        let log: Vec<(i64, &str, ChangeOp)> = changes
+            .iter()
+            .map(|change| (change.seq, change.id.as_str(), change.op))
+            .collect();
+        assert_eq!(
+            log,
+            vec![
+                (1, "a", ChangeOp::Insert),
+                (2, "a", ChangeOp::Update),
+                (3, "a", ChangeOp::Delete),
+                (4, "a", ChangeOp::Insert),
+                (5, "r1", ChangeOp::Insert),
+                (6, "r1", ChangeOp::Delete),
+                (7, "a", ChangeOp::Delete),
+            ]
+        );
+        assert_eq!(changes[4].kind, ChangeKind::Relation);
+        let docs_only = store
+            .changes_since("db", 3, Some("docs"), 100)
+            .await
+            .unwrap();
+        let seqs: Vec<i64> = docs_only.iter().map(|change| change.seq).collect();
+        assert_eq!(seqs, vec![4, 7]);
+
+        assert!(store.changes_retained_since("db", 0).await.unwrap());
+        let later = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(store.prune_changes("db", later).await.unwrap(), 7);
+        assert!(!store.changes_retained_since("db", 0).await.unwrap());
+        assert!(store.changes_retained_since("db", 7).await.unwrap());
+        assert_eq!(store.last_change_seq("db").await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trips_documents_and_relations() {
+        let store = test_store().await;
+        let mut parent = doc("a", "parent");
+        parent.vector = Some(vec![0.25, -1.5]);
+        parent.created_at = Utc::now() - chrono::Duration::days(3);
+        let mut chunk = doc("a-0", "chunk");
+        chunk.is_chunk = true;
+        chunk.parent_id = Some("a".to_string());
+        chunk.chunk_index = Some(0);
+        store
+            .store_document("db", "docs", parent.clone())
+            .await
+            .unwrap();
+        store.store_document("db", "docs", chunk).await.unwrap();
+        store
+            .store_document("db", "docs", doc("b", "other"))
+            .await
+            .unwrap();
+        store
+            .create_relation(
+                "db",
+                DocumentRelation {
+                    id: "r1".to_string(),
+                    source_id: "a".to_string(),
+                    target_id: "b".to_string(),
+                    relation_type: "cites".to_string(),
+                    metadata: HashMap::new(),
+                    created_at: Utc::now(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // Page through documents as the export endpoint does, then relations
+        let mut records = Vec::new();
+        let mut after: Option<(bool, String)> = None;
+        loop {
+            let position = after
+                .as_ref()
+                .map(|(is_chunk, id)| (*is_chunk, id.as_str()));
+            let page = store
+                .export_documents("db", "docs", position, 2)
+                .await
+                .unwrap();
+            let Some(ExportRecord::Document(last)) = page.last() else {
+                break;
+            };
+            after = Some((last.document.is_chunk, last.document.id.clone()));
+            records.extend(page);
+        }
+        records.extend(store.export_relations("db", "docs").await.unwrap());
+
+        let items: Vec<ImportItem> = records
+            .iter()
+            .map(|record| {
+                let line = serde_json::to_string(record).unwrap();
+                let record: ExportRecord = serde_json::from_str(&line).unwrap();
+                ImportItem::from_record(record, "db2", "copy").unwrap()
+            })
+            .collect();
+        let ids: Vec<&str> = items.iter().map(ImportItem::id).collect();
+        assert_eq!(ids, vec!["a", "b", "a-0", "r1"]);
+
+        let result = store
+            .import_records("db2", "copy", items, BulkMode::Atomic)
+            .await
+            .unwrap();
+        assert!(result.committed);
+
+        let imported = store.get_document("db2", "copy", "a").await.unwrap();
+        assert_eq!(imported.vector, parent.vector);
+        assert!(imported.is_embedded);
+        let created_at = imported.created_at.timestamp_millis();
+        assert_eq!(created_at, parent.created_at.timestamp_millis());
+        let chunks = store.get_chunks("db2", "copy", "a").await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        let relations = store.get_document_relations("db2", "b").await.unwrap();
+        assert_eq!(relations[0].id, "r1");
+
+        assert!(decode_vector("not base64!").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backup_restores_database_and_prunes() {
+        let store = test_store().await;
+        store
+            .store_document("db", "docs", doc("a", "first"))
+            .await
+            .unwrap();
+
+        let options = BackupOptions::default();
+        let first = store.backup_database("db", &options).await.unwrap();
+        assert_eq!(first.tables, vec!["docs".to_string()]);
+
+        store
+            .store_document("db", "docs", doc("b", "second"))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let second = store.backup_database("db", &options).await.unwrap();
+        let backups = store.list_backups("db").unwrap();
+        let ids: Vec<_> = backups.iter().map(|info| info.id.clone()).collect();
+        assert_eq!(ids, vec![second.id.clone(), first.id.clone()]);
+
+        store
+            .delete_document_by_id("db", "docs", "a")
+            .await
+            .unwrap();
+        let restored = store
+            .restore_database("db", Some(&first.id), true, false)
+            .await
+            .unwrap();
+        assert_eq!(restored.unwrap().id, first.id);
+        let doc_a = store.get_document("db", "docs", "a").await.unwrap();
+        assert_eq!(doc_a.content, "first");
+        assert!(store.get_document("db", "docs", "b").await.is_err());
+
+        let missing = store.restore_database("db", Some("0"), true, false).await;
+        assert!(missing.unwrap().is_none());
+
+        assert_eq!(store.prune_backups("db", 1).unwrap(), 1);
+        let ids: Vec<_> = store
+            .list_backups("db")
+            .unwrap()
+            .into_iter()
+            .map(|info| info.id)
+            .collect();
+        assert_eq!(ids, vec![second.id]);
+    }
+
+    #[tokio::test]
+    async fn test_catalog_reports_database_and_table_stats() {
+        let store = test_store().await;
+        store
+            .store_document("db", "docs", doc("a", "first"))
+            .await
+            .unwrap();
+        store
+            .store_document("db", "docs", doc("b", "second"))
+            .await
+            .unwrap();
+        store
+            .update_document_vector("db", "docs", "a", &[1.0, 0.0])
+            .await
+            .unwrap();
+        store
+            .record_embedding_failure("db", "docs", "b", "timeout")
+            .await
+            .unwrap();
+
+        let stats = store.table_stats("db", "docs").await.unwrap();
+        assert_eq!(stats.document_count, 2);
+        assert_eq!(stats.embedded_count, 1);
+        assert_eq!(stats.pending_count, 0);
+        assert_eq!(stats.failed_count, 1);
+        assert!(stats.size_bytes > 0);
+
+        let settings = DatabaseSettings {
+            description: Some("notes".to_string()),
+            ..Default::default()
+        };
+        store.set_database_settings("db", &settings).await.unwrap();
+        let info = store.database_info("db").await.unwrap();
+        assert_eq!(info.tables, vec!["docs".to_string()]);
+        assert_eq!(info.document_count, 2);
+        assert_eq!(info.failed_count, 1);
+        assert_eq!(info.settings, settings);
+        assert_eq!(store.list_databases().await.unwrap(), vec!["db".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_writes_over_database_limits_are_rejected() {
+        let store = test_store().await;
+        let settings = DatabaseSettings {
+            limits: DatabaseLimits {
+                max_documents: Some(2),
+                max_content_bytes: Some(10),
+                max_tables: Some(1),
+                max_embedding_requests_per_minute: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        store.set_database_settings("db", &settings).await.unwrap();
+
+        let quota = |e: anyhow::Error| e.downcast::<QuotaExceeded>().unwrap().kind;
+        store
+            .store_document("db", "docs", doc("a", "first"))
+            .await
+            .unwrap();
+        let e = store
+            .store_document("db", "docs", doc("b", "far too long"))
+            .await
+            .unwrap_err();
+        assert_eq!(quota(e), QuotaKind::ContentBytes);
+        let e = store
+            .store_document("db", "other", doc("b", "second"))
+            .await
+            .unwrap_err();
+        assert_eq!(quota(e), QuotaKind::Tables);
+
+        // A batch may fill the database but not go over, and updates still fit
+        let item = |id: &str| BatchItem {
+            document: doc(id, id),
+            chunks: Vec::new(),
+        };
+        let e = store
+            .store_documents("db", "docs", vec![item("b"), item("c")], BulkMode::Atomic)
+            .await
+            .unwrap_err();
+        assert_eq!(quota(e), QuotaKind::Documents);
+        store
+            .store_documents("db", "docs", vec![item("a"), item("b")], BulkMode::Atomic)
+            .await
+            .unwrap();
+        assert_eq!(store.list_tables("db").await.unwrap(), vec!["docs".to_string()]);
+
+        store.acquire_embedding_request("db").await.unwrap();
+        let e = store.acquire_embedding_request("db").await.unwrap_err();
+        assert_eq!(quota(e), QuotaKind::EmbeddingRequestsPerMinute);
+        assert_eq!(store.take_embedding_requests("db", 5).await.unwrap(), 0);
+
+        let usage = store.database_info("db").await.unwrap().usage;
+        assert_eq!(usage.documents.used, 2);
+        assert_eq!(usage.documents.limit, Some(2));
+        assert_eq!(usage.tables.used, 1);
+        assert_eq!(usage.embedding_requests_per_minute.used, 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writes_cannot_pass_document_limit_together() {
+        let store = test_store().await;
+        let mut settings = DatabaseSettings::default();
+        settings.limits.max_documents = Some(3);
+        store.set_database_settings("db", &settings).await.unwrap();
+
+        let docs: Vec<Document> = (0..8).map(|n| doc(&format!("d{}", n), "text")).collect();
+        let written = futures::future::join_all(
+            docs.into_iter().map(|doc| store.store_document("db", "docs", doc)),
+        )
+        .await;
+        assert_eq!(written.iter().filter(|result| result.is_ok()).count(), 3);
+        assert_eq!(stored_ids(&store).await.len(), 3);
+
+        // Raising the limit is seen by the next write
+        settings.limits.max_documents = Some(4);
+        store.set_database_settings("db", &settings).await.unwrap();
+        store
+            .store_document("db", "docs", doc("d8", "text"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_found_merged_and_reported() {
+        let store = test_store().await;
+        let page = "The quick brown fox jumps over the lazy dog while the farmer \
+                    watches from the porch and the sun sets behind the hills";
+        let mut first = doc("a", page);
+        first.tags = vec!["news".to_string()];
+        store.store_document("db", "docs", first).await.unwrap();
+        store
+            .store_document("db", "docs", doc("b", "Something else entirely"))
+            .await
+            .unwrap();
+
+        let exact_only = DedupSettings {
+            policy: Some(DedupPolicy::Reject),
+            ..Default::default()
+        };
+        let minhash = DedupSettings {
+            near: Some(NearDuplicateMethod::Minhash),
+            threshold: Some(0.6),
+            ..exact_only.clone()
+        };
+        let repost = doc("c", &format!("{} Shared from another site.", page.to_uppercase()));
+
+        let found = |doc: Document, settings: DedupSettings| {
+            let store = &store;
+            async move { store.find_duplicate("db", "docs", &doc, &settings).await.unwrap() }
+        };
+        let exact = found(doc("new", page), exact_only.clone()).await.unwrap();
+        assert_eq!((exact.id.as_str(), exact.kind), ("a", DuplicateKind::Exact));
+        assert!(found(doc("a", page), exact_only.clone()).await.is_none());
+        assert!(found(repost.clone(), exact_only).await.is_none());
+        let near = found(repost.clone(), minhash.clone()).await.unwrap();
+        assert_eq!((near.id.as_str(), near.kind), ("a", DuplicateKind::Near));
+
+        // Merging adds the duplicate's tags and new metadata keys only
+        let mut merged = doc("d", page);
+        merged.tags = vec!["rss".to_string()];
+        merged.metadata.insert("source".to_string(), json!("feed"));
+        let merged = store
+            .merge_duplicate("db", "docs", "a", &merged)
+            .await
+            .unwrap();
+        assert_eq!(merged.content, page);
+        assert_eq!(merged.tags, vec!["news".to_string(), "rss".to_string()]);
+        assert_eq!(merged.metadata["source"], json!("feed"));
+
+        store.store_document("db", "docs", repost).await.unwrap();
+        store.link_duplicate("db", "c", &near).await.unwrap();
+        let relations = store.get_document_relations("db", "c").await.unwrap();
+        assert_eq!(relations[0].relation_type, "duplicate_of");
+        assert_eq!(relations[0].target_id, "a");
+
+        let query = DuplicatesQuery {
+            near: Some(NearDuplicateMethod::Minhash),
+            threshold: Some(0.6),
+            limit: None,
+        };
+        let report = store.duplicate_report("db", "docs", &query).await.unwrap();
+        assert_eq!(report.duplicate_count, 1);
+        assert_eq!(report.clusters[0].ids, vec!["a".to_string(), "c".to_string()]);
+        assert_eq!(report.clusters[0].kind, DuplicateKind::Near);
+
+        // Deleted documents are no longer duplicated
+        store.delete_document_by_id("db", "docs", "c").await.unwrap();
+        let report = store.duplicate_report("db", "docs", &query).await.unwrap();
+        assert!(report.clusters.is_empty());
+        store.delete_document_by_id("db", "docs", "a").await.unwrap();
+        assert!(found(doc("new", page), minhash).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_report_by_vector_with_and_without_index() {
+        let mut store = test_store().await;
+        store.configure_indexing(true, 1000, IndexConfig::default());
+        let embedded = [
+            ("a", vec![1.0, 0.0, 0.0]),
+            ("b", vec![0.0, 1.0, 0.0]),
+            ("c", vec![0.99, 0.05, 0.0]),
+            ("d", vec![0.0, 0.0, 1.0]),
+            ("e", vec![0.0, 0.02, 1.0]),
+        ];
+        for (id, vector) in embedded {
+            let mut doc = doc(id, id);
+            doc.vector = Some(vector);
+            doc.is_embedded = true;
+            store.store_document("db", "docs", doc).await.unwrap();
+        }
+
+        let query = DuplicatesQuery {
+            near: Some(NearDuplicateMethod::Vector),
+            threshold: Some(0.95),
+            limit: None,
+        };
+        let ids = |report: &DuplicateReport| -> Vec<Vec<String>> {
+            report.clusters.iter().map(|cluster| cluster.ids.clone()).collect()
+        };
+        let expected = vec![
+            vec!["a".to_string(), "c".to_string()],
+            vec!["d".to_string(), "e".to_string()],
+        ];
+
+        let pairwise = store.duplicate_report("db", "docs", &query).await.unwrap();
+        assert_eq!(ids(&pairwise), expected);
+        assert!(!pairwise.truncated);
+
+        let always = TableSettings {
+            vector_index: VectorIndexSettings {
+                mode: Some("always".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        store
+            .set_table_settings("db", "docs", always)
+            .await
+            .unwrap();
+        assert!(store.should_use_index("db", "docs").await.unwrap());
+        let indexed = store.duplicate_report("db", "docs", &query).await.unwrap();
+        assert_eq!(ids(&indexed), expected);
+
+        // Clusters past the limit are counted but not listed
+        let limited = DuplicatesQuery {
+            limit: Some(1),
+            ..query
+        };
+        let report = store.duplicate_report("db", "docs", &limited).await.unwrap();
+        assert_eq!(ids(&report), expected[..1]);
+        assert_eq!(report.duplicate_count, 2);
+        assert!(report.truncated);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_shared_store_serves_concurrent_writes_and_reads() {
+        let store = Arc::new(test_store().await);
+
+        let mut handles = Vec::new();
+        for i in 0..16 {
+            let store = store.clone();
+            handles.push(tokio::spawn(async move {
+                let id = format!("doc-{:02}", i);
+                store
+                    .store_document("db", "docs", doc(&id, "concurrent"))
+                    .await?;
+                store.get_document("db", "docs", &id).await?;
+                store.list_tables("db").await
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        assert_eq!(stored_ids(&store).await.len(), 16);
+        let pool = store.get_pool("db").await.unwrap();
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn test_page_cursor_rejects_other_sort_field() {
+        let cursor = PageCursor {
+            sort: SortField::CreatedAt,
+            key: "2026-01-01".to_string(),
+            id: "a".to_string(),
+        }
+        .encode();
+
+        assert!(PageCursor::decode(&cursor, SortField::CreatedAt).is_ok());
+        assert!(PageCursor::decode(&cursor, SortField::UpdatedAt).is_err());
+        assert!(PageCursor::decode("not-a-cursor", SortField::CreatedAt).is_err());
+    }
+}
@@ -0,0 +1,895 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::{Config, WebhooksConfig};
+use crate::models::{
+    Change, ChangeKind, ChangeOp, CreateWebhookRequest, UpdateWebhookRequest, Webhook,
+    WebhookDelivery, WebhookDeliveryStatus, WebhookEvent, WebhookPayload,
+};
+use crate::store::{DocumentStore, BEGIN_WRITE};
+
+/// Header carrying `sha256=` and the hex HMAC-SHA256 of the body, keyed with the
+/// webhook's secret
+pub const SIGNATURE_HEADER: &str = "X-KuiperDb-Signature";
+
+/// Header carrying the event name, e.g. `document.created`
+pub const EVENT_HEADER: &str = "X-KuiperDb-Event";
+
+/// Header carrying the delivery ID, which stays the same across retries
+pub const DELIVERY_HEADER: &str = "X-KuiperDb-Delivery";
+
+/// Change log entries read per query while queuing events
+const FAN_OUT_BATCH: usize = 500;
+
+/// Deliveries attempted per dispatcher pass
+const DELIVERY_BATCH: usize = 100;
+
+/// Signature of a webhook body, as sent in [`SIGNATURE_HEADER`]
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// A time in the layout webhook timestamps are stored in, which sorts as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// A pending delivery with what is needed to send it
+struct DueDelivery {
+    id: String,
+    event: String,
+    payload: String,
+    attempts: u32,
+    url: String,
+    secret: String,
+}
+
+/// Webhook registrations and their delivery queue, kept in the global database
+///
+/// Deliveries are written before they are sent, so events queued before a
+/// restart are still delivered after it.
+pub struct WebhookManager {
+    pool: SqlitePool,
+}
+
+impl WebhookManager {
+    pub async fn new(pool: SqlitePool) -> Result<Self> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webhooks (
+                id TEXT PRIMARY KEY,
+                db TEXT NOT NULL,
+                url TEXT NOT NULL,
+                secret TEXT NOT NULL,
+                events TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                created_at DATETIME NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_webhooks_db ON webhooks(db)")
+            .execute(&pool)
+            .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id TEXT PRIMARY KEY,
+                webhook_id TEXT NOT NULL,
+                event TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at DATETIME,
+                last_status_code INTEGER,
+                last_error TEXT,
+                created_at DATETIME NOT NULL,
+                delivered_at DATETIME
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due
+            ON webhook_deliveries(status, next_attempt_at)
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook
+            ON webhook_deliveries(webhook_id, created_at DESC)
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Position in each database's change log up to which events were queued
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webhook_cursors (
+                db TEXT PRIMARY KEY,
+                last_seq INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Register a webhook, generating its secret if none is given
+    ///
+    /// `last_seq` is the database's latest change: the first webhook of a database
+    /// starts receiving events after it rather than for the whole change log.
+    pub async fn create_webhook(
+        &self,
+        db_id: &str,
+        request: CreateWebhookRequest,
+        last_seq: i64,
+    ) -> Result<Webhook> {
+        request.validate()?;
+
+        let webhook = Webhook {
+            id: Uuid::new_v4().to_string(),
+            db: db_id.to_string(),
+            url: request.url,
+            events: request.events.unwrap_or_else(|| WebhookEvent::ALL.to_vec()),
+            enabled: request.enabled,
+            secret: Some(request.secret.unwrap_or_else(|| {
+                format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+            })),
+            created_at: Utc::now(),
+        };
+
+        let mut tx = self.pool.begin_with(BEGIN_WRITE).await?;
+
+        let existing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM webhooks WHERE db = ?")
+            .bind(db_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if existing == 0 {
+            sqlx::query(
+                r#"
+                INSERT INTO webhook_cursors (db, last_seq) VALUES (?, ?)
+                ON CONFLICT(db) DO UPDATE SET last_seq = excluded.last_seq
+                "#,
+            )
+            .bind(db_id)
+            .bind(last_seq)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhooks (id, db, url, secret, events, enabled, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&webhook.id)
+        .bind(db_id)
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(serde_json::to_string(&webhook.events)?)
+        .bind(webhook.enabled)
+        .bind(timestamp(webhook.created_at))
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(webhook)
+    }
+
+    /// A database's webhooks, oldest first
+    pub async fn list_webhooks(&self, db_id: &str) -> Result<Vec<Webhook>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, db, url, events, enabled, created_at FROM webhooks
+            WHERE db = ? ORDER BY created_at ASC, id ASC
+            "#,
+        )
+        .bind(db_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_webhook).collect()
+    }
+
+    pub async fn get_webhook(&self, db_id: &str, webhook_id: &str) -> Result<Option<Webhook>> {
+        let row = sqlx::query(
+            "SELECT id, db, url, events, enabled, created_at FROM webhooks WHERE db = ? AND id = ?",
+        )
+        .bind(db_id)
+        .bind(webhook_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(row_to_webhook).transpose()
+    }
+
+    /// Change a webhook's URL, events or enabled state
+    ///
+    /// A disabled webhook is not sent anything, and gets no events queued while
+    /// disabled.
+    pub async fn update_webhook(
+        &self,
+        db_id: &str,
+        webhook_id: &str,
+        request: UpdateWebhookRequest,
+    ) -> Result<Option<Webhook>> {
+        request.validate()?;
+
+        let Some(mut webhook) = self.get_webhook(db_id, webhook_id).await? else {
+            return Ok(None);
+        };
+        if let Some(url) = request.url {
+            webhook.url = url;
+        }
+        if let Some(events) = request.events {
+            webhook.events = events;
+        }
+        if let Some(enabled) = request.enabled {
+            webhook.enabled = enabled;
+        }
+
+        sqlx::query("UPDATE webhooks SET url = ?, events = ?, enabled = ? WHERE id = ?")
+            .bind(&webhook.url)
+            .bind(serde_json::to_string(&webhook.events)?)
+            .bind(webhook.enabled)
+            .bind(webhook_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(Some(webhook))
+    }
+
+    /// Delete a webhook and its delivery log, returning whether it existed
+    pub async fn delete_webhook(&self, db_id: &str, webhook_id: &str) -> Result<bool> {
+        let mut tx = self.pool.begin_with(BEGIN_WRITE).await?;
+
+        let deleted = sqlx::query("DELETE FROM webhooks WHERE db = ? AND id = ?")
+            .bind(db_id)
+            .bind(webhook_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if deleted == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = ?")
+            .bind(webhook_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Without webhooks the database's cursor would only go stale
+        sqlx::query(
+            r#"
+            DELETE FROM webhook_cursors
+            WHERE db = ? AND NOT EXISTS (SELECT 1 FROM webhooks WHERE db = ?)
+            "#,
+        )
+        .bind(db_id)
+        .bind(db_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// A webhook's deliveries, newest first
+    pub async fn deliveries(
+        &self,
+        db_id: &str,
+        webhook_id: &str,
+        status: Option<WebhookDeliveryStatus>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let status_clause = if status.is_some() {
+            "AND d.status = ?"
+        } else {
+            ""
+        };
+        let query = format!(
+            r#"
+            SELECT d.id, d.webhook_id, d.event, d.payload, d.status, d.attempts,
+                   d.next_attempt_at, d.last_status_code, d.last_error, d.created_at,
+                   d.delivered_at
+            FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+            WHERE w.db = ? AND d.webhook_id = ? {}
+            ORDER BY d.created_at DESC, d.rowid DESC
+            LIMIT ?
+        "#,
+            status_clause
+        );
+
+        let mut sql = sqlx::query(&query).bind(db_id).bind(webhook_id);
+        if let Some(status) = status {
+            sql = sql.bind(status_name(status));
+        }
+        let rows = sql.bind(limit as i64).fetch_all(&self.pool).await?;
+
+        rows.iter().map(row_to_delivery).collect()
+    }
+
+    /// Queue an event for every enabled webhook of its database subscribed to it,
+    /// returning how many deliveries were queued
+    pub async fn enqueue(&self, payload: &WebhookPayload) -> Result<usize> {
+        let rows = sqlx::query("SELECT id, events FROM webhooks WHERE db = ? AND enabled = 1")
+            .bind(&payload.db)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut webhook_ids = Vec::new();
+        for row in rows {
+            let events: Vec<WebhookEvent> = serde_json::from_str(row.get("events"))?;
+            if events.contains(&payload.event) {
+                webhook_ids.push(row.get::<String, _>("id"));
+            }
+        }
+        if webhook_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let body = serde_json::to_string(payload)?;
+        let now = timestamp(Utc::now());
+        let mut tx = self.pool.begin_with(BEGIN_WRITE).await?;
+        for webhook_id in &webhook_ids {
+            sqlx::query(
+                r#"
+                INSERT INTO webhook_deliveries
+                    (id, webhook_id, event, payload, status, attempts, next_attempt_at, created_at)
+                VALUES (?, ?, ?, ?, 'pending', 0, ?, ?)
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(webhook_id)
+            .bind(payload.event.as_str())
+            .bind(&body)
+            .bind(&now)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(webhook_ids.len())
+    }
+
+    /// Databases with at least one webhook, enabled or not
+    async fn hooked_databases(&self) -> Result<Vec<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT DISTINCT db FROM webhooks ORDER BY db")
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    async fn cursor(&self, db_id: &str) -> Result<Option<i64>> {
+        Ok(
+            sqlx::query_scalar("SELECT last_seq FROM webhook_cursors WHERE db = ?")
+                .bind(db_id)
+                .fetch_optional(&self.pool)
+                .await?,
+        )
+    }
+
+    async fn set_cursor(&self, db_id: &str, last_seq: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_cursors (db, last_seq) VALUES (?, ?)
+            ON CONFLICT(db) DO UPDATE SET last_seq = excluded.last_seq
+            "#,
+        )
+        .bind(db_id)
+        .bind(last_seq)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Pending deliveries of enabled webhooks whose next attempt is due
+    async fn due_deliveries(&self, limit: usize) -> Result<Vec<DueDelivery>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT d.id, d.event, d.payload, d.attempts, w.url, w.secret
+            FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= ? AND w.enabled = 1
+            ORDER BY d.next_attempt_at ASC, d.created_at ASC
+            LIMIT ?
+            "#,
+        )
+        .bind(timestamp(Utc::now()))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| DueDelivery {
+                id: row.get("id"),
+                event: row.get("event"),
+                payload: row.get("payload"),
+                attempts: row.get::<i64, _>("attempts") as u32,
+                url: row.get("url"),
+                secret: row.get("secret"),
+            })
+            .collect())
+    }
+
+    /// Record the outcome of an attempt: delivered, retried after a backoff, or
+    /// failed once `max_attempts` is reached
+    async fn record_attempt(
+        &self,
+        delivery: &DueDelivery,
+        status_code: Option<u16>,
+        error: Option<String>,
+        config: &WebhooksConfig,
+    ) -> Result<WebhookDeliveryStatus> {
+        let attempts = delivery.attempts + 1;
+        let now = Utc::now();
+
+        let (status, next_attempt_at, delivered_at) = if error.is_none() {
+            (WebhookDeliveryStatus::Delivered, None, Some(now))
+        } else if attempts >= config.max_attempts {
+            (WebhookDeliveryStatus::Failed, None, None)
+        } else {
+            let backoff = config
+                .backoff_base_secs
+                .saturating_mul(1 << (attempts - 1).min(32))
+                .min(config.backoff_max_secs);
+            let next = now + chrono::Duration::seconds(backoff as i64);
+            (WebhookDeliveryStatus::Pending, Some(next), None)
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?, attempts = ?, next_attempt_at = ?, last_status_code = ?,
+                last_error = ?, delivered_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(status_name(status))
+        .bind(attempts as i64)
+        .bind(next_attempt_at.map(timestamp))
+        .bind(status_code.map(|code| code as i64))
+        .bind(error)
+        .bind(delivered_at.map(timestamp))
+        .bind(&delivery.id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(status)
+    }
+}
+
+fn status_name(status: WebhookDeliveryStatus) -> &'static str {
+    match status {
+        WebhookDeliveryStatus::Pending => "pending",
+        WebhookDeliveryStatus::Delivered => "delivered",
+        WebhookDeliveryStatus::Failed => "failed",
+    }
+}
+
+/// Build a [`Webhook`] from a `webhooks` row, leaving out its secret
+fn row_to_webhook(row: &SqliteRow) -> Result<Webhook> {
+    Ok(Webhook {
+        id: row.get("id"),
+        db: row.get("db"),
+        url: row.get("url"),
+        events: serde_json::from_str(row.get("events"))?,
+        enabled: row.get("enabled"),
+        secret: None,
+        created_at: row.get("created_at"),
+    })
+}
+
+/// Build a [`WebhookDelivery`] from a `webhook_deliveries` row
+fn row_to_delivery(row: &SqliteRow) -> Result<WebhookDelivery> {
+    let status = match row.get::<String, _>("status").as_str() {
+        "pending" => WebhookDeliveryStatus::Pending,
+        "delivered" => WebhookDeliveryStatus::Delivered,
+        "failed" => WebhookDeliveryStatus::Failed,
+        other => anyhow::bail!("Unknown delivery status: {}", other),
+    };
+
+    Ok(WebhookDelivery {
+        id: row.get("id"),
+        webhook_id: row.get("webhook_id"),
+        event: serde_json::from_value(serde_json::Value::String(row.get("event")))?,
+        status,
+        attempts: row.get::<i64, _>("attempts") as u32,
+        payload: serde_json::from_str(row.get("payload"))?,
+        last_status_code: row
+            .get::<Option<i64>, _>("last_status_code")
+            .map(|code| code as u16),
+        last_error: row.get("last_error"),
+        created_at: row.get("created_at"),
+        next_attempt_at: row.get("next_attempt_at"),
+        delivered_at: row.get("delivered_at"),
+    })
+}
+
+/// Background task that turns change log entries into webhook events and sends
+/// due deliveries
+///
+/// Events for embedding and chunking are queued where they happen; document
+/// creates, updates and deletes are read from each database's change log, so
+/// they are queued for writes from every source. Chunks don't produce events of
+/// their own.
+pub struct WebhookDispatcher {
+    webhooks: Arc<WebhookManager>,
+    store: Arc<DocumentStore>,
+    config: Arc<Config>,
+    client: reqwest::Client,
+    shutdown: Arc<tokio::sync::Notify>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        webhooks: Arc<WebhookManager>,
+        store: Arc<DocumentStore>,
+        config: Arc<Config>,
+    ) -> Self {
+        Self {
+            webhooks,
+            store,
+            config,
+            client: reqwest::Client::new(),
+            shutdown: Arc::new(tokio::sync::Notify::new()),
+        }
+    }
+
+    /// Start the dispatcher
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("Webhook dispatcher started");
+
+            let period = Duration::from_millis(self.config.webhooks.poll_interval_ms.max(1));
+            let mut interval = time::interval(period);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+            loop {
+                tokio::select! {
+                    _ = self.shutdown.notified() => {
+                        info!("Webhook dispatcher stopped");
+                        break;
+                    }
+                    _ = interval.tick() => {
+                        if let Err(e) = self.fan_out_changes().await {
+                            error!("Error queuing webhook events: {}", e);
+                        }
+                        if let Err(e) = self.deliver_due().await {
+                            error!("Error delivering webhooks: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Stop the dispatcher
+    pub fn stop(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Queue events for document changes logged since the last pass, across all
+    /// databases with webhooks. Returns how many deliveries were queued.
+    pub async fn fan_out_changes(&self) -> Result<usize> {
+        let mut queued = 0;
+        for db_name in self.webhooks.hooked_databases().await? {
+            // One failing database should not hold back the rest
+            match self.fan_out_database(&db_name).await {
+                Ok(count) => queued += count,
+                Err(e) => warn!("Failed to queue webhook events of {}: {}", db_name, e),
+            }
+        }
+        Ok(queued)
+    }
+
+    async fn fan_out_database(&self, db_name: &str) -> Result<usize> {
+        if !self.store.database_exists(db_name) {
+            return Ok(0);
+        }
+
+        let last_seq = self.store.last_change_seq(db_name).await?;
+        let mut cursor = match self.webhooks.cursor(db_name).await? {
+            // A restored database can be behind the cursor; carry on from its end
+            Some(cursor) if cursor <= last_seq => {
+                if !self.store.changes_retained_since(db_name, cursor).await? {
+                    warn!(
+                        "Change log of {} was pruned past {}; those webhook events are lost",
+                        db_name, cursor
+                    );
+                }
+                cursor
+            }
+            _ => {
+                self.webhooks.set_cursor(db_name, last_seq).await?;
+                return Ok(0);
+            }
+        };
+
+        let mut queued = 0;
+        loop {
+            let changes = self
+                .store
+                .changes_since(db_name, cursor, None, FAN_OUT_BATCH)
+                .await?;
+            for change in &changes {
+                if let Some(payload) = change_payload(&self.store, db_name, change).await {
+                    queued += self.webhooks.enqueue(&payload).await?;
+                }
+            }
+
+            let Some(last) = changes.last() else {
+                break;
+            };
+            cursor = last.seq;
+            self.webhooks.set_cursor(db_name, cursor).await?;
+
+            if changes.len() < FAN_OUT_BATCH {
+                break;
+            }
+        }
+
+        Ok(queued)
+    }
+
+    /// Send deliveries that are due, returning how many were acknowledged
+    pub async fn deliver_due(&self) -> Result<usize> {
+        let timeout = Duration::from_secs(self.config.webhooks.timeout_secs.max(1));
+
+        let mut delivered = 0;
+        for delivery in self.webhooks.due_deliveries(DELIVERY_BATCH).await? {
+            let response = self
+                .client
+                .post(&delivery.url)
+                .timeout(timeout)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, &delivery.event)
+                .header(DELIVERY_HEADER, &delivery.id)
+                .header(
+                    SIGNATURE_HEADER,
+                    sign_payload(&delivery.secret, delivery.payload.as_bytes()),
+                )
+                .body(delivery.payload.clone())
+                .send()
+                .await;
+
+            let (status_code, error) = match response {
+                Ok(response) if response.status().is_success() => {
+                    (Some(response.status().as_u16()), None)
+                }
+                Ok(response) => (
+                    Some(response.status().as_u16()),
+                    Some(format!("HTTP {}", response.status())),
+                ),
+                Err(e) => (None, Some(e.to_string())),
+            };
+
+            let status = self
+                .webhooks
+                .record_attempt(&delivery, status_code, error, &self.config.webhooks)
+                .await?;
+            match status {
+                WebhookDeliveryStatus::Delivered => delivered += 1,
+                WebhookDeliveryStatus::Failed => warn!(
+                    "Webhook delivery {} failed after {} attempts",
+                    delivery.id,
+                    delivery.attempts + 1
+                ),
+                WebhookDeliveryStatus::Pending => {}
+            }
+        }
+
+        Ok(delivered)
+    }
+}
+
+impl Drop for WebhookDispatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Payload of the event for a change to a root document, with its current
+/// version unless it was deleted
+async fn change_payload(
+    store: &DocumentStore,
+    db_name: &str,
+    change: &Change,
+) -> Option<WebhookPayload> {
+    let table_name = change.table.as_deref()?;
+    if change.kind != ChangeKind::Document || change.parent_id.is_some() {
+        return None;
+    }
+
+    let event = match change.op {
+        ChangeOp::Insert => WebhookEvent::DocumentCreated,
+        ChangeOp::Update => WebhookEvent::DocumentUpdated,
+        ChangeOp::Delete => WebhookEvent::DocumentDeleted,
+    };
+    let mut payload = WebhookPayload::new(event, db_name, table_name, &change.id);
+    payload.occurred_at = change.recorded_at;
+    payload.seq = Some(change.seq);
+    if change.op != ChangeOp::Delete {
+        payload.document = store
+            .get_document(db_name, table_name, &change.id)
+            .await
+            .ok();
+    }
+
+    Some(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StoreDocumentRequest;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accept one HTTP request, answer it with `status` and return its head and body
+    async fn receive(listener: &TcpListener, status: u16) -> (String, String) {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length {
+                    let response = format!(
+                        "HTTP/1.1 {} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                        status
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                    return (text[..end].to_lowercase(), text[end + 4..].to_string());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_delivers_signed_events_with_retries() {
+        let dir = std::env::temp_dir().join(format!("kuiperdb-webhooks-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = DocumentStore::new(dir.to_string_lossy().to_string())
+            .await
+            .unwrap();
+        store.ensure_table("db", "docs").await.unwrap();
+        let webhooks = Arc::new(
+            WebhookManager::new(store.get_global_pool().await.unwrap())
+                .await
+                .unwrap(),
+        );
+        let store = Arc::new(store);
+
+        let mut config = Config::default();
+        config.webhooks.backoff_base_secs = 0;
+        config.webhooks.max_attempts = 2;
+        let dispatcher = WebhookDispatcher::new(webhooks.clone(), store.clone(), Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hook = webhooks
+            .create_webhook(
+                "db",
+                CreateWebhookRequest {
+                    url: format!("http://{}/hook", listener.local_addr().unwrap()),
+                    events: Some(vec![WebhookEvent::DocumentCreated]),
+                    secret: Some("s3cret".to_string()),
+                    enabled: true,
+                },
+                0,
+            )
+            .await
+            .unwrap();
+
+        let request = StoreDocumentRequest {
+            id: Some("a".to_string()),
+            content: "hello".to_string(),
+            metadata: HashMap::new(),
+            tags: Vec::new(),
+            vectorize: true,
+            vector: None,
+            model: None,
+            expires_at: None,
+            ttl_seconds: None,
+        };
+        let doc = crate::models::Document::from_request("db", "docs", request);
+        store
+            .store_document("db", "docs", doc.clone())
+            .await
+            .unwrap();
+        // Not subscribed to updates
+        store
+            .store_document("db", "docs", doc.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(dispatcher.fan_out_changes().await.unwrap(), 1);
+        assert_eq!(dispatcher.fan_out_changes().await.unwrap(), 0);
+
+        // The first attempt is refused and retried
+        let (sent, received) = tokio::join!(dispatcher.deliver_due(), receive(&listener, 500));
+        assert_eq!(sent.unwrap(), 0);
+        let (sent, (head, body)) = tokio::join!(dispatcher.deliver_due(), receive(&listener, 200));
+        assert_eq!(sent.unwrap(), 1);
+        assert_eq!(received.1, body);
+
+        let signature = format!(
+            "x-kuiperdb-signature: {}",
+            sign_payload("s3cret", body.as_bytes())
+        );
+        assert!(head.contains(&signature));
+        assert!(head.contains("x-kuiperdb-event: document.created"));
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["document_id"], "a");
+        assert_eq!(payload["document"]["content"], "hello");
+
+        let deliveries = webhooks.deliveries("db", &hook.id, None, 10).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].attempts, 2);
+        assert_eq!(deliveries[0].last_status_code, Some(200));
+
+        // Disabled webhooks get no new events
+        webhooks
+            .update_webhook(
+                "db",
+                &hook.id,
+                UpdateWebhookRequest {
+                    enabled: Some(false),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let other = crate::models::Document {
+            id: "b".to_string(),
+            ..doc
+        };
+        store
+            .store_document("db", "docs", other)
+            .await
+            .unwrap();
+        assert_eq!(dispatcher.fan_out_changes().await.unwrap(), 0);
+    }
+}
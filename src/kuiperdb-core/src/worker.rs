@@ -1,24 +1,26 @@
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Mutex;
 use tokio::time;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::config::Config;
 use crate::embedder::{Embedder, OpenAIEmbedder};
+use crate::models::{WebhookEvent, WebhookPayload};
 use crate::store::DocumentStore;
+use crate::webhooks::WebhookManager;
 
 /// Background worker that processes non-embedded documents
 pub struct BackgroundWorker {
-    store: Arc<Mutex<DocumentStore>>,
+    store: Arc<DocumentStore>,
     embedder: Arc<OpenAIEmbedder>,
     config: Arc<Config>,
+    webhooks: Option<Arc<WebhookManager>>,
     shutdown: Arc<tokio::sync::Notify>,
 }
 
 impl BackgroundWorker {
     pub fn new(
-        store: Arc<Mutex<DocumentStore>>,
+        store: Arc<DocumentStore>,
         embedder: Arc<OpenAIEmbedder>,
         config: Arc<Config>,
     ) -> Self {
@@ -26,10 +28,17 @@ impl BackgroundWorker {
             store,
             embedder,
             config,
+            webhooks: None,
             shutdown: Arc::new(tokio::sync::Notify::new()),
         }
     }
 
+    /// Queue `document.embedded` webhook events for the documents embedded
+    pub fn with_webhooks(mut self, webhooks: Arc<WebhookManager>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
     /// Start the background worker
     pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
@@ -64,10 +73,7 @@ impl BackgroundWorker {
         info!("Embedding worker: checking for non-embedded documents...");
 
         // Get all databases
-        let databases = {
-            let store = self.store.lock().await;
-            store.list_databases().await?
-        };
+        let databases = self.store.list_databases().await?;
 
         info!(
             "Embedding worker: found {} databases to check",
@@ -84,10 +90,7 @@ impl BackgroundWorker {
             }
 
             // Get all tables in this database
-            let tables = {
-                let mut store = self.store.lock().await;
-                store.list_tables(&db_name).await?
-            };
+            let tables = self.store.list_tables(&db_name).await?;
 
             info!(
                 "Embedding worker: found {} tables in database '{}'",
@@ -107,18 +110,36 @@ impl BackgroundWorker {
                     db_name, table_name, remaining
                 );
 
-                // Get non-embedded documents
-                let docs = {
-                    let mut store = self.store.lock().await;
-                    store
-                        .get_non_embedded_documents(&db_name, &table_name, remaining)
-                        .await?
-                };
+                // Get non-embedded documents, and the model the table embeds with
+                let mut docs = self
+                    .store
+                    .get_non_embedded_documents(&db_name, &table_name, remaining)
+                    .await?;
+                let settings = self.store.table_settings(&db_name, &table_name).await?;
 
                 if docs.is_empty() {
                     continue;
                 }
 
+                // Stay within the database's embedding rate limit; the rest are
+                // left for a later run
+                let granted = self
+                    .store
+                    .take_embedding_requests(&db_name, docs.len())
+                    .await?;
+                if granted < docs.len() {
+                    info!(
+                        "Embedding worker: database '{}' is at its embedding rate limit, embedding {} of {} documents",
+                        db_name,
+                        granted,
+                        docs.len()
+                    );
+                    docs.truncate(granted);
+                    if docs.is_empty() {
+                        continue;
+                    }
+                }
+
                 info!(
                     "Embedding worker: found {} non-embedded documents in table '{}.{}'",
                     docs.len(),
@@ -143,25 +164,56 @@ impl BackgroundWorker {
                     let embedder = self.embedder.clone();
                     let db_name = db_name.clone();
                     let table_name = table_name.clone();
+                    let webhooks = self.webhooks.clone();
+                    let model = settings.embedding_model.clone();
+                    let dimensions = settings.embedding_dimensions;
                     let chunk = chunk.to_vec();
 
                     let handle = tokio::spawn(async move {
-                        // Embed all documents in this batch
-                        let mut vectors = Vec::new();
+                        // Embed all documents in this batch; one failing document
+                        // is recorded and retried later without failing the batch
+                        let mut embedded = Vec::new();
                         for doc in &chunk {
-                            let vector = embedder.embed(&doc.content).await?;
-                            vectors.push(vector);
+                            match embedder
+                                .embed_with(&doc.content, model.as_deref(), dimensions)
+                                .await
+                            {
+                                Ok(vector) => embedded.push((doc, vector)),
+                                Err(e) => {
+                                    warn!("Failed to embed document {}: {}", doc.id, e);
+                                    store
+                                        .record_embedding_failure(
+                                            &db_name,
+                                            &table_name,
+                                            &doc.id,
+                                            &e.to_string(),
+                                        )
+                                        .await?;
+                                }
+                            }
                         }
 
                         // Update each document with its vector
-                        for (doc, vector) in chunk.iter().zip(vectors.iter()) {
-                            let mut store = store.lock().await;
+                        for (doc, vector) in &embedded {
                             store
                                 .update_document_vector(&db_name, &table_name, &doc.id, vector)
                                 .await?;
+
+                            if let Some(ref webhooks) = webhooks {
+                                let mut payload = WebhookPayload::new(
+                                    WebhookEvent::DocumentEmbedded,
+                                    &db_name,
+                                    &table_name,
+                                    &doc.id,
+                                );
+                                payload.parent_id = doc.parent_id.clone();
+                                if let Err(e) = webhooks.enqueue(&payload).await {
+                                    warn!("Failed to queue webhook event for {}: {}", doc.id, e);
+                                }
+                            }
                         }
 
-                        Ok::<usize, anyhow::Error>(chunk.len())
+                        Ok::<usize, anyhow::Error>(embedded.len())
                     });
 
                     handles.push(handle);
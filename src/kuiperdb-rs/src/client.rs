@@ -20,6 +20,12 @@ struct AddDocumentRequest {
     tags: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     vectorize: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vector: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl_seconds: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -56,14 +62,67 @@ impl Client {
         tags: Option<Vec<String>>,
         vectorize: Option<bool>,
     ) -> Result<String> {
-        let url = format!("{}/documents", self.base_url);
-        let req = AddDocumentRequest {
+        self.post_document(AddDocumentRequest {
             id,
             content,
             metadata,
             tags,
             vectorize,
-        };
+            vector: None,
+            model: None,
+            ttl_seconds: None,
+        })
+        .await
+    }
+
+    /// Add a document with a precomputed embedding
+    ///
+    /// The server stores `vector` as the document's embedding instead of
+    /// embedding the content itself; `model` records what produced it.
+    pub async fn add_document_with_vector(
+        &self,
+        id: Option<String>,
+        content: String,
+        metadata: Option<serde_json::Value>,
+        vector: Vec<f32>,
+        model: Option<String>,
+    ) -> Result<String> {
+        self.post_document(AddDocumentRequest {
+            id,
+            content,
+            metadata,
+            tags: None,
+            vectorize: None,
+            vector: Some(vector),
+            model,
+            ttl_seconds: None,
+        })
+        .await
+    }
+
+    /// Add a document that the server deletes once `ttl` has passed
+    pub async fn add_document_with_ttl(
+        &self,
+        id: Option<String>,
+        content: String,
+        metadata: Option<serde_json::Value>,
+        ttl: std::time::Duration,
+    ) -> Result<String> {
+        self.post_document(AddDocumentRequest {
+            id,
+            content,
+            metadata,
+            tags: None,
+            vectorize: None,
+            vector: None,
+            model: None,
+            ttl_seconds: Some(ttl.as_secs().max(1)),
+        })
+        .await
+    }
+
+    async fn post_document(&self, req: AddDocumentRequest) -> Result<String> {
+        let url = format!("{}/documents", self.base_url);
 
         let response = self.client.post(&url).json(&req).send().await?;
 
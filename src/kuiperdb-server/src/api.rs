@@ -2,21 +2,34 @@ use actix_web::{web, HttpRequest, HttpResponse, Result as ActixResult};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use kuiperdb_core::cache::hash_content;
+use kuiperdb_core::chunking::{Chunker, FixedTokenChunker, MarkdownChunker};
 use kuiperdb_core::config::Config;
 use kuiperdb_core::embedder::Embedder;
+use kuiperdb_core::filter::SearchFilter;
 use kuiperdb_core::models::{
-    Document, ErrorResponse, SearchRequest, SearchResponse, StoreDocumentRequest,
+    BackupOptions, BulkItemResult, BulkMode, BulkResponse, Change, ChangeFeed, ChangesQuery,
+    ChangesResponse, CreateWebhookRequest, DatabaseSettings, DedupPolicy, Document, Duplicate,
+    DuplicateErrorResponse, DuplicateKind, DuplicatesQuery, ErrorResponse, ExportRecord,
+    ListDocumentsQuery, MetadataIndex, PatchDocumentRequest, QuotaErrorResponse,
+    RenameTagRequest, RestoreRequest, RestoreRevisionRequest, SearchFacets, SearchRequest,
+    SearchResponse, SearchType, StoreDocumentRequest, TableSettings, UpdateWebhookRequest,
+    WebhookDeliveriesQuery, WebhookEvent, WebhookPayload,
 };
-use kuiperdb_core::store::DocumentStore;
+use kuiperdb_core::quota::QuotaExceeded;
+use kuiperdb_core::store::{
+    check_vector, BatchItem, DocumentStore, ImportItem, PageCursor, PatchOutcome,
+};
+use kuiperdb_core::webhooks::WebhookManager;
 
 /// Shared application state
 pub struct AppState {
-    pub store: Arc<Mutex<DocumentStore>>,
+    pub store: Arc<DocumentStore>,
     pub embedder: Option<Arc<dyn Embedder>>,
     pub config: Arc<Config>,
+    pub webhooks: Arc<WebhookManager>,
 }
 
 /// Log file information
@@ -72,23 +85,42 @@ pub async fn store_document(
         }));
     }
 
-    // Use the cleaner add_document API
-    let mut store = state.store.lock().await;
-    let mut doc = match store
-        .add_document(&db_name, &table_name, req.0.clone())
-        .await
-    {
-        Ok(doc) => doc,
-        Err(e) => {
-            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: "failed to store document".to_string(),
+    if let Err(e) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid expiry".to_string(),
+            message: Some(e.to_string()),
+        }));
+    }
+
+    let store = &state.store;
+
+    let (settings, config) =
+        match table_config(store, &state.config, &db_name, &table_name).await {
+            Ok(table_config) => table_config,
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "failed to store document".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        };
+
+    // Client-supplied embeddings must match the table's (or configured) dimensions
+    if let Some(ref vector) = req.vector {
+        if let Err(e) = store
+            .validate_vector(&db_name, &table_name, vector, config.embedding_dimensions)
+            .await
+        {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid vector".to_string(),
                 message: Some(e.to_string()),
             }));
         }
-    };
+    }
 
-    // Check if sync embedding is requested
-    if state.config.features.embedding {
+    // Check if sync embedding is requested (not needed for client-supplied vectors)
+    let mut sync_embedder = None;
+    if state.config.features.embedding && req.vector.is_none() {
         if let Some(embedder) = &state.embedder {
             // Parse X-Client-Features header
             let client_features =
@@ -98,155 +130,110 @@ pub async fn store_document(
                 || client_features.get("embed").map(|v| v.as_str()) != Some("async");
 
             if should_embed {
-                match embedder.embed(&req.content).await {
-                    Ok(vector) => {
-                        doc.vector = Some(vector);
-                        doc.is_embedded = true;
-                    }
-                    Err(e) => {
-                        return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                            error: "embedding failed".to_string(),
-                            message: Some(e.to_string()),
-                        }));
-                    }
-                }
+                sync_embedder = Some(embedder);
             }
         }
     }
 
-    // Handle chunking if enabled and document exceeds threshold
-    let mut chunks_to_store = Vec::new();
-    if state.config.features.chunking && state.config.chunking.enabled && doc.vectorize {
-        use kuiperdb_core::chunking::{Chunker, FixedTokenChunker, MarkdownChunker};
-
-        // Select chunker based on strategy
-        let use_markdown = state.config.chunking.strategy.as_str() == "markdown";
-        
-        // Count tokens and chunk if needed
-        if use_markdown {
-            if let Ok(chunker) = MarkdownChunker::new() {
-                if let Ok(token_count) = chunker.count_tokens(&doc.content) {
-                    doc.token_count = Some(token_count as i32);
-
-                    if token_count > state.config.chunking.token_threshold {
-                        tracing::info!(
-                            "Document {} has {} tokens, chunking with markdown strategy...",
-                            doc.id,
-                            token_count
-                        );
-                        doc.vectorize = false;
-
-                        if let Ok(chunk_texts) = chunker.chunk(
-                            &doc.content,
-                            state.config.chunking.chunk_size,
-                            state.config.chunking.chunk_overlap,
-                        ) {
-                            for (idx, chunk_text) in chunk_texts.iter().enumerate() {
-                                let chunk_doc = Document {
-                                    id: Uuid::new_v4().to_string(),
-                                    db: db_name.clone(),
-                                    table: table_name.clone(),
-                                    content: chunk_text.clone(),
-                                    metadata: doc.metadata.clone(),
-                                    tags: doc.tags.clone(),
-                                    vector: None,
-                                    created_at: Utc::now(),
-                                    updated_at: Utc::now(),
-                                    is_embedded: false,
-                                    vectorize: true,
-                                    is_chunk: true,
-                                    parent_id: Some(doc.id.clone()),
-                                    chunk_index: Some(idx as i32),
-                                    token_count: chunker
-                                        .count_tokens(chunk_text)
-                                        .ok()
-                                        .map(|c| c as i32),
-                                    is_vectorized: false,
-                                };
-                                chunks_to_store.push(chunk_doc);
-                            }
+    // Count the embedding against the database's rate limit before writing anything
+    if sync_embedder.is_some() {
+        if let Err(e) = store.acquire_embedding_request(&db_name).await {
+            return Ok(store_error(&e, "failed to store document"));
+        }
+    }
 
-                            tracing::info!(
-                                "Created {} chunks for document {}",
-                                chunks_to_store.len(),
-                                doc.id
-                            );
-                        }
-                    }
-                }
+    let mut doc = Document::from_request(&db_name, &table_name, req.0.clone());
+
+    if let Some(embedder) = sync_embedder {
+        match embedder
+            .embed_with(
+                &req.content,
+                settings.embedding_model.as_deref(),
+                settings.embedding_dimensions,
+            )
+            .await
+        {
+            Ok(vector) => {
+                doc.vector = Some(vector);
+                doc.is_embedded = true;
             }
-        } else {
-            // Use fixed token chunker
-            if let Ok(chunker) = FixedTokenChunker::new() {
-                if let Ok(token_count) = chunker.count_tokens(&doc.content) {
-                    doc.token_count = Some(token_count as i32);
-
-                    if token_count > state.config.chunking.token_threshold {
-                        tracing::info!(
-                            "Document {} has {} tokens, chunking with fixed token strategy...",
-                            doc.id,
-                            token_count
-                        );
-                        doc.vectorize = false;
-
-                        if let Ok(chunk_texts) = chunker.chunk(
-                            &doc.content,
-                            state.config.chunking.chunk_size,
-                            state.config.chunking.chunk_overlap,
-                        ) {
-                            for (idx, chunk_text) in chunk_texts.iter().enumerate() {
-                                let chunk_doc = Document {
-                                    id: Uuid::new_v4().to_string(),
-                                    db: db_name.clone(),
-                                    table: table_name.clone(),
-                                    content: chunk_text.clone(),
-                                    metadata: doc.metadata.clone(),
-                                    tags: doc.tags.clone(),
-                                    vector: None,
-                                    created_at: Utc::now(),
-                                    updated_at: Utc::now(),
-                                    is_embedded: false,
-                                    vectorize: true,
-                                    is_chunk: true,
-                                    parent_id: Some(doc.id.clone()),
-                                    chunk_index: Some(idx as i32),
-                                    token_count: chunker
-                                        .count_tokens(chunk_text)
-                                        .ok()
-                                        .map(|c| c as i32),
-                                    is_vectorized: false,
-                                };
-                                chunks_to_store.push(chunk_doc);
-                            }
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "embedding failed".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        }
+    }
 
-                            tracing::info!(
-                                "Created {} chunks for document {}",
-                                chunks_to_store.len(),
-                                doc.id
-                            );
-                        }
-                    }
-                }
+    // Check for a duplicate once the document has its embedding
+    let dedup = &settings.dedup;
+    let mut duplicate = None;
+    if let Some(policy) = dedup.policy {
+        duplicate = match store.find_duplicate(&db_name, &table_name, &doc, dedup).await {
+            Ok(duplicate) => duplicate,
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "failed to store document".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        };
+
+        match (policy, duplicate.clone()) {
+            (DedupPolicy::Reject, Some(duplicate)) => {
+                return Ok(HttpResponse::Conflict().json(DuplicateErrorResponse {
+                    error: "duplicate document".to_string(),
+                    message: format!("document duplicates {}", duplicate.id),
+                    duplicate,
+                }));
+            }
+            (DedupPolicy::Merge, Some(duplicate)) => {
+                return match store
+                    .merge_duplicate(&db_name, &table_name, &duplicate.id, &doc)
+                    .await
+                {
+                    Ok(merged) => Ok(HttpResponse::Ok().json(merged)),
+                    Err(e) => Ok(store_error(&e, "failed to merge document")),
+                };
             }
+            _ => {}
         }
     }
 
-    // Update parent document with new settings
+    // Handle chunking if enabled and document exceeds threshold. Documents with a
+    // client-supplied vector are kept whole, since their chunks could not be embedded
+    // the same way.
+    let chunker = req.vector.is_none().then(|| new_chunker(&config)).flatten();
+    let chunks_to_store = match chunker {
+        Some(chunker) => chunk_document(&config, chunker.as_ref(), &mut doc),
+        None => Vec::new(),
+    };
+
     if let Err(e) = store
         .store_document(&db_name, &table_name, doc.clone())
         .await
     {
-        return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-            error: "failed to store document".to_string(),
-            message: Some(e.to_string()),
-        }));
+        return Ok(store_error(&e, "failed to store document"));
+    }
+
+    // Linked duplicates are written, then related to the document they duplicate
+    if let Some(duplicate) = duplicate {
+        if let Err(e) = store.link_duplicate(&db_name, &doc.id, &duplicate).await {
+            tracing::warn!("Failed to link duplicate {}: {}", doc.id, e);
+        }
     }
 
     // Store chunks
-    for chunk in chunks_to_store {
-        if let Err(e) = store.store_document(&db_name, &table_name, chunk).await {
-            tracing::warn!("Failed to store chunk: {}", e);
+    if !chunks_to_store.is_empty() {
+        let mut stored = 0;
+        for chunk in chunks_to_store {
+            match store.store_document(&db_name, &table_name, chunk).await {
+                Ok(_) => stored += 1,
+                Err(e) => tracing::warn!("Failed to store chunk: {}", e),
+            }
         }
+        notify_chunked(&state.webhooks, &db_name, &table_name, &doc.id, stored).await;
     }
 
     // Parse metadata level from Accept header
@@ -266,106 +253,1940 @@ pub async fn store_document(
     }
 }
 
-/// Get a document by ID
-/// GET /db/{db_name}/{table_name}/{doc_id}
-#[tracing::instrument(skip(path, state))]
-pub async fn get_document(
-    path: web::Path<(String, String, String)>,
+/// Error response for a failed store call: `413 Payload Too Large` for a write
+/// over one of the database's size limits, `429 Too Many Requests` with a
+/// `Retry-After` header over its embedding rate limit, and otherwise `500` with
+/// `error`
+fn store_error(e: &anyhow::Error, error: &str) -> HttpResponse {
+    let Some(quota) = e.downcast_ref::<QuotaExceeded>() else {
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: error.to_string(),
+            message: Some(e.to_string()),
+        });
+    };
+
+    let retry_after = quota.retry_after.map(|wait| wait.as_secs_f64().ceil() as u64);
+    let mut response = if quota.is_rate_limit() {
+        HttpResponse::TooManyRequests()
+    } else {
+        HttpResponse::PayloadTooLarge()
+    };
+    if let Some(seconds) = retry_after {
+        response.insert_header(("Retry-After", seconds.to_string()));
+    }
+
+    response.json(QuotaErrorResponse {
+        error: "quota exceeded".to_string(),
+        message: quota.to_string(),
+        quota: quota.kind,
+        limit: quota.limit,
+        used: quota.used,
+        retry_after_seconds: retry_after,
+    })
+}
+
+/// Settings of a table and the configuration they give it
+async fn table_config(
+    store: &DocumentStore,
+    config: &Config,
+    db_name: &str,
+    table_name: &str,
+) -> anyhow::Result<(TableSettings, Config)> {
+    let settings = store.table_settings(db_name, table_name).await?;
+    let config = config.for_table(&settings);
+    Ok((settings, config))
+}
+
+/// Chunker for the configured strategy, or `None` if chunking is disabled
+///
+/// Loading the tokenizer is slow, so create one per request and reuse it.
+fn new_chunker(config: &Config) -> Option<Box<dyn Chunker>> {
+    if !(config.features.chunking && config.chunking.enabled) {
+        return None;
+    }
+
+    // Select chunker based on strategy
+    let chunker: anyhow::Result<Box<dyn Chunker>> = match config.chunking.strategy.as_str() {
+        "markdown" => MarkdownChunker::new().map(|c| Box::new(c) as Box<dyn Chunker>),
+        _ => FixedTokenChunker::new().map(|c| Box::new(c) as Box<dyn Chunker>),
+    };
+    chunker.ok()
+}
+
+/// Split a document into chunks if it exceeds the token threshold. Records the
+/// document's token count, and turns off `vectorize` on a chunked document so only
+/// its chunks get embedded.
+fn chunk_document(config: &Config, chunker: &dyn Chunker, doc: &mut Document) -> Vec<Document> {
+    if !doc.vectorize {
+        return Vec::new();
+    }
+
+    // Count tokens and chunk if needed
+    let Ok(token_count) = chunker.count_tokens(&doc.content) else {
+        return Vec::new();
+    };
+    doc.token_count = Some(token_count as i32);
+
+    if token_count <= config.chunking.token_threshold {
+        return Vec::new();
+    }
+
+    tracing::info!(
+        "Document {} has {} tokens, chunking with {} strategy...",
+        doc.id,
+        token_count,
+        config.chunking.strategy
+    );
+    doc.vectorize = false;
+
+    let Ok(chunk_texts) = chunker.chunk(
+        &doc.content,
+        config.chunking.chunk_size,
+        config.chunking.chunk_overlap,
+    ) else {
+        return Vec::new();
+    };
+
+    let chunks: Vec<Document> = chunk_texts
+        .iter()
+        .enumerate()
+        .map(|(idx, chunk_text)| Document {
+            id: Uuid::new_v4().to_string(),
+            db: doc.db.clone(),
+            table: doc.table.clone(),
+            content: chunk_text.clone(),
+            metadata: doc.metadata.clone(),
+            tags: doc.tags.clone(),
+            vector: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            is_embedded: false,
+            vectorize: true,
+            is_chunk: true,
+            parent_id: Some(doc.id.clone()),
+            chunk_index: Some(idx as i32),
+            token_count: chunker.count_tokens(chunk_text).ok().map(|c| c as i32),
+            is_vectorized: false,
+            embedding_model: None,
+            expires_at: doc.expires_at,
+        })
+        .collect();
+
+    tracing::info!("Created {} chunks for document {}", chunks.len(), doc.id);
+    chunks
+}
+
+/// Largest NDJSON body accepted by the bulk endpoint
+const BULK_PAYLOAD_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Largest export accepted by the import endpoint; larger exports can be split
+/// into several imports at line boundaries
+const IMPORT_PAYLOAD_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Documents read from the store per page while streaming an export
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Bulk write query parameters
+#[derive(Deserialize)]
+pub struct BulkQuery {
+    #[serde(default)]
+    pub mode: BulkMode,
+}
+
+/// Store many documents from an NDJSON body, one `StoreDocumentRequest` per line
+/// POST /db/{db_name}/{table_name}/_bulk?mode=atomic|best_effort
+///
+/// Documents are chunked like single writes, and embedded by the background worker
+/// unless they carry a vector.
+pub async fn bulk_store_documents(
+    path: web::Path<(String, String)>,
+    query: web::Query<BulkQuery>,
+    body: web::Bytes,
     state: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
-    let (db_name, table_name, doc_id) = path.into_inner();
-    tracing::debug!(db = %db_name, table = %table_name, doc_id = %doc_id, "Getting document");
+    let (db_name, table_name) = path.into_inner();
+    let mode = query.mode;
 
-    let mut store = state.store.lock().await;
-    match store.get_document(&db_name, &table_name, &doc_id).await {
-        Ok(doc) => {
-            tracing::debug!("Document retrieved successfully");
-            Ok(HttpResponse::Ok().json(doc))
+    let body = match std::str::from_utf8(&body) {
+        Ok(body) => body,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid body".to_string(),
+                message: Some(e.to_string()),
+            }));
         }
+    };
+
+    let store = &state.store;
+
+    let (settings, config) =
+        match table_config(store, &state.config, &db_name, &table_name).await {
+            Ok(table_config) => table_config,
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "failed to store documents".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        };
+    let dedup = &settings.dedup;
+
+    // Vectors must match the table's (or configured) dimensions
+    let dimensions = match store.table_vector_dimensions(&db_name, &table_name).await {
+        Ok(dimensions) => dimensions.unwrap_or(config.embedding_dimensions),
         Err(e) => {
-            tracing::warn!("Document not found: {}", e);
-            Ok(HttpResponse::NotFound().json(ErrorResponse {
-                error: "document not found".to_string(),
-                message: None,
-            }))
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to store documents".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    let chunker = new_chunker(&config);
+
+    // Parse and validate every line; valid ones are queued for the batch
+    let mut results = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_positions = Vec::new();
+    // Duplicates merged into or linked to existing documents once the batch is
+    // committed, by position
+    let mut merges = Vec::new();
+    let mut links = Vec::new();
+    // Documents of the batch by content hash, so repeats within it are duplicates too
+    let mut batch_hashes: std::collections::HashMap<String, String> = Default::default();
+
+    let lines = body.lines().filter(|line| !line.trim().is_empty());
+    for (index, line) in lines.enumerate() {
+        let req: StoreDocumentRequest = match serde_json::from_str(line) {
+            Ok(req) => req,
+            Err(e) => {
+                results.push(bulk_item(index, None, 400, Some(e.to_string())));
+                continue;
+            }
+        };
+
+        let invalid = if req.content.is_empty() {
+            Some("content is required".to_string())
+        } else if let Err(e) = req.validate() {
+            Some(e.to_string())
+        } else if let Some(ref vector) = req.vector {
+            check_vector(vector, dimensions)
+                .err()
+                .map(|e| e.to_string())
+        } else {
+            None
+        };
+        if invalid.is_some() {
+            results.push(bulk_item(index, req.id, 400, invalid));
+            continue;
+        }
+
+        let has_vector = req.vector.is_some();
+        let mut doc = Document::from_request(&db_name, &table_name, req);
+
+        let mut duplicate_of = None;
+        if let Some(policy) = dedup.policy {
+            let content_hash = hash_content(&doc.content);
+            let duplicate = match batch_hashes.get(&content_hash) {
+                Some(id) if *id != doc.id => Some(Duplicate {
+                    id: id.clone(),
+                    kind: DuplicateKind::Exact,
+                    similarity: 1.0,
+                }),
+                _ => match store.find_duplicate(&db_name, &table_name, &doc, dedup).await {
+                    Ok(duplicate) => duplicate,
+                    Err(e) => {
+                        return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                            error: "failed to store documents".to_string(),
+                            message: Some(e.to_string()),
+                        }));
+                    }
+                },
+            };
+
+            match (policy, duplicate) {
+                (_, None) => {
+                    batch_hashes.insert(content_hash, doc.id.clone());
+                }
+                (DedupPolicy::Reject, Some(duplicate)) => {
+                    let error = format!("document duplicates {}", duplicate.id);
+                    let mut result = bulk_item(index, Some(doc.id), 409, Some(error));
+                    result.duplicate_of = Some(duplicate.id);
+                    results.push(result);
+                    continue;
+                }
+                (DedupPolicy::Merge, Some(duplicate)) => {
+                    let mut result = bulk_item(index, Some(doc.id.clone()), 200, None);
+                    result.duplicate_of = Some(duplicate.id.clone());
+                    results.push(result);
+                    merges.push((index, duplicate.id, doc));
+                    continue;
+                }
+                (DedupPolicy::Link, Some(duplicate)) => {
+                    duplicate_of = Some(duplicate.id.clone());
+                    links.push((index, duplicate));
+                }
+            }
+        }
+
+        let chunks = match chunker {
+            Some(ref chunker) if !has_vector => chunk_document(&config, chunker.as_ref(), &mut doc),
+            _ => Vec::new(),
+        };
+
+        let mut result = bulk_item(index, Some(doc.id.clone()), 201, None);
+        result.chunks = Some(chunks.len());
+        result.duplicate_of = duplicate_of;
+        results.push(result);
+        batch_positions.push(index);
+        batch.push(BatchItem {
+            document: doc,
+            chunks,
+        });
+    }
+
+    if results.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "no documents".to_string(),
+            message: Some("expected one JSON document per line".to_string()),
+        }));
+    }
+
+    let invalid_count = results.len() - batch.len() - merges.len();
+    let mut committed = false;
+
+    if mode == BulkMode::BestEffort || invalid_count == 0 {
+        let outcome = match store
+            .store_documents(&db_name, &table_name, batch, mode)
+            .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => return Ok(store_error(&e, "failed to store documents")),
+        };
+
+        committed = outcome.committed;
+        for (position, error) in batch_positions.iter().zip(outcome.errors) {
+            if let Some(error) = error {
+                let result = &mut results[*position];
+                result.status = 500;
+                result.error = Some(error);
+                result.chunks = None;
+            }
+        }
+
+        if committed {
+            for result in results.iter().filter(|r| r.status == 201) {
+                if let (Some(id), Some(chunks @ 1..)) = (&result.id, result.chunks) {
+                    notify_chunked(&state.webhooks, &db_name, &table_name, id, chunks).await;
+                }
+            }
+
+            let duplicates = BulkDuplicates { merges, links };
+            apply_bulk_duplicates(store, &db_name, &table_name, &mut results, duplicates).await;
         }
     }
+
+    // Nothing from an uncommitted batch was written
+    if !committed {
+        for result in results.iter_mut().filter(|r| matches!(r.status, 200 | 201)) {
+            result.status = 424;
+            result.error = Some("not written: another item in the batch failed".to_string());
+            result.chunks = None;
+        }
+    }
+
+    let succeeded = results
+        .iter()
+        .filter(|r| matches!(r.status, 200 | 201))
+        .count();
+    let response = BulkResponse {
+        mode,
+        committed,
+        total: results.len(),
+        succeeded,
+        failed: results.len() - succeeded,
+        items: results,
+    };
+
+    if committed {
+        Ok(HttpResponse::Ok().json(response))
+    } else if invalid_count > 0 {
+        Ok(HttpResponse::BadRequest().json(response))
+    } else {
+        Ok(HttpResponse::InternalServerError().json(response))
+    }
 }
 
-/// Delete a document
-/// DELETE /db/{db_name}/{table_name}/{doc_id}
-#[tracing::instrument(skip(path, state))]
-pub async fn delete_document(
-    path: web::Path<(String, String, String)>,
+/// Duplicates found in a bulk write, by the position of their item
+struct BulkDuplicates {
+    /// Existing document each duplicate is merged into
+    merges: Vec<(usize, String, Document)>,
+    links: Vec<(usize, Duplicate)>,
+}
+
+/// Merge and link the duplicates of a committed bulk write, failing the items
+/// that could not be merged
+async fn apply_bulk_duplicates(
+    store: &DocumentStore,
+    db_name: &str,
+    table_name: &str,
+    results: &mut [BulkItemResult],
+    duplicates: BulkDuplicates,
+) {
+    for (position, existing_id, doc) in duplicates.merges {
+        if let Err(e) = store
+            .merge_duplicate(db_name, table_name, &existing_id, &doc)
+            .await
+        {
+            results[position].status = 500;
+            results[position].error = Some(e.to_string());
+        }
+    }
+
+    for (position, duplicate) in duplicates.links {
+        let result = &results[position];
+        let Some(id) = result.id.as_deref().filter(|_| result.status == 201) else {
+            continue;
+        };
+        if let Err(e) = store.link_duplicate(db_name, id, &duplicate).await {
+            tracing::warn!("Failed to link duplicate {}: {}", id, e);
+        }
+    }
+}
+
+/// Result for one bulk item
+fn bulk_item(
+    index: usize,
+    id: Option<String>,
+    status: u16,
+    error: Option<String>,
+) -> BulkItemResult {
+    BulkItemResult {
+        index,
+        id,
+        status,
+        error,
+        chunks: None,
+        duplicate_of: None,
+    }
+}
+
+/// Position of a streamed export
+enum ExportState {
+    /// Documents after the `(is_chunk, id)` of the last one sent
+    Documents(Option<(bool, String)>),
+    Relations,
+    Done,
+}
+
+/// Export a table as NDJSON, one `ExportRecord` per line
+/// GET /db/{db_name}/{table_name}/_export
+///
+/// Documents are streamed a page at a time, roots before chunks, with their
+/// vectors; the relations touching them follow.
+pub async fn export_table(
+    path: web::Path<(String, String)>,
     state: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
-    let (db_name, table_name, doc_id) = path.into_inner();
-    tracing::debug!(db = %db_name, table = %table_name, doc_id = %doc_id, "Deleting document");
+    let (db_name, table_name) = path.into_inner();
 
-    let mut store = state.store.lock().await;
-    match store
-        .delete_document_by_id(&db_name, &table_name, &doc_id)
-        .await
-    {
-        Ok(_) => {
-            tracing::info!("Document deleted successfully");
-            Ok(HttpResponse::NoContent().finish())
+    let exists = state.store.database_exists(&db_name)
+        && state
+            .store
+            .table_exists(&db_name, &table_name)
+            .await
+            .unwrap_or(false);
+    if !exists {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "table not found".to_string(),
+            message: None,
+        }));
+    }
+
+    let store = state.store.clone();
+    let lines = futures::stream::unfold(ExportState::Documents(None), move |position| {
+        let store = store.clone();
+        let db_name = db_name.clone();
+        let table_name = table_name.clone();
+        async move {
+            let (records, next) = match position {
+                ExportState::Documents(after) => {
+                    let after_ref = after
+                        .as_ref()
+                        .map(|(is_chunk, id)| (*is_chunk, id.as_str()));
+                    match store
+                        .export_documents(&db_name, &table_name, after_ref, EXPORT_PAGE_SIZE)
+                        .await
+                    {
+                        Ok(records) => {
+                            let next = match records.last() {
+                                Some(ExportRecord::Document(last)) => ExportState::Documents(Some(
+                                    (last.document.is_chunk, last.document.id.clone()),
+                                )),
+                                _ => ExportState::Relations,
+                            };
+                            (Ok(records), next)
+                        }
+                        Err(e) => (Err(e), ExportState::Done),
+                    }
+                }
+                ExportState::Relations => (
+                    store.export_relations(&db_name, &table_name).await,
+                    ExportState::Done,
+                ),
+                ExportState::Done => return None,
+            };
+
+            // An error ends the stream, which aborts the response
+            let chunk = records.and_then(|records| {
+                let mut chunk = Vec::new();
+                for record in records {
+                    serde_json::to_writer(&mut chunk, &record)?;
+                    chunk.push(b'\n');
+                }
+                Ok(web::Bytes::from(chunk))
+            });
+            let next = if chunk.is_err() {
+                ExportState::Done
+            } else {
+                next
+            };
+            Some((
+                chunk.map_err(actix_web::error::ErrorInternalServerError),
+                next,
+            ))
         }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(lines))
+}
+
+/// Import an export into a table, keeping IDs, timestamps and vectors
+/// POST /db/{db_name}/{table_name}/_import?mode=atomic|best_effort
+///
+/// Imported documents are neither chunked nor embedded again. Lines are reported
+/// like bulk writes, with the relation ID as the ID of relation lines.
+pub async fn import_table(
+    path: web::Path<(String, String)>,
+    query: web::Query<BulkQuery>,
+    body: web::Bytes,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let mode = query.mode;
+
+    let body = match std::str::from_utf8(&body) {
+        Ok(body) => body,
         Err(e) => {
-            tracing::error!("Failed to delete document: {}", e);
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: "failed to delete document".to_string(),
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid body".to_string(),
                 message: Some(e.to_string()),
-            }))
+            }));
         }
-    }
-}
+    };
 
-/// DELETE /db/{db_name}/{table_name}
-pub async fn delete_table(
+    let store = &state.store;
+
+    let config = match table_config(store, &state.config, &db_name, &table_name).await {
+        Ok((_, config)) => config,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to import".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    // Vectors must match the table's (or configured) dimensions
+    let dimensions = match store.table_vector_dimensions(&db_name, &table_name).await {
+        Ok(dimensions) => dimensions.unwrap_or(config.embedding_dimensions),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to import".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    // Parse and validate every line; valid ones are queued for the batch
+    let mut results = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_positions = Vec::new();
+
+    let lines = body.lines().filter(|line| !line.trim().is_empty());
+    for (index, line) in lines.enumerate() {
+        let item = serde_json::from_str::<ExportRecord>(line)
+            .map_err(anyhow::Error::from)
+            .and_then(|record| ImportItem::from_record(record, &db_name, &table_name));
+        let item = match item {
+            Ok(item) => item,
+            Err(e) => {
+                results.push(bulk_item(index, None, 400, Some(e.to_string())));
+                continue;
+            }
+        };
+
+        let invalid = match item {
+            ImportItem::Document(Document {
+                vector: Some(ref vector),
+                ..
+            }) => check_vector(vector, dimensions)
+                .err()
+                .map(|e| e.to_string()),
+            _ => None,
+        };
+        if invalid.is_some() {
+            results.push(bulk_item(index, Some(item.id().to_string()), 400, invalid));
+            continue;
+        }
+
+        results.push(bulk_item(index, Some(item.id().to_string()), 201, None));
+        batch_positions.push(index);
+        batch.push(item);
+    }
+
+    if results.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "no records".to_string(),
+            message: Some("expected one exported record per line".to_string()),
+        }));
+    }
+
+    let invalid_count = results.len() - batch.len();
+    let mut committed = false;
+
+    if mode == BulkMode::BestEffort || invalid_count == 0 {
+        let outcome = match store
+            .import_records(&db_name, &table_name, batch, mode)
+            .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => return Ok(store_error(&e, "failed to import")),
+        };
+
+        committed = outcome.committed;
+        for (position, error) in batch_positions.iter().zip(outcome.errors) {
+            if let Some(error) = error {
+                let result = &mut results[*position];
+                result.status = 500;
+                result.error = Some(error);
+            }
+        }
+    }
+
+    // Nothing from an uncommitted batch was written
+    if !committed {
+        for result in results.iter_mut().filter(|r| r.status == 201) {
+            result.status = 424;
+            result.error = Some("not written: another item in the batch failed".to_string());
+        }
+    }
+
+    let succeeded = results.iter().filter(|r| r.status == 201).count();
+    let response = BulkResponse {
+        mode,
+        committed,
+        total: results.len(),
+        succeeded,
+        failed: results.len() - succeeded,
+        items: results,
+    };
+
+    if committed {
+        Ok(HttpResponse::Ok().json(response))
+    } else if invalid_count > 0 {
+        Ok(HttpResponse::BadRequest().json(response))
+    } else {
+        Ok(HttpResponse::InternalServerError().json(response))
+    }
+}
+
+/// Get a document by ID
+/// GET /db/{db_name}/{table_name}/{doc_id}
+#[tracing::instrument(skip(path, state))]
+pub async fn get_document(
+    path: web::Path<(String, String, String)>,
+    query: web::Query<DocumentVersionQuery>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, doc_id) = path.into_inner();
+    tracing::debug!(db = %db_name, table = %table_name, doc_id = %doc_id, "Getting document");
+
+    let store = &state.store;
+
+    if query.rev.is_some() || query.as_of.is_some() {
+        return get_document_revision(store, &db_name, &table_name, &doc_id, &query).await;
+    }
+
+    match store.get_document(&db_name, &table_name, &doc_id).await {
+        Ok(doc) => {
+            tracing::debug!("Document retrieved successfully");
+            Ok(HttpResponse::Ok().json(doc))
+        }
+        Err(e) => {
+            tracing::warn!("Document not found: {}", e);
+            Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "document not found".to_string(),
+                message: None,
+            }))
+        }
+    }
+}
+
+/// Partially update a document
+/// PATCH /db/{db_name}/{table_name}/{doc_id}
+///
+/// Changed content is re-chunked here and embedded by the background worker.
+pub async fn patch_document(
+    path: web::Path<(String, String, String)>,
+    req: web::Json<PatchDocumentRequest>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, doc_id) = path.into_inner();
+    tracing::debug!(db = %db_name, table = %table_name, doc_id = %doc_id, "Patching document");
+
+    if let Err(e) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid patch".to_string(),
+            message: Some(e.to_string()),
+        }));
+    }
+
+    let store = &state.store;
+    if store
+        .get_document(&db_name, &table_name, &doc_id)
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "document not found".to_string(),
+            message: None,
+        }));
+    }
+
+    let outcome = match store
+        .patch_document(&db_name, &table_name, &doc_id, &req)
+        .await
+    {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to update document".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    match rechunk_revised(store, &state.config, &state.webhooks, outcome).await {
+        Ok(doc) => Ok(HttpResponse::Ok().json(doc)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to store chunks".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Chunk a patched or restored root document again if its content changed
+///
+/// Its new embedding is left to the background worker.
+async fn rechunk_revised(
+    store: &DocumentStore,
+    config: &Config,
+    webhooks: &WebhookManager,
+    outcome: PatchOutcome,
+) -> anyhow::Result<Document> {
+    let mut doc = outcome.document;
+    if !outcome.content_changed || doc.is_chunk {
+        return Ok(doc);
+    }
+
+    let (_, config) = table_config(store, config, &doc.db, &doc.table).await?;
+    if let Some(chunker) = new_chunker(&config) {
+        let chunks = chunk_document(&config, chunker.as_ref(), &mut doc);
+        if !chunks.is_empty() {
+            let count = chunks.len();
+            let item = BatchItem {
+                document: doc.clone(),
+                chunks,
+            };
+            let (db_name, table_name) = (doc.db.clone(), doc.table.clone());
+            store
+                .store_documents(&db_name, &table_name, vec![item], BulkMode::Atomic)
+                .await?;
+            notify_chunked(webhooks, &db_name, &table_name, &doc.id, count).await;
+        }
+    }
+
+    Ok(doc)
+}
+
+/// Queue `document.chunked` webhook events once a document's chunks are written
+///
+/// A failure to queue is logged rather than failing the write, which already
+/// happened.
+async fn notify_chunked(
+    webhooks: &WebhookManager,
+    db_name: &str,
+    table_name: &str,
+    doc_id: &str,
+    chunks: usize,
+) {
+    let mut payload =
+        WebhookPayload::new(WebhookEvent::DocumentChunked, db_name, table_name, doc_id);
+    payload.chunks = Some(chunks);
+    if let Err(e) = webhooks.enqueue(&payload).await {
+        tracing::warn!("Failed to queue webhook event for {}: {}", doc_id, e);
+    }
+}
+
+/// Get the settings of a table
+/// GET /db/{db_name}/{table_name}/_settings
+pub async fn get_table_settings(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let store = &state.store;
+
+    match store.table_settings(&db_name, &table_name).await {
+        Ok(settings) => Ok(HttpResponse::Ok().json(settings)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to load settings".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Table settings query parameters
+#[derive(Deserialize)]
+pub struct TableSettingsQuery {
+    #[serde(default)]
+    pub reembed: bool,
+}
+
+/// Replace the settings of a table
+/// PUT /db/{db_name}/{table_name}/_settings?reembed=true
+///
+/// Changing the embedding model or dimensions of a table holding vectors clears
+/// them all, so it is refused unless `reembed` is set.
+pub async fn put_table_settings(
+    path: web::Path<(String, String)>,
+    query: web::Query<TableSettingsQuery>,
+    req: web::Json<TableSettings>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+
+    if let Err(e) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid settings".to_string(),
+            message: Some(e.to_string()),
+        }));
+    }
+
+    let store = &state.store;
+
+    if !query.reembed {
+        let previous = match store.table_settings(&db_name, &table_name).await {
+            Ok(previous) => previous,
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "failed to load settings".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        };
+        if previous.changes_embedding(&req) {
+            match store.table_vector_dimensions(&db_name, &table_name).await {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    return Ok(HttpResponse::Conflict().json(ErrorResponse {
+                        error: "table has embeddings".to_string(),
+                        message: Some(
+                            "changing the embedding model or dimensions clears every vector \
+                             in the table; pass reembed=true to proceed"
+                                .to_string(),
+                        ),
+                    }));
+                }
+                Err(e) => {
+                    return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                        error: "failed to save settings".to_string(),
+                        message: Some(e.to_string()),
+                    }));
+                }
+            }
+        }
+    }
+
+    match store
+        .set_table_settings(&db_name, &table_name, req.0.clone())
+        .await
+    {
+        Ok(()) => Ok(HttpResponse::Ok().json(req.0)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to save settings".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List the metadata indexes of a table
+/// GET /db/{db_name}/{table_name}/_indexes
+pub async fn list_metadata_indexes(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let store = &state.store;
+
+    match store.metadata_indexes(&db_name, &table_name).await {
+        Ok(indexes) => Ok(HttpResponse::Ok().json(serde_json::json!({ "indexes": indexes }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to load indexes".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Index a metadata field of a table
+/// POST /db/{db_name}/{table_name}/_indexes
+pub async fn create_metadata_index(
+    path: web::Path<(String, String)>,
+    req: web::Json<MetadataIndex>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let index = req.into_inner();
+
+    if let Err(e) = index.validate() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid index".to_string(),
+            message: Some(e.to_string()),
+        }));
+    }
+
+    let store = &state.store;
+    let conflict = match store.metadata_indexes(&db_name, &table_name).await {
+        Ok(indexes) => indexes
+            .into_iter()
+            .any(|existing| existing.field == index.field && existing != index),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to load indexes".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+    if conflict {
+        return Ok(HttpResponse::Conflict().json(ErrorResponse {
+            error: "field already indexed".to_string(),
+            message: Some("drop the existing index to change its type".to_string()),
+        }));
+    }
+
+    match store
+        .create_metadata_index(&db_name, &table_name, index.clone())
+        .await
+    {
+        Ok(true) => Ok(HttpResponse::Created().json(index)),
+        Ok(false) => Ok(HttpResponse::Ok().json(index)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to create index".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Drop a metadata index of a table
+/// DELETE /db/{db_name}/{table_name}/_indexes/{field}
+pub async fn drop_metadata_index(
+    path: web::Path<(String, String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, field) = path.into_inner();
+    let store = &state.store;
+
+    match store
+        .drop_metadata_index(&db_name, &table_name, &field)
+        .await
+    {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "index not found".to_string(),
+            message: Some(field),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to drop index".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Error response if a table does not keep history
+async fn history_disabled(
+    store: &DocumentStore,
+    db_name: &str,
+    table_name: &str,
+) -> Option<HttpResponse> {
+    match store.table_settings(db_name, table_name).await {
+        Ok(settings) if settings.history => None,
+        Ok(_) => Some(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "history not enabled".to_string(),
+            message: Some(format!(
+                "enable it with PUT /db/{}/{}/_settings",
+                db_name, table_name
+            )),
+        })),
+        Err(e) => Some(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to load settings".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Parse a timestamp given as Unix milliseconds or RFC 3339
+fn parse_timestamp(value: &str) -> Option<chrono::DateTime<Utc>> {
+    match value.parse::<i64>() {
+        Ok(millis) => chrono::DateTime::from_timestamp_millis(millis),
+        Err(_) => chrono::DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc)),
+    }
+}
+
+/// List the revisions of a document
+/// GET /db/{db_name}/{table_name}/{doc_id}/_revisions
+pub async fn list_revisions(
+    path: web::Path<(String, String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, doc_id) = path.into_inner();
+    let store = &state.store;
+    if let Some(response) = history_disabled(store, &db_name, &table_name).await {
+        return Ok(response);
+    }
+
+    let revisions = store
+        .list_revisions(&db_name, &table_name, &doc_id)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Query error: {}", e)))?;
+    if revisions.is_empty() {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "document not found".to_string(),
+            message: None,
+        }));
+    }
+
+    let revisions: Vec<serde_json::Value> = revisions
+        .iter()
+        .map(|revision| {
+            serde_json::json!({
+                "rev": revision.rev,
+                "recorded_at": revision.recorded_at.timestamp_millis(),
+                "deleted": revision.deleted,
+                "content_hash": kuiperdb_core::cache::hash_content(&revision.content),
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": doc_id,
+        "revisions": revisions,
+    })))
+}
+
+/// Revision diff query parameters
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub from: i64,
+    pub to: Option<i64>, // Defaults to the latest revision
+}
+
+/// Compare two revisions of a document
+/// GET /db/{db_name}/{table_name}/{doc_id}/_diff?from=N&to=M
+pub async fn diff_revisions(
+    path: web::Path<(String, String, String)>,
+    query: web::Query<DiffQuery>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, doc_id) = path.into_inner();
+    let store = &state.store;
+    if let Some(response) = history_disabled(store, &db_name, &table_name).await {
+        return Ok(response);
+    }
+
+    let revisions = store
+        .list_revisions(&db_name, &table_name, &doc_id)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Query error: {}", e)))?;
+
+    let to_rev = query
+        .to
+        .or_else(|| revisions.last().map(|revision| revision.rev));
+    let find = |rev: Option<i64>| revisions.iter().find(|revision| Some(revision.rev) == rev);
+    match (find(Some(query.from)), find(to_rev)) {
+        (Some(from), Some(to)) => {
+            Ok(HttpResponse::Ok().json(kuiperdb_core::history::diff_revisions(from, to)))
+        }
+        _ => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "revision not found".to_string(),
+            message: None,
+        })),
+    }
+}
+
+/// Restore an old revision of a document, which chunks and embeds it again
+/// POST /db/{db_name}/{table_name}/{doc_id}/_restore
+pub async fn restore_revision(
+    path: web::Path<(String, String, String)>,
+    req: web::Json<RestoreRevisionRequest>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, doc_id) = path.into_inner();
+    let store = &state.store;
+    if let Some(response) = history_disabled(store, &db_name, &table_name).await {
+        return Ok(response);
+    }
+
+    match store
+        .get_revision(&db_name, &table_name, &doc_id, req.rev)
+        .await
+    {
+        Ok(Some(revision)) if !revision.deleted => {}
+        Ok(_) => {
+            return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "revision not found".to_string(),
+                message: None,
+            }));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to load revision".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    }
+
+    let outcome = match store
+        .restore_revision(&db_name, &table_name, &doc_id, req.rev)
+        .await
+    {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to restore revision".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    match rechunk_revised(store, &state.config, &state.webhooks, outcome).await {
+        Ok(doc) => Ok(HttpResponse::Ok().json(doc)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to store chunks".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Past version selection for getting a document
+#[derive(Debug, Deserialize)]
+pub struct DocumentVersionQuery {
+    pub rev: Option<i64>,
+    pub as_of: Option<String>, // Unix milliseconds or RFC 3339
+}
+
+/// Get a past version of a document, by revision number or point in time
+async fn get_document_revision(
+    store: &DocumentStore,
+    db_name: &str,
+    table_name: &str,
+    doc_id: &str,
+    query: &DocumentVersionQuery,
+) -> ActixResult<HttpResponse> {
+    if let Some(response) = history_disabled(store, db_name, table_name).await {
+        return Ok(response);
+    }
+
+    let revision = match (query.rev, query.as_of.as_deref()) {
+        (Some(rev), None) => store.get_revision(db_name, table_name, doc_id, rev).await,
+        (None, Some(as_of)) => {
+            let Some(as_of) = parse_timestamp(as_of) else {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "invalid as_of".to_string(),
+                    message: Some(
+                        "expected Unix milliseconds or an RFC 3339 timestamp".to_string(),
+                    ),
+                }));
+            };
+            store
+                .get_revision_as_of(db_name, table_name, doc_id, as_of)
+                .await
+        }
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "conflicting version".to_string(),
+                message: Some("use either rev or as_of".to_string()),
+            }));
+        }
+    };
+
+    match revision {
+        // A deletion marker means the document did not exist at that point
+        Ok(Some(revision)) if !revision.deleted => Ok(HttpResponse::Ok().json(revision)),
+        Ok(_) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "document not found".to_string(),
+            message: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to load revision".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Delete a document
+/// DELETE /db/{db_name}/{table_name}/{doc_id}
+#[tracing::instrument(skip(path, state))]
+pub async fn delete_document(
+    path: web::Path<(String, String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, doc_id) = path.into_inner();
+    tracing::debug!(db = %db_name, table = %table_name, doc_id = %doc_id, "Deleting document");
+
+    let store = &state.store;
+    match store
+        .delete_document_by_id(&db_name, &table_name, &doc_id)
+        .await
+    {
+        Ok(_) => {
+            tracing::info!("Document deleted successfully");
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(e) => {
+            tracing::error!("Failed to delete document: {}", e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to delete document".to_string(),
+                message: Some(e.to_string()),
+            }))
+        }
+    }
+}
+
+/// Trash listing query parameters
+#[derive(Deserialize)]
+pub struct TrashQuery {
+    pub limit: Option<usize>,
+}
+
+/// List deleted documents of a table, most recently deleted first
+/// GET /db/{db_name}/{table_name}/_trash
+pub async fn list_trash(
+    path: web::Path<(String, String)>,
+    query: web::Query<TrashQuery>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let store = &state.store;
+
+    let documents = store
+        .list_trash(&db_name, &table_name, query.limit)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Query error: {}", e)))?;
+
+    let retention = chrono::Duration::days(state.config.trash.retention_days as i64);
+    let documents: Vec<serde_json::Value> = documents
+        .into_iter()
+        .map(|trashed| {
+            let purge_at = (trashed.deleted_at + retention).timestamp_millis();
+            let mut value = serde_json::to_value(&trashed).unwrap_or_default();
+            value["purge_at"] = serde_json::json!(purge_at);
+            value
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "documents": documents,
+        "total": documents.len(),
+    })))
+}
+
+/// Permanently delete everything in a table's trash
+/// DELETE /db/{db_name}/{table_name}/_trash
+pub async fn empty_trash(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let store = &state.store;
+
+    match store.purge_deleted(&db_name, &table_name, Utc::now()).await {
+        Ok(purged) => Ok(HttpResponse::Ok().json(serde_json::json!({ "purged": purged }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to empty trash".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Restore a deleted document and its chunks from the trash
+/// POST /db/{db_name}/{table_name}/{doc_id}/_undelete
+pub async fn undelete_document(
+    path: web::Path<(String, String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name, doc_id) = path.into_inner();
+    let store = &state.store;
+
+    match store
+        .undelete_document(&db_name, &table_name, &doc_id)
+        .await
+    {
+        Ok(Some(doc)) => Ok(HttpResponse::Ok().json(doc)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "document not in trash".to_string(),
+            message: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to restore document".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Move a table to the trash
+/// DELETE /db/{db_name}/{table_name}
+pub async fn delete_table(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    tracing::debug!(db = %db_name, table = %table_name, "Deleting table");
+
+    let store = &state.store;
+    match store.delete_table(&db_name, &table_name).await {
+        Ok(_) => {
+            tracing::info!("Table deleted successfully");
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(e) => {
+            tracing::error!("Failed to delete table: {}", e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to delete table".to_string(),
+                message: Some(e.to_string()),
+            }))
+        }
+    }
+}
+
+/// List deleted tables of a database, most recently deleted first
+/// GET /db/{db_name}/_trash
+pub async fn list_deleted_tables(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    if !store.database_exists(&db_name) {
+        return Ok(database_not_found());
+    }
+
+    let tables = match store.list_deleted_tables(&db_name).await {
+        Ok(tables) => tables,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to list deleted tables".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    let retention = chrono::Duration::days(state.config.trash.retention_days as i64);
+    let tables: Vec<serde_json::Value> = tables
+        .into_iter()
+        .map(|trashed| {
+            let purge_at = (trashed.deleted_at + retention).timestamp_millis();
+            let mut value = serde_json::to_value(&trashed).unwrap_or_default();
+            value["purge_at"] = serde_json::json!(purge_at);
+            value
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "tables": tables,
+        "total": tables.len(),
+    })))
+}
+
+/// Restore the most recently deleted copy of a table from the trash
+/// POST /db/{db_name}/{table_name}/_undelete
+pub async fn undelete_table(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let store = &state.store;
+
+    if !store.database_exists(&db_name) {
+        return Ok(database_not_found());
+    }
+    if store
+        .table_exists(&db_name, &table_name)
+        .await
+        .unwrap_or(false)
+    {
+        return Ok(HttpResponse::Conflict().json(ErrorResponse {
+            error: "table exists".to_string(),
+            message: Some(format!("delete {} before restoring it", table_name)),
+        }));
+    }
+
+    match store.undelete_table(&db_name, &table_name).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(serde_json::json!({ "name": table_name }))),
+        Ok(false) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "table not in trash".to_string(),
+            message: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to restore table".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// DELETE /db/{db_name}
+pub async fn delete_database(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    tracing::debug!(db = %db_name, "Deleting database");
+
+    let store = &state.store;
+    match store.delete_database(&db_name).await {
+        Ok(_) => {
+            tracing::info!("Database deleted successfully");
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(e) => {
+            tracing::error!("Failed to delete database: {}", e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to delete database".to_string(),
+                message: Some(e.to_string()),
+            }))
+        }
+    }
+}
+
+/// Restore the most recently deleted copy of a database from the trash
+/// POST /db/{db_name}/_undelete
+pub async fn undelete_database(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    if store.database_exists(&db_name) {
+        return Ok(HttpResponse::Conflict().json(ErrorResponse {
+            error: "database exists".to_string(),
+            message: Some(format!("delete or rename {} before restoring it", db_name)),
+        }));
+    }
+
+    match store.undelete_database(&db_name).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(serde_json::json!({ "name": db_name }))),
+        Ok(false) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "database not in trash".to_string(),
+            message: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to restore database".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Take an online backup of a database
+/// POST /db/{db_name}/_backup
+pub async fn backup_database(
+    path: web::Path<String>,
+    query: web::Query<BackupOptions>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    if !store.database_exists(&db_name) {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "database not found".to_string(),
+            message: None,
+        }));
+    }
+
+    match store.backup_database(&db_name, &query).await {
+        Ok(info) => Ok(HttpResponse::Created().json(info)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to back up database".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List the backups of a database, newest first
+/// GET /db/{db_name}/_backups
+pub async fn list_backups(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    match store.list_backups(&db_name) {
+        Ok(backups) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "total": backups.len(),
+            "backups": backups,
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to list backups".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Replace a database with one of its backups
+/// POST /db/{db_name}/_restore
+pub async fn restore_database(
+    path: web::Path<String>,
+    query: web::Query<RestoreRequest>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    match store
+        .restore_database(
+            &db_name,
+            query.backup.as_deref(),
+            query.include_index,
+            query.include_cache,
+        )
+        .await
+    {
+        Ok(Some(info)) => Ok(HttpResponse::Ok().json(info)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "backup not found".to_string(),
+            message: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to restore database".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Changes per response when none are asked for
+const DEFAULT_CHANGES_LIMIT: usize = 100;
+
+/// Most changes per response
+const MAX_CHANGES_LIMIT: usize = 1000;
+
+/// Longest an event stream stays silent before sending a keep-alive comment
+const SSE_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Read changes after `since`, with the current documents if asked for
+async fn read_changes(
+    store: &DocumentStore,
+    db_name: &str,
+    since: i64,
+    query: &ChangesQuery,
+    limit: usize,
+) -> anyhow::Result<Vec<Change>> {
+    let mut changes = store
+        .changes_since(db_name, since, query.table.as_deref(), limit)
+        .await?;
+    if query.include_docs {
+        store.attach_changed_items(db_name, &mut changes).await?;
+    }
+    Ok(changes)
+}
+
+/// Read a database's change log
+/// GET /db/{db_name}/_changes?since=&limit=&table=&feed=normal|longpoll|sse&timeout=&include_docs=
+///
+/// A long poll waits up to `timeout` milliseconds for a change if there are none
+/// yet. The SSE feed streams changes as they are logged, resuming after the
+/// `Last-Event-ID` header if the client sends one. If changes after `since` were
+/// pruned, responds `410 Gone` and the client has to resync.
+pub async fn get_changes(
+    path: web::Path<String>,
+    query: web::Query<ChangesQuery>,
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let query = query.into_inner();
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_CHANGES_LIMIT)
+        .clamp(1, MAX_CHANGES_LIMIT);
+
+    let since = match req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok()?.parse().ok())
+    {
+        Some(last_event_id) if query.feed == ChangeFeed::Sse => last_event_id,
+        _ => query.since,
+    };
+
+    let store = &state.store;
+    if !store.database_exists(&db_name) {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "database not found".to_string(),
+            message: None,
+        }));
+    }
+
+    let retained = match store.changes_retained_since(&db_name, since).await {
+        Ok(retained) => retained,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to read changes".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+    if !retained {
+        let last_seq = store.last_change_seq(&db_name).await.unwrap_or(0);
+        return Ok(HttpResponse::Gone().json(ErrorResponse {
+            error: "changes pruned".to_string(),
+            message: Some(format!(
+                "changes after {} are no longer logged; resync and continue from {}",
+                since, last_seq
+            )),
+        }));
+    }
+
+    let poll_interval =
+        std::time::Duration::from_millis(state.config.changes.poll_interval_ms.max(1));
+
+    if query.feed == ChangeFeed::Sse {
+        let store = state.store.clone();
+        let events =
+            futures::stream::unfold(Some((since, std::time::Instant::now())), move |position| {
+                let store = store.clone();
+                let db_name = db_name.clone();
+                let query = query.clone();
+                async move {
+                    let (since, last_sent) = position?;
+                    loop {
+                        let changes =
+                            match read_changes(&store, &db_name, since, &query, limit).await {
+                                Ok(changes) => changes,
+                                Err(e) => {
+                                    return Some((
+                                        Err(actix_web::error::ErrorInternalServerError(e)),
+                                        None,
+                                    ))
+                                }
+                            };
+
+                        if let Some(last) = changes.last() {
+                            let last_seq = last.seq;
+                            let mut chunk = String::new();
+                            for change in &changes {
+                                let data = serde_json::to_string(change).unwrap_or_default();
+                                chunk.push_str(&format!(
+                                    "id: {}\nevent: change\ndata: {}\n\n",
+                                    change.seq, data
+                                ));
+                            }
+                            let next = Some((last_seq, std::time::Instant::now()));
+                            return Some((Ok(web::Bytes::from(chunk)), next));
+                        }
+
+                        if last_sent.elapsed() >= SSE_KEEPALIVE {
+                            let next = Some((since, std::time::Instant::now()));
+                            return Some((Ok(web::Bytes::from_static(b": keepalive\n\n")), next));
+                        }
+                        tokio::time::sleep(poll_interval).await;
+                    }
+                }
+            });
+
+        return Ok(HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header(("Cache-Control", "no-cache"))
+            .streaming(events));
+    }
+
+    let max_wait = std::time::Duration::from_secs(state.config.changes.max_wait_secs);
+    let deadline = match query.feed {
+        ChangeFeed::Longpoll => {
+            let timeout = query
+                .timeout
+                .map(std::time::Duration::from_millis)
+                .unwrap_or(max_wait);
+            Some(tokio::time::Instant::now() + timeout.min(max_wait))
+        }
+        _ => None,
+    };
+
+    loop {
+        let changes = match read_changes(&state.store, &db_name, since, &query, limit).await {
+            Ok(changes) => changes,
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "failed to read changes".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        };
+
+        let waiting = deadline.is_some_and(|deadline| tokio::time::Instant::now() < deadline);
+        if !changes.is_empty() || !waiting {
+            let last_seq = changes.last().map(|change| change.seq).unwrap_or(since);
+            return Ok(HttpResponse::Ok().json(ChangesResponse { changes, last_seq }));
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Deliveries per response when none are asked for
+const DEFAULT_DELIVERIES_LIMIT: usize = 100;
+
+/// Most deliveries per response
+const MAX_DELIVERIES_LIMIT: usize = 1000;
+
+/// Error response if a webhook does not exist in a database
+fn webhook_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse {
+        error: "webhook not found".to_string(),
+        message: None,
+    })
+}
+
+/// Register a webhook for a database's document events
+/// POST /db/{db_name}/_webhooks
+///
+/// The response is the only one that includes the webhook's secret.
+pub async fn create_webhook(
+    path: web::Path<String>,
+    req: web::Json<CreateWebhookRequest>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let req = req.into_inner();
+
+    if let Err(e) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid webhook".to_string(),
+            message: Some(e.to_string()),
+        }));
+    }
+
+    if !state.store.database_exists(&db_name) {
+        return Ok(database_not_found());
+    }
+    let last_seq = match state.store.last_change_seq(&db_name).await {
+        Ok(last_seq) => last_seq,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to create webhook".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    match state.webhooks.create_webhook(&db_name, req, last_seq).await {
+        Ok(webhook) => Ok(HttpResponse::Created().json(webhook)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to create webhook".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List the webhooks of a database
+/// GET /db/{db_name}/_webhooks
+pub async fn list_webhooks(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+
+    match state.webhooks.list_webhooks(&db_name).await {
+        Ok(webhooks) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "total": webhooks.len(),
+            "webhooks": webhooks,
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to list webhooks".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Get a webhook
+/// GET /db/{db_name}/_webhooks/{webhook_id}
+pub async fn get_webhook(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, webhook_id) = path.into_inner();
+
+    match state.webhooks.get_webhook(&db_name, &webhook_id).await {
+        Ok(Some(webhook)) => Ok(HttpResponse::Ok().json(webhook)),
+        Ok(None) => Ok(webhook_not_found()),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to get webhook".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Change a webhook's URL or events, or disable or enable it
+/// PATCH /db/{db_name}/_webhooks/{webhook_id}
+pub async fn update_webhook(
+    path: web::Path<(String, String)>,
+    req: web::Json<UpdateWebhookRequest>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, webhook_id) = path.into_inner();
+    let req = req.into_inner();
+
+    if let Err(e) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid webhook".to_string(),
+            message: Some(e.to_string()),
+        }));
+    }
+
+    match state
+        .webhooks
+        .update_webhook(&db_name, &webhook_id, req)
+        .await
+    {
+        Ok(Some(webhook)) => Ok(HttpResponse::Ok().json(webhook)),
+        Ok(None) => Ok(webhook_not_found()),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to update webhook".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Delete a webhook and its delivery log
+/// DELETE /db/{db_name}/_webhooks/{webhook_id}
+pub async fn delete_webhook(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, webhook_id) = path.into_inner();
+
+    match state.webhooks.delete_webhook(&db_name, &webhook_id).await {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => Ok(webhook_not_found()),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to delete webhook".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List a webhook's deliveries, newest first
+/// GET /db/{db_name}/_webhooks/{webhook_id}/deliveries?status=&limit=
+pub async fn list_webhook_deliveries(
+    path: web::Path<(String, String)>,
+    query: web::Query<WebhookDeliveriesQuery>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, webhook_id) = path.into_inner();
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERIES_LIMIT)
+        .clamp(1, MAX_DELIVERIES_LIMIT);
+
+    match state.webhooks.get_webhook(&db_name, &webhook_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Ok(webhook_not_found()),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "failed to list deliveries".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    }
+
+    match state
+        .webhooks
+        .deliveries(&db_name, &webhook_id, query.status, limit)
+        .await
+    {
+        Ok(deliveries) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "total": deliveries.len(),
+            "deliveries": deliveries,
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to list deliveries".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List the tags of a table with their document counts, most used first
+/// GET /db/{db_name}/{table_name}/_tags
+pub async fn list_tags(
     path: web::Path<(String, String)>,
     state: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
     let (db_name, table_name) = path.into_inner();
-    tracing::debug!(db = %db_name, table = %table_name, "Deleting table");
+    let store = &state.store;
 
-    let mut store = state.store.lock().await;
-    match store.delete_table(&db_name, &table_name).await {
-        Ok(_) => {
-            tracing::info!("Table deleted successfully");
-            Ok(HttpResponse::NoContent().finish())
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete table: {}", e);
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: "failed to delete table".to_string(),
-                message: Some(e.to_string()),
-            }))
-        }
-    }
+    let tags = store
+        .tag_counts(&db_name, &table_name, None)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Query error: {}", e)))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "tags": tags,
+        "total": tags.len(),
+    })))
 }
 
-/// DELETE /db/{db_name}
-pub async fn delete_database(
-    path: web::Path<String>,
+/// Rename a tag across a table, merging it into the new name where present
+/// POST /db/{db_name}/{table_name}/_tags/{tag}/_rename
+pub async fn rename_tag(
+    path: web::Path<(String, String, String)>,
+    req: web::Json<RenameTagRequest>,
     state: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
-    let db_name = path.into_inner();
-    tracing::debug!(db = %db_name, "Deleting database");
+    let (db_name, table_name, tag) = path.into_inner();
 
-    let mut store = state.store.lock().await;
-    match store.delete_database(&db_name).await {
-        Ok(_) => {
-            tracing::info!("Database deleted successfully");
-            Ok(HttpResponse::NoContent().finish())
-        }
-        Err(e) => {
-            tracing::error!("Failed to delete database: {}", e);
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: "failed to delete database".to_string(),
-                message: Some(e.to_string()),
-            }))
-        }
+    if req.to.is_empty() || req.to == tag {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid tag name".to_string(),
+            message: Some("to must be a non-empty tag different from the renamed one".to_string()),
+        }));
+    }
+
+    let store = &state.store;
+    match store.rename_tag(&db_name, &table_name, &tag, &req.to).await {
+        Ok(updated) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "from": tag,
+            "to": req.to,
+            "updated": updated,
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to rename tag".to_string(),
+            message: Some(e.to_string()),
+        })),
     }
 }
 
@@ -382,25 +2203,158 @@ pub async fn search(
         db = %db_name,
         table = %table_name,
         query_len = req.query.len(),
+        search_type = ?req.search_type,
         limit = req.limit.unwrap_or(10),
         "Searching documents"
     );
 
-    let mut store = state.store.lock().await;
-    let searcher = kuiperdb_core::search::HybridSearcher::new();
+    if req.query_vector.is_some() && req.more_like_this.is_some() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "conflicting query".to_string(),
+            message: Some("use either query_vector or more_like_this, not both".to_string()),
+        }));
+    }
+
+    let has_vector = req.query_vector.is_some() || req.more_like_this.is_some();
+    if req.query.trim().is_empty() && (!has_vector || req.search_type == SearchType::FullText) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "query is required".to_string(),
+            message: None,
+        }));
+    }
+
+    if req.search_type == SearchType::Vector && !has_vector && state.embedder.is_none() {
+        return Ok(HttpResponse::NotImplemented().json(ErrorResponse {
+            error: "vector search requires an embedder".to_string(),
+            message: Some("enable the embedding feature to use type \"vector\"".to_string()),
+        }));
+    }
+
+    let filter = match SearchFilter::parse(&req.filters) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid filters".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    };
+
+    let store = &state.store;
+    let (settings, config) =
+        match table_config(store, &state.config, &db_name, &table_name).await {
+            Ok(table) => table,
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "failed to load settings".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        };
+
+    let mut searcher = kuiperdb_core::search::HybridSearcher::new().with_filter(filter);
+    if !req.include_chunks {
+        searcher = searcher.without_chunks();
+    }
+    if req.group_by_parent {
+        searcher = searcher.with_grouping(req.aggregation, req.max_chunks_per_parent);
+    }
+
+    if let Some(ref vector) = req.query_vector {
+        if let Err(e) = store
+            .validate_vector(&db_name, &table_name, vector, config.embedding_dimensions)
+            .await
+        {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid query vector".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+        searcher = searcher.with_query_vector(vector.clone());
+    }
+
+    if let Some(ref doc_id) = req.more_like_this {
+        let doc = match store.get_document(&db_name, &table_name, doc_id).await {
+            Ok(doc) => doc,
+            Err(_) => {
+                return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                    error: "document not found".to_string(),
+                    message: Some(doc_id.clone()),
+                }));
+            }
+        };
+
+        searcher = match searcher
+            .like_document(store, &doc, req.exclude_own_chunks)
+            .await
+        {
+            Ok(searcher) => searcher,
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "document has no embedding".to_string(),
+                    message: Some(e.to_string()),
+                }));
+            }
+        };
+    }
+
+    // Embedding the query counts against the database's rate limit
+    let embeds_query =
+        !has_vector && req.search_type != SearchType::FullText && state.embedder.is_some();
+    if embeds_query {
+        if let Err(e) = store.acquire_embedding_request(&db_name).await {
+            return Ok(store_error(&e, "failed to search"));
+        }
+    }
+
+    // A table with its own embedding model needs the query embedded the same way
+    let overrides_embedding =
+        settings.embedding_model.is_some() || settings.embedding_dimensions.is_some();
+    if overrides_embedding && !has_vector && req.search_type != SearchType::FullText {
+        if let Some(ref embedder) = state.embedder {
+            let vector = embedder
+                .embed_with(
+                    &req.query,
+                    settings.embedding_model.as_deref(),
+                    settings.embedding_dimensions,
+                )
+                .await
+                .map_err(|e| {
+                    actix_web::error::ErrorInternalServerError(format!(
+                        "Failed to embed query: {}",
+                        e
+                    ))
+                })?;
+            searcher = searcher.with_query_vector(vector);
+        }
+    }
 
     let results = searcher
-        .search(
-            &mut store,
+        .search_with_type(
+            store,
             state.embedder.as_deref(),
             &db_name,
             &table_name,
             &req.query,
             req.limit.unwrap_or(10),
+            req.search_type,
         )
         .await
         .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Search failed: {}", e)))?;
 
+    let facets = if req.facets {
+        let ids: Vec<String> = results.iter().map(|result| result.id.clone()).collect();
+        let tags = store
+            .tag_counts(&db_name, &table_name, Some(&ids))
+            .await
+            .map_err(|e| {
+                actix_web::error::ErrorInternalServerError(format!("Facet query failed: {}", e))
+            })?;
+        Some(SearchFacets { tags })
+    } else {
+        None
+    };
+
     let total = results.len();
     let response = SearchResponse {
         results,
@@ -408,6 +2362,7 @@ pub async fn search(
         search_type: req.search_type,
         db: db_name,
         total,
+        facets,
     };
 
     Ok(HttpResponse::Ok().json(response))
@@ -425,7 +2380,7 @@ pub async fn health() -> ActixResult<HttpResponse> {
 /// List databases
 /// GET /db
 pub async fn list_databases(state: web::Data<AppState>) -> ActixResult<HttpResponse> {
-    let store = state.store.lock().await;
+    let store = &state.store;
     let databases = store.list_databases().await.map_err(|e| {
         actix_web::error::ErrorInternalServerError(format!("Failed to list databases: {}", e))
     })?;
@@ -435,6 +2390,147 @@ pub async fn list_databases(state: web::Data<AppState>) -> ActixResult<HttpRespo
     })))
 }
 
+/// Get a database's catalog entry with totals over its tables
+/// GET /db/{db_name}
+pub async fn get_database(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    if !store.database_exists(&db_name) {
+        return Ok(database_not_found());
+    }
+
+    match store.database_info(&db_name).await {
+        Ok(info) => Ok(HttpResponse::Ok().json(info)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to load database".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Get the settings of a database
+/// GET /db/{db_name}/_settings
+pub async fn get_database_settings(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    if !store.database_exists(&db_name) {
+        return Ok(database_not_found());
+    }
+
+    match store.database_settings(&db_name).await {
+        Ok(settings) => Ok(HttpResponse::Ok().json(settings)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to load settings".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Replace the settings of a database, creating it if needed
+/// PUT /db/{db_name}/_settings
+pub async fn put_database_settings(
+    path: web::Path<String>,
+    req: web::Json<DatabaseSettings>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let db_name = path.into_inner();
+    let store = &state.store;
+
+    match store.set_database_settings(&db_name, &req).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(req.0)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to save settings".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Get document, embedding and storage statistics of a table
+/// GET /db/{db_name}/{table_name}/_stats
+pub async fn get_table_stats(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let store = &state.store;
+
+    let exists = store.database_exists(&db_name)
+        && store
+            .table_exists(&db_name, &table_name)
+            .await
+            .unwrap_or(false);
+    if !exists {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "table not found".to_string(),
+            message: None,
+        }));
+    }
+
+    match store.table_stats(&db_name, &table_name).await {
+        Ok(stats) => Ok(HttpResponse::Ok().json(stats)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to load stats".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// List clusters of documents in a table that duplicate each other
+/// GET /db/{db_name}/{table_name}/_duplicates?near=vector|minhash&threshold=0.9
+pub async fn get_duplicates(
+    path: web::Path<(String, String)>,
+    query: web::Query<DuplicatesQuery>,
+    state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (db_name, table_name) = path.into_inner();
+    let store = &state.store;
+
+    if let Some(threshold) = query.threshold {
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid threshold".to_string(),
+                message: Some("threshold must be in (0, 1]".to_string()),
+            }));
+        }
+    }
+
+    let exists = store.database_exists(&db_name)
+        && store
+            .table_exists(&db_name, &table_name)
+            .await
+            .unwrap_or(false);
+    if !exists {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "table not found".to_string(),
+            message: None,
+        }));
+    }
+
+    match store.duplicate_report(&db_name, &table_name, &query).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "failed to find duplicates".to_string(),
+            message: Some(e.to_string()),
+        })),
+    }
+}
+
+/// Error response if a database file does not exist
+fn database_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse {
+        error: "database not found".to_string(),
+        message: None,
+    })
+}
+
 /// List tables in a database
 /// GET /db/{db_name}/tables
 pub async fn list_tables(
@@ -442,7 +2538,7 @@ pub async fn list_tables(
     state: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
     let db_name = path.into_inner();
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     
     let tables = store.list_tables(&db_name).await.map_err(|e| {
         actix_web::error::ErrorInternalServerError(format!("Failed to list tables: {}", e))
@@ -459,27 +2555,51 @@ pub async fn list_tables(
     })))
 }
 
-/// List documents in a table (roots only - documents without parent_id)
-/// GET /db/{db_name}/{table_name}/documents
+/// List documents in a table, one page at a time (roots only by default)
+/// GET /db/{db_name}/{table_name}/documents?limit=&cursor=&sort=&order=&kind=&not_embedded=
 pub async fn list_documents(
     path: web::Path<(String, String)>,
+    query: web::Query<ListDocumentsQuery>,
     state: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
     let (db_name, table_name) = path.into_inner();
-    let mut store = state.store.lock().await;
-    
-    // Get all documents (not just non-embedded ones)
-    let all_docs = store.get_all_documents(&db_name, &table_name, 1000).await
+
+    if let Some(ref cursor) = query.cursor {
+        if let Err(e) = PageCursor::decode(cursor, query.sort) {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid cursor".to_string(),
+                message: Some(e.to_string()),
+            }));
+        }
+    }
+
+    let store = &state.store;
+    let page = store
+        .list_documents(&db_name, &table_name, &query)
+        .await
         .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Query error: {}", e)))?;
-    
-    // Filter for root documents (no parent_id)
-    let root_docs: Vec<_> = all_docs.into_iter()
-        .filter(|doc| doc.parent_id.is_none() || doc.parent_id.as_ref().map(|s| s.is_empty()).unwrap_or(true))
-        .take(100)
+
+    // Documents never serialize their vector, so add it here when asked for
+    let documents: Vec<serde_json::Value> = page
+        .documents
+        .into_iter()
+        .map(|doc| {
+            let mut value = serde_json::json!(doc);
+            if let Some(fields) = value.as_object_mut() {
+                if !query.include_content {
+                    fields.remove("content");
+                }
+                if let Some(ref vector) = doc.vector {
+                    fields.insert("vector".to_string(), serde_json::json!(vector));
+                }
+            }
+            value
+        })
         .collect();
-    
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
-        "documents": root_docs
+        "documents": documents,
+        "next_cursor": page.next_cursor,
     })))
 }
 
@@ -559,7 +2679,7 @@ pub async fn create_relation(
         created_at: Utc::now(),
     };
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     match store.create_relation(&db_name, relation.clone()).await {
         Ok(_) => Ok(HttpResponse::Created().json(relation)),
         Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
@@ -577,7 +2697,7 @@ pub async fn get_relation(
 ) -> ActixResult<HttpResponse> {
     let (db_name, relation_id) = path.into_inner();
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     match store.get_relation(&db_name, &relation_id).await {
         Ok(relation) => Ok(HttpResponse::Ok().json(relation)),
         Err(_) => Ok(HttpResponse::NotFound().json(ErrorResponse {
@@ -595,7 +2715,7 @@ pub async fn delete_relation(
 ) -> ActixResult<HttpResponse> {
     let (db_name, relation_id) = path.into_inner();
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     match store.delete_relation(&db_name, &relation_id).await {
         Ok(_) => Ok(HttpResponse::NoContent().finish()),
         Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
@@ -613,7 +2733,7 @@ pub async fn get_document_relations(
 ) -> ActixResult<HttpResponse> {
     let (db_name, doc_id) = path.into_inner();
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     match store.get_document_relations(&db_name, &doc_id).await {
         Ok(relations) => Ok(HttpResponse::Ok().json(relations)),
         Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
@@ -641,7 +2761,7 @@ pub async fn graph_traverse(
 
     use kuiperdb_core::graph::DocumentGraph;
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     let relations = store.get_all_relations(&db_name).await.map_err(|e| {
         actix_web::error::ErrorInternalServerError(format!("Failed to get relations: {}", e))
     })?;
@@ -685,7 +2805,7 @@ pub async fn graph_shortest_path(
 
     use kuiperdb_core::graph::DocumentGraph;
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     let relations = store.get_all_relations(&db_name).await.map_err(|e| {
         actix_web::error::ErrorInternalServerError(format!("Failed to get relations: {}", e))
     })?;
@@ -720,7 +2840,7 @@ pub async fn graph_statistics(
 
     use kuiperdb_core::graph::DocumentGraph;
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     let relations = store.get_all_relations(&db_name).await.map_err(|e| {
         actix_web::error::ErrorInternalServerError(format!("Failed to get relations: {}", e))
     })?;
@@ -741,7 +2861,7 @@ pub async fn get_chunks(
 ) -> ActixResult<HttpResponse> {
     let (db_name, table_name, doc_id) = path.into_inner();
 
-    let mut store = state.store.lock().await;
+    let store = &state.store;
     match store.get_chunks(&db_name, &table_name, &doc_id).await {
         Ok(chunks) => Ok(HttpResponse::Ok().json(chunks)),
         Err(e) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
@@ -759,15 +2879,21 @@ pub async fn rechunk_document(
 ) -> ActixResult<HttpResponse> {
     let (db_name, table_name, doc_id) = path.into_inner();
 
-    if !state.config.features.chunking || !state.config.chunking.enabled {
+    let store = &state.store;
+    let (_, config) = table_config(store, &state.config, &db_name, &table_name)
+        .await
+        .map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!("Failed to load settings: {}", e))
+        })?;
+
+    let Some(chunker) = new_chunker(&config) else {
         return Ok(HttpResponse::NotImplemented().json(ErrorResponse {
             error: "chunking feature is disabled".to_string(),
             message: None,
         }));
-    }
+    };
 
     // Get original document
-    let mut store = state.store.lock().await;
     let doc = store
         .get_document(&db_name, &table_name, &doc_id)
         .await
@@ -782,17 +2908,11 @@ pub async fn rechunk_document(
         })?;
 
     // Re-chunk
-    use kuiperdb_core::chunking::{Chunker, FixedTokenChunker};
-
-    let chunker = FixedTokenChunker::new().map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to create chunker: {}", e))
-    })?;
-
     let chunks_texts = chunker
         .chunk(
             &doc.content,
-            state.config.chunking.chunk_size,
-            state.config.chunking.chunk_overlap,
+            config.chunking.chunk_size,
+            config.chunking.chunk_overlap,
         )
         .map_err(|e| {
             actix_web::error::ErrorInternalServerError(format!("Chunking failed: {}", e))
@@ -817,6 +2937,8 @@ pub async fn rechunk_document(
             chunk_index: Some(idx as i32),
             token_count: chunker.count_tokens(chunk_text).ok().map(|c| c as i32),
             is_vectorized: false,
+            embedding_model: None,
+            expires_at: doc.expires_at,
         };
 
         store
@@ -829,6 +2951,15 @@ pub async fn rechunk_document(
         created_chunks.push(chunk_doc);
     }
 
+    notify_chunked(
+        &state.webhooks,
+        &db_name,
+        &table_name,
+        &doc_id,
+        created_chunks.len(),
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "chunks_created": created_chunks.len(),
         "chunks": created_chunks,
@@ -1032,11 +3163,11 @@ pub async fn analyze_logs(path: web::Path<String>) -> ActixResult<HttpResponse>
     }
 
     let mut by_target_vec: Vec<(String, usize)> = by_target.into_iter().collect();
-    by_target_vec.sort_by(|a, b| b.1.cmp(&a.1));
+    by_target_vec.sort_by_key(|b| std::cmp::Reverse(b.1));
     by_target_vec.truncate(10); // Top 10
 
     let mut api_ops_vec: Vec<(String, usize)> = api_ops.into_iter().collect();
-    api_ops_vec.sort_by(|a, b| b.1.cmp(&a.1));
+    api_ops_vec.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     Ok(HttpResponse::Ok().json(LogAnalysisResponse {
         date,
@@ -1115,10 +3246,89 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/{db_name}/graph/traverse", web::post().to(graph_traverse))
             .route("/{db_name}/graph/path", web::get().to(graph_shortest_path))
             .route("/{db_name}/graph/stats", web::get().to(graph_statistics))
+            .route("/{db_name}/_settings", web::get().to(get_database_settings))
+            .route("/{db_name}/_settings", web::put().to(put_database_settings))
+            .route("/{db_name}/_undelete", web::post().to(undelete_database))
+            .route("/{db_name}/_trash", web::get().to(list_deleted_tables))
+            .route("/{db_name}/_backup", web::post().to(backup_database))
+            .route("/{db_name}/_backups", web::get().to(list_backups))
+            .route("/{db_name}/_restore", web::post().to(restore_database))
+            .route("/{db_name}/_changes", web::get().to(get_changes))
+            .route("/{db_name}/_webhooks", web::post().to(create_webhook))
+            .route("/{db_name}/_webhooks", web::get().to(list_webhooks))
+            .route(
+                "/{db_name}/_webhooks/{webhook_id}",
+                web::get().to(get_webhook),
+            )
+            .route(
+                "/{db_name}/_webhooks/{webhook_id}",
+                web::patch().to(update_webhook),
+            )
+            .route(
+                "/{db_name}/_webhooks/{webhook_id}",
+                web::delete().to(delete_webhook),
+            )
+            .route(
+                "/{db_name}/_webhooks/{webhook_id}/deliveries",
+                web::get().to(list_webhook_deliveries),
+            )
             // Table-specific routes
             .route("/{db_name}/tables", web::get().to(list_tables))
             .route("/{db_name}/{table_name}/documents", web::get().to(list_documents))
             .route("/{db_name}/{table_name}/search", web::post().to(search))
+            .route(
+                "/{db_name}/{table_name}/_settings",
+                web::get().to(get_table_settings),
+            )
+            .route(
+                "/{db_name}/{table_name}/_settings",
+                web::put().to(put_table_settings),
+            )
+            .route("/{db_name}/{table_name}/_stats", web::get().to(get_table_stats))
+            .route(
+                "/{db_name}/{table_name}/_duplicates",
+                web::get().to(get_duplicates),
+            )
+            .route("/{db_name}/{table_name}/_trash", web::get().to(list_trash))
+            .route(
+                "/{db_name}/{table_name}/_undelete",
+                web::post().to(undelete_table),
+            )
+            .route("/{db_name}/{table_name}/_tags", web::get().to(list_tags))
+            .route(
+                "/{db_name}/{table_name}/_export",
+                web::get().to(export_table),
+            )
+            .route(
+                "/{db_name}/{table_name}/_indexes",
+                web::get().to(list_metadata_indexes),
+            )
+            .route(
+                "/{db_name}/{table_name}/_indexes",
+                web::post().to(create_metadata_index),
+            )
+            .route(
+                "/{db_name}/{table_name}/_indexes/{field}",
+                web::delete().to(drop_metadata_index),
+            )
+            .route(
+                "/{db_name}/{table_name}/_tags/{tag}/_rename",
+                web::post().to(rename_tag),
+            )
+            .route(
+                "/{db_name}/{table_name}/_trash",
+                web::delete().to(empty_trash),
+            )
+            .service(
+                web::resource("/{db_name}/{table_name}/_bulk")
+                    .app_data(web::PayloadConfig::new(BULK_PAYLOAD_LIMIT))
+                    .route(web::post().to(bulk_store_documents)),
+            )
+            .service(
+                web::resource("/{db_name}/{table_name}/_import")
+                    .app_data(web::PayloadConfig::new(IMPORT_PAYLOAD_LIMIT))
+                    .route(web::post().to(import_table)),
+            )
             .route(
                 "/{db_name}/{table_name}/{doc_id}/chunks",
                 web::get().to(get_chunks),
@@ -1127,10 +3337,30 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/{db_name}/{table_name}/{doc_id}/rechunk",
                 web::post().to(rechunk_document),
             )
+            .route(
+                "/{db_name}/{table_name}/{doc_id}/_revisions",
+                web::get().to(list_revisions),
+            )
+            .route(
+                "/{db_name}/{table_name}/{doc_id}/_diff",
+                web::get().to(diff_revisions),
+            )
+            .route(
+                "/{db_name}/{table_name}/{doc_id}/_restore",
+                web::post().to(restore_revision),
+            )
+            .route(
+                "/{db_name}/{table_name}/{doc_id}/_undelete",
+                web::post().to(undelete_document),
+            )
             .route(
                 "/{db_name}/{table_name}/{doc_id}",
                 web::get().to(get_document),
             )
+            .route(
+                "/{db_name}/{table_name}/{doc_id}",
+                web::patch().to(patch_document),
+            )
             .route(
                 "/{db_name}/{table_name}/{doc_id}",
                 web::delete().to(delete_document),
@@ -1140,7 +3370,8 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/{db_name}/{table_name}",
                 web::delete().to(delete_table),
             )
-            // Database deletion - MUST be last for /{db_name}
+            // Database info and deletion - MUST be last for /{db_name}
+            .route("/{db_name}", web::get().to(get_database))
             .route(
                 "/{db_name}",
                 web::delete().to(delete_database),
@@ -1155,3 +3386,67 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     )
     .route("/health", web::get().to(health));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    /// App state over a store in a fresh directory, without an embedder
+    async fn test_state() -> web::Data<AppState> {
+        let dir = std::env::temp_dir().join(format!("kuiperdb-api-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = DocumentStore::new(dir.to_string_lossy().to_string())
+            .await
+            .unwrap();
+        let webhooks = WebhookManager::new(store.get_global_pool().await.unwrap())
+            .await
+            .unwrap();
+
+        web::Data::new(AppState {
+            store: Arc::new(store),
+            embedder: None,
+            config: Arc::new(Config::default()),
+            webhooks: Arc::new(webhooks),
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_search_without_embedder_rejects_only_vector_type() {
+        let state = test_state().await;
+        let request = StoreDocumentRequest {
+            id: Some("a".to_string()),
+            content: "red apple".to_string(),
+            metadata: Default::default(),
+            tags: Vec::new(),
+            vectorize: false,
+            vector: None,
+            model: None,
+            expires_at: None,
+            ttl_seconds: None,
+        };
+        let doc = Document::from_request("db", "docs", request);
+        state.store.store_document("db", "docs", doc).await.unwrap();
+        let app = test::init_service(App::new().app_data(state).configure(configure)).await;
+
+        let search = |search_type: &str| {
+            test::TestRequest::post()
+                .uri("/db/db/docs/search")
+                .set_json(serde_json::json!({ "query": "apple", "type": search_type }))
+                .to_request()
+        };
+
+        let response = test::call_service(&app, search("vector")).await;
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "vector search requires an embedder");
+
+        for search_type in ["fulltext", "hybrid"] {
+            let response = test::call_service(&app, search(search_type)).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", search_type);
+            let body: serde_json::Value = test::read_body_json(response).await;
+            assert_eq!(body["results"][0]["id"], "a", "{}", search_type);
+            assert_eq!(body["total"], 1, "{}", search_type);
+        }
+    }
+}
@@ -4,7 +4,6 @@ use actix_files as fs;
 use anyhow::Result;
 use kuiperdb_core::*;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 mod api;
 mod telemetry;
@@ -43,35 +42,23 @@ async fn main() -> Result<()> {
     // Create data directory if it doesn't exist
     std::fs::create_dir_all(&config.data_dir)?;
 
-    // Initialize document store
+    // Initialize document store, shared by the API and every background job so
+    // they see the same vector indexes
     let mut store = store::DocumentStore::new(config.data_dir.clone()).await?;
     let store_pool = store.get_global_pool().await?;
 
     // Configure vector indexing
-    if config.features.vector_index {
-        let index_config = index::IndexConfig {
-            hnsw_m: config.vector_index.hnsw_m,
-            hnsw_ef_construction: config.vector_index.hnsw_ef_construction,
-            hnsw_ef_search: config.vector_index.hnsw_ef_search,
-        };
-
-        let enabled = match config.vector_index.mode.as_str() {
-            "always" => true,
-            "never" => false,
-            "auto" => true, // Will auto-enable at threshold
-            _ => true,
-        };
-
-        store.configure_indexing(enabled, config.vector_index.threshold, index_config);
-        tracing::info!(
-            "✓ Vector indexing configured (mode={}, threshold={})",
-            config.vector_index.mode,
-            config.vector_index.threshold
-        );
+    configure_vector_index(&mut store, &config);
+    if !config.backup.dir.is_empty() {
+        store.configure_backup_dir(config.backup.dir.clone());
     }
+    let store = Arc::new(store);
 
     tracing::info!("✓ Document store initialized");
 
+    // Webhook registrations and deliveries live in the global database
+    let webhooks = Arc::new(webhooks::WebhookManager::new(store_pool.clone()).await?);
+
     // Initialize embedder with cache
     let embedder: Option<Arc<embedder::OpenAIEmbedder>> = if config.features.embedding {
         let cache_opt = if config.features.embedding_cache {
@@ -104,11 +91,14 @@ async fn main() -> Result<()> {
     // Start background embedding worker if enabled
     let _worker_handle = if config.features.embedding_job {
         if let Some(ref emb) = embedder {
-            let worker = Arc::new(worker::BackgroundWorker::new(
-                Arc::new(Mutex::new(store)),
-                emb.clone(),
-                Arc::new(config.clone()),
-            ));
+            let worker = Arc::new(
+                worker::BackgroundWorker::new(
+                    store.clone(),
+                    emb.clone(),
+                    Arc::new(config.clone()),
+                )
+                .with_webhooks(webhooks.clone()),
+            );
 
             let handle = worker.start();
             tracing::info!("✓ Background embedding worker started");
@@ -121,14 +111,65 @@ async fn main() -> Result<()> {
         None
     };
 
-    // Create shared application state (note: store is duplicated for worker)
-    let store_for_api = store::DocumentStore::new(config.data_dir.clone()).await?;
+    // Create shared application state
     let app_state = web::Data::new(api::AppState {
-        store: Arc::new(Mutex::new(store_for_api)),
+        store: store.clone(),
         embedder: embedder.clone().map(|e| e as Arc<dyn embedder::Embedder>),
         config: Arc::new(config.clone()),
+        webhooks: webhooks.clone(),
     });
 
+    // Start background trash purger
+    let _purger_handle = Arc::new(purger::TrashPurger::new(
+        store.clone(),
+        Arc::new(config.clone()),
+    ))
+    .start();
+    tracing::info!(
+        "✓ Trash purger started (retention={} days)",
+        config.trash.retention_days
+    );
+
+    // Start background expiry reaper alongside it
+    let _reaper_handle = Arc::new(reaper::ExpiryReaper::new(
+        store.clone(),
+        Arc::new(config.clone()),
+    ))
+    .start();
+    tracing::info!(
+        "✓ Expiry reaper started (interval={}s)",
+        config.expiry.reap_interval_secs
+    );
+
+    // Start scheduled backups if enabled
+    let _backup_handle = if config.backup.scheduled {
+        let handle = Arc::new(backup::BackupScheduler::new(
+            store.clone(),
+            Arc::new(config.clone()),
+        ))
+        .start();
+        tracing::info!(
+            "✓ Backup scheduler started (interval={}s, keep={})",
+            config.backup.interval_secs,
+            config.backup.keep
+        );
+        Some(handle)
+    } else {
+        None
+    };
+
+    // Start webhook delivery
+    let _webhook_handle = Arc::new(webhooks::WebhookDispatcher::new(
+        webhooks.clone(),
+        store.clone(),
+        Arc::new(config.clone()),
+    ))
+    .start();
+    tracing::info!(
+        "✓ Webhook dispatcher started (max_attempts={})",
+        config.webhooks.max_attempts
+    );
+
     tracing::info!("kuiperdb initialized successfully");
 
     // Start HTTP server
@@ -168,6 +209,11 @@ async fn main() -> Result<()> {
     // Run server and handle shutdown
     server.await?;
 
+    // Save indexes changed since they were loaded or built
+    if let Err(e) = store.persist_indexes().await {
+        tracing::warn!("Failed to save vector indexes: {}", e);
+    }
+
     tracing::info!("Shutting down telemetry...");
     telemetry::shutdown_telemetry();
 
@@ -176,3 +222,33 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Apply the vector index settings from config to a document store
+fn configure_vector_index(store: &mut store::DocumentStore, config: &config::Config) {
+    if !config.features.vector_index {
+        return;
+    }
+
+    let index_config = index::IndexConfig {
+        hnsw_m: config.vector_index.hnsw_m,
+        hnsw_ef_construction: config.vector_index.hnsw_ef_construction,
+        hnsw_ef_search: config.vector_index.hnsw_ef_search,
+        compaction_threshold: config.vector_index.compaction_threshold,
+    };
+
+    let enabled = match config.vector_index.mode.as_str() {
+        "always" => true,
+        "never" => false,
+        "auto" => true, // Will auto-enable at threshold
+        _ => true,
+    };
+
+    store.configure_indexing(enabled, config.vector_index.threshold, index_config);
+    store.configure_index_persistence(config.vector_index.persist_to_disk);
+    tracing::info!(
+        "✓ Vector indexing configured (mode={}, threshold={}, persist={})",
+        config.vector_index.mode,
+        config.vector_index.threshold,
+        config.vector_index.persist_to_disk
+    );
+}